@@ -1,16 +1,17 @@
 #![deny(clippy::all)]
 #![warn(clippy::pedantic, clippy::nursery)]
 
-use std::collections::HashSet;
+use std::collections::{BTreeSet, HashSet};
 use std::convert::Infallible;
 use std::fmt::{Display, Formatter};
+use std::net::IpAddr;
 use std::num::{NonZeroU32, NonZeroU8};
 use std::str::FromStr;
 use std::string::FromUtf8Error;
 use chrono::{DateTime, FixedOffset, Local};
 use serde::{Deserialize, Deserializer, Serialize};
 use serde::de::Error;
-use strum::EnumString;
+use strum::{AsRefStr, EnumString};
 
 #[derive(Hash, Eq, PartialEq, Debug, Clone, Serialize, Deserialize)]
 pub struct ArticleId(pub String);
@@ -64,6 +65,8 @@ Note: `man curl(1)` said:
 #[derive(Eq, PartialEq, Clone)]
 pub enum CreateArticleError {
     Unauthorized,
+    /// トークンは正しいが、この操作が許可されていない
+    InsufficientScope,
     DuplicatedArticleId,
     InvalidUtf8,
 }
@@ -94,43 +97,83 @@ impl ArticleContent {
     #[must_use] pub fn into_inner(self) -> String {
         self.0
     }
+
+    #[must_use] pub fn as_str(&self) -> &str {
+        &self.0
+    }
 }
 
 pub enum GetArticleError {
     NoSuchArticleFoundById,
 }
 
+/// `Accept: application/json`で記事を取得したときの結果
+pub type GetArticleDocumentResult = Result<ArticleDocument, GetArticleError>;
+
+/// JSONで返す記事
+#[derive(Serialize, Clone, Eq, PartialEq, Debug)]
+pub struct ArticleDocument {
+    pub id: ArticleId,
+    pub content: String,
+    pub visibility: Visibility,
+    pub created_at: DateTime<Local>,
+    pub updated_at: DateTime<Local>,
+}
+
 pub type UpdateArticleResult = Result<(), UpdateArticleError>;
 
 pub enum UpdateArticleError {
     InvalidBearerToken,
+    /// トークンは正しいが、この操作が許可されていない
+    InsufficientScope,
     ArticleNotFoundById,
     InvalidByteSequenceForUtf8(FromUtf8Error),
+    /// `If-Match`または`If-Unmodified-Since`が満たされなかった
+    PreconditionFailed,
+    /// 前提条件が必須なのに与えられなかった
+    PreconditionRequired,
 }
 
 pub type DeleteArticleResult = Result<(), DeleteArticleError>;
 
 pub enum DeleteArticleError {
     InvalidBearerToken,
+    /// トークンは正しいが、この操作が許可されていない
+    InsufficientScope,
     NoSuchArticleFoundById,
+    /// `If-Match`または`If-Unmodified-Since`が満たされなかった
+    PreconditionFailed,
+    /// 前提条件が必須なのに与えられなかった
+    PreconditionRequired,
 }
 
 pub type ListArticleResult = Result<ListArticleResponse, Infallible>;
 
-#[derive(EnumString, Deserialize, Copy, Clone, Eq, PartialEq)]
+#[derive(EnumString, AsRefStr, Deserialize, Serialize, Copy, Clone, Eq, PartialEq, Debug, Default)]
 #[strum(serialize_all = "snake_case")]
 #[serde(rename_all = "snake_case")]
 pub enum ListArticleSortPolicy {
+    /// 作成日時の新しい順
+    #[default]
     Newest,
+    /// 作成日時の古い順
     Oldest,
+    /// 更新日時の新しい順
     RecentUpdated,
+    /// 更新日時の古い順
     LeastRecentlyUpdated,
 }
 
-#[derive(Deserialize, Copy, Clone, Eq, PartialEq)]
+#[derive(Deserialize, Clone, Eq, PartialEq)]
 pub struct ListArticleRequestQuery {
     #[serde(rename = "sort")]
     pub policy: Option<ListArticleSortPolicy>,
+    /// 1ページに含める記事の数。省略した場合は残りの記事を全て返す
+    pub limit: Option<NonZeroU32>,
+    /// 前のページの`Link`ヘッダーで与えられた、次のページの位置
+    pub cursor: Option<String>,
+    /// このタグが付いた記事だけを返す
+    pub tag: Option<String>,
 }
 
 #[derive(Eq, PartialEq, Debug, Serialize, Deserialize)]
@@ -153,7 +196,15 @@ pub type ChangeArticleIdRequestResult = Result<(), ChangeArticleIdError>;
 
 pub enum ChangeArticleIdError {
     Unauthorized,
+    /// トークンは正しいが、この操作が許可されていない
+    InsufficientScope,
     ArticleNotFoundById,
+    /// 変更後のIDの記事が既に存在する
+    DuplicatedArticleId,
+    /// `If-Match`または`If-Unmodified-Since`が満たされなかった
+    PreconditionFailed,
+    /// 前提条件が必須なのに与えられなかった
+    PreconditionRequired,
 }
 
 #[derive(Deserialize, Serialize, Clone, Eq, PartialEq, Debug)]
@@ -161,7 +212,62 @@ pub struct Article {
     pub created_at: DateTime<Local>,
     pub updated_at: DateTime<Local>,
     pub content: String,
-    pub visibility: Visibility
+    pub visibility: Visibility,
+    /// 記事を作成したアカウント。この記事の著者として扱われる。
+    /// この機能より前に作られた記事は`None`で、管理者だけが編集できる
+    #[serde(default)]
+    pub created_by: Option<String>,
+    /// 最後に本文を更新したアカウント
+    #[serde(default)]
+    pub updated_by: Option<String>,
+    /// 記事に付けられたタグ。順序に意味は無く、重複しない
+    #[serde(default)]
+    pub tags: BTreeSet<String>,
+    /// 記事の題名。本文には含まれない
+    #[serde(default)]
+    pub title: Option<String>,
+    /// 一覧などに表示する記事の要約
+    #[serde(default)]
+    pub summary: Option<String>,
+    /// 本文の言語。BCP 47の言語タグ (例: `ja`、`en-US`)
+    #[serde(default)]
+    pub lang: Option<String>,
+    /// この日時になったら公開する。公開範囲を手で変えると取り消される
+    #[serde(default)]
+    pub publish_at: Option<DateTime<Local>>,
+    /// 公開されている本文とは別に編集している下書き。トークンが無いと読めない
+    #[serde(default)]
+    pub draft: Option<ArticleDraft>,
+}
+
+impl Article {
+    #[must_use] pub fn metadata(&self) -> ArticleMetadata {
+        ArticleMetadata {
+            title: self.title.clone(),
+            summary: self.summary.clone(),
+            lang: self.lang.clone(),
+        }
+    }
+}
+
+/// 記事の下書き。公開するまで`content`は変わらない
+#[derive(Deserialize, Serialize, Clone, Eq, PartialEq, Debug)]
+pub struct ArticleDraft {
+    pub content: String,
+    pub updated_at: DateTime<Local>,
+    /// 最後に下書きを保存したアカウント
+    pub updated_by: Option<String>,
+}
+
+/// 本文とは別に編集できる、記事の題名・要約・言語
+#[derive(Deserialize, Serialize, Clone, Eq, PartialEq, Debug, Default)]
+pub struct ArticleMetadata {
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub summary: Option<String>,
+    #[serde(default)]
+    pub lang: Option<String>,
 }
 
 #[derive(Deserialize, Serialize, Copy, Clone, Eq, PartialEq, Debug)]
@@ -185,6 +291,11 @@ pub struct ArticleListResponseEntry {
     pub id: ArticleId,
     pub created_at: DateTime<Local>,
     pub updated_at: DateTime<Local>,
+    /// 記事の著者。[`Article::created_by`]と同じ
+    pub author: Option<String>,
+    pub title: Option<String>,
+    pub summary: Option<String>,
+    pub lang: Option<String>,
 }
 
 #[derive(Deserialize, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Debug)]
@@ -263,3 +374,368 @@ impl<'de> Deserialize<'de> for OneOriginTwoDigitsMonth {
 pub struct UpdateVisibilityPayload {
     pub visibility: Visibility,
 }
+
+#[derive(Deserialize, Serialize, Clone, Eq, PartialEq, Debug)]
+pub struct UpdateTagsPayload {
+    pub tags: BTreeSet<String>,
+}
+
+pub type UpdateTagsResult = Result<(), UpdateTagsError>;
+
+pub enum UpdateTagsError {
+    InvalidBearerToken,
+    /// トークンは正しいが、この操作が許可されていない
+    InsufficientScope,
+    NoSuchArticleFoundById,
+    /// 空のタグや、空白・制御文字を含むタグ、長すぎるタグ
+    InvalidTag(String),
+    TooManyTags {
+        max: usize,
+    },
+}
+
+pub type UpdateArticleMetadataResult = Result<(), UpdateArticleMetadataError>;
+
+pub enum UpdateArticleMetadataError {
+    InvalidBearerToken,
+    /// トークンは正しいが、この操作が許可されていない
+    InsufficientScope,
+    NoSuchArticleFoundById,
+    /// 空であるか、長すぎるか、制御文字を含む題名
+    InvalidTitle,
+    /// 空であるか、長すぎる要約
+    InvalidSummary,
+    /// BCP 47の言語タグとして読めない
+    InvalidLang(String),
+}
+
+/// タグと、そのタグが付いた公開記事の数
+#[derive(Deserialize, Serialize, Clone, Eq, PartialEq, Debug)]
+pub struct TagCount {
+    pub tag: String,
+    pub count: usize,
+}
+
+/// 記事の版番号。最初の版は1である。
+#[derive(Deserialize, Serialize, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+#[serde(transparent)]
+pub struct ArticleRevisionNumber(NonZeroU32);
+
+impl ArticleRevisionNumber {
+    pub const FIRST: Self = Self(NonZeroU32::MIN);
+
+    #[must_use] pub const fn into_inner(self) -> u32 {
+        self.0.get()
+    }
+
+    #[must_use] pub const fn next(self) -> Option<Self> {
+        match self.0.checked_add(1) {
+            Some(x) => Some(Self(x)),
+            None => None,
+        }
+    }
+}
+
+impl TryFrom<u32> for ArticleRevisionNumber {
+    type Error = ();
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        value.try_into().map(Self).map_err(|_| ())
+    }
+}
+
+impl Display for ArticleRevisionNumber {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(&self.0, f)
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Eq, PartialEq, Debug)]
+pub struct ArticleRevision {
+    pub revision: ArticleRevisionNumber,
+    pub created_at: DateTime<Local>,
+    pub content: String,
+}
+
+#[derive(Serialize, Clone, Eq, PartialEq, Debug)]
+pub struct ArticleRevisionSummary {
+    pub revision: ArticleRevisionNumber,
+    pub created_at: DateTime<Local>,
+}
+
+pub type ListArticleRevisionsResult = Result<Vec<ArticleRevisionSummary>, GetArticleError>;
+
+pub type GetArticleRevisionResult = Result<OwnedMetadata<ArticleSnapshotMetadata, ArticleSnapshot>, GetArticleRevisionError>;
+
+pub enum GetArticleRevisionError {
+    NoSuchArticleFoundById,
+    NoSuchRevisionFound,
+}
+
+#[derive(Deserialize, Copy, Clone, Eq, PartialEq, Debug)]
+pub struct ArticleRevisionDiffQuery {
+    pub from: ArticleRevisionNumber,
+    pub to: ArticleRevisionNumber,
+}
+
+/// unified diff形式の差分
+pub struct UnifiedDiff(pub String);
+
+pub type DiffArticleRevisionResult = Result<UnifiedDiff, GetArticleRevisionError>;
+
+pub type RevertArticleResult = Result<(), RevertArticleError>;
+
+pub enum RevertArticleError {
+    InvalidBearerToken,
+    /// トークンは正しいが、この操作が許可されていない
+    InsufficientScope,
+    ArticleNotFoundById,
+    NoSuchRevisionFound,
+}
+
+/// ゴミ箱に移された記事
+#[derive(Deserialize, Serialize, Clone, Eq, PartialEq, Debug)]
+pub struct TrashedArticle {
+    pub deleted_at: DateTime<Local>,
+    pub article: Article,
+}
+
+#[derive(Serialize)]
+pub struct TrashedArticleSummary {
+    pub id: ArticleId,
+    pub deleted_at: DateTime<Local>,
+}
+
+pub type ListTrashResult = Result<Vec<TrashedArticleSummary>, ListTrashError>;
+
+pub enum ListTrashError {
+    InvalidBearerToken,
+    /// トークンは正しいが、この操作が許可されていない
+    InsufficientScope,
+}
+
+#[derive(Deserialize, Serialize, Copy, Clone, Eq, PartialEq, Debug)]
+pub struct SchedulePublicationPayload {
+    pub publish_at: DateTime<FixedOffset>,
+}
+
+/// 公開の予約と、その取り消しの結果
+pub type SchedulePublicationResult = Result<(), SchedulePublicationError>;
+
+pub enum SchedulePublicationError {
+    InvalidBearerToken,
+    /// トークンは正しいが、この操作が許可されていない
+    InsufficientScope,
+    NoSuchArticleFoundById,
+    /// 非公開の記事だけを予約できる
+    NotPrivate,
+    /// 予約する日時が過去である
+    NotInFuture,
+    /// 取り消そうとした記事は予約されていない
+    NotScheduled,
+}
+
+#[derive(Serialize, Clone, Eq, PartialEq, Debug)]
+pub struct ScheduledPublication {
+    pub id: ArticleId,
+    pub publish_at: DateTime<Local>,
+}
+
+pub type ListScheduledPublicationsResult = Result<Vec<ScheduledPublication>, ListScheduledPublicationsError>;
+
+pub enum ListScheduledPublicationsError {
+    InvalidBearerToken,
+    /// トークンは正しいが、この操作が許可されていない
+    InsufficientScope,
+}
+
+pub type GetDraftResult = Result<OwnedMetadata<ArticleSnapshotMetadata, ArticleSnapshot>, DraftError>;
+
+/// 下書きの保存・破棄・公開の結果
+pub type UpdateDraftResult = Result<(), DraftError>;
+
+pub enum DraftError {
+    InvalidBearerToken,
+    /// トークンは正しいが、この操作が許可されていない
+    InsufficientScope,
+    NoSuchArticleFoundById,
+    /// 記事に下書きが無い
+    NoDraft,
+    InvalidByteSequenceForUtf8(FromUtf8Error),
+}
+
+pub type RestoreArticleResult = Result<(), RestoreArticleError>;
+
+pub enum RestoreArticleError {
+    InvalidBearerToken,
+    /// トークンは正しいが、この操作が許可されていない
+    InsufficientScope,
+    NoSuchArticleFoundInTrash,
+    DuplicatedArticleId,
+}
+
+pub type PurgeArticleResult = Result<(), PurgeArticleError>;
+
+pub enum PurgeArticleError {
+    InvalidBearerToken,
+    /// トークンは正しいが、この操作が許可されていない
+    InsufficientScope,
+    NoSuchArticleFoundInTrash,
+}
+
+/// 限定公開の記事の共有リンクのID。トークンとは異なり、秘密ではない。
+#[derive(Deserialize, Serialize, Hash, Eq, PartialEq, Clone, Debug)]
+#[serde(transparent)]
+pub struct ShareLinkId(pub String);
+
+impl Display for ShareLinkId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(&self.0, f)
+    }
+}
+
+/// 限定公開の記事を読むための共有リンク。トークンそのものは保存せず、SHA-256のダイジェストだけを持つ。
+#[derive(Deserialize, Serialize, Clone, Eq, PartialEq, Debug)]
+pub struct ArticleShareLink {
+    pub id: ShareLinkId,
+    /// トークンのSHA-256ダイジェストを16進数で表したもの
+    pub digest: String,
+    pub created_at: DateTime<Local>,
+    /// この日時以降は使えない。`None`のときは取り消されるまで使える
+    pub expires_at: Option<DateTime<Local>>,
+}
+
+#[derive(Deserialize, Copy, Clone, Eq, PartialEq, Debug)]
+pub struct CreateShareLinkQuery {
+    /// 作ってから使えなくなるまでの時間数。省略した場合は取り消されるまで使える
+    pub expires_in_hours: Option<u32>,
+}
+
+#[derive(Serialize)]
+pub struct CreatedShareLink {
+    pub id: ShareLinkId,
+    pub token: String,
+    pub expires_at: Option<DateTime<Local>>,
+}
+
+pub type CreateShareLinkResult = Result<CreatedShareLink, ShareLinkError>;
+
+pub type ListShareLinksResult = Result<Vec<ArticleShareLink>, ShareLinkError>;
+
+pub type RevokeShareLinkResult = Result<(), ShareLinkError>;
+
+pub enum ShareLinkError {
+    InvalidBearerToken,
+    /// トークンは正しいが、この操作が許可されていない
+    InsufficientScope,
+    NoSuchArticleFoundById,
+    NoSuchShareLinkFound,
+}
+
+#[derive(Deserialize, Clone, Eq, PartialEq, Debug)]
+pub struct GetArticleQuery {
+    /// 限定公開の記事の共有リンクのトークン
+    pub share: Option<String>,
+}
+
+/// 監査ログに記録される操作
+#[derive(Deserialize, Serialize, Copy, Clone, Eq, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    Create,
+    Update,
+    /// 記事をゴミ箱へ移す
+    Delete,
+    Rename,
+    ChangeVisibility,
+    ChangeTags,
+    ChangeMetadata,
+    /// 公開を予約する。予約の取り消しは`publish_at`が無いもの
+    SchedulePublication,
+    /// 予約された日時になり、サーバーが記事を公開した
+    Publish,
+    /// 下書きを保存する
+    SaveDraft,
+    /// 下書きを公開せずに捨てる
+    DiscardDraft,
+    /// 下書きを本文にする
+    PublishDraft,
+    /// 過去の版に戻す
+    Revert,
+    /// ゴミ箱から戻す
+    Restore,
+    /// ゴミ箱から完全に削除する
+    Purge,
+}
+
+/// 監査ログの1行。
+#[derive(Deserialize, Serialize, Clone, Eq, PartialEq, Debug)]
+pub struct AuditEntry {
+    pub at: DateTime<Local>,
+    pub action: AuditAction,
+    pub article_id: ArticleId,
+    /// `rename`の変更後のID
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub renamed_to: Option<ArticleId>,
+    /// 操作前の本文のSHA-256ダイジェストを16進数で表したもの。作成・IDの変更・予約による公開の場合は`None`
+    pub old_content_hash: Option<String>,
+    /// 操作後の本文のSHA-256ダイジェストを16進数で表したもの。削除・IDの変更・予約による公開の場合は`None`
+    pub new_content_hash: Option<String>,
+    /// `change_visibility`の変更後の公開範囲
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub visibility: Option<Visibility>,
+    /// `change_tags`の変更後のタグ
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tags: Option<BTreeSet<String>>,
+    /// `change_metadata`の変更後の題名・要約・言語
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<ArticleMetadata>,
+    /// `schedule_publication`で予約された公開日時
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub publish_at: Option<DateTime<Local>>,
+    /// 操作したトークンの名前
+    pub token: String,
+    pub client_ip: Option<IpAddr>,
+}
+
+#[derive(Deserialize, Copy, Clone, Eq, PartialEq, Debug)]
+pub struct GetAuditLogQuery {
+    /// この日時以降の操作だけを返す
+    pub since: Option<DateTime<FixedOffset>>,
+    /// この日時より前の操作だけを返す
+    pub until: Option<DateTime<FixedOffset>>,
+    /// 返す操作の数の上限。省略した場合は100
+    pub limit: Option<NonZeroU32>,
+}
+
+pub type GetAuditLogResult = Result<Vec<AuditEntry>, GetAuditLogError>;
+
+pub enum GetAuditLogError {
+    InvalidBearerToken,
+    /// トークンは正しいが、この操作が許可されていない
+    InsufficientScope,
+}
+
+#[derive(Deserialize, Clone, Eq, PartialEq, Debug)]
+pub struct SearchArticlesQuery {
+    pub q: String,
+    /// 返す記事の数の上限。省略した場合は20
+    pub limit: Option<NonZeroU32>,
+}
+
+/// 検索に一致した記事。
+#[derive(Serialize, Clone, PartialEq, Debug)]
+pub struct SearchHit {
+    pub id: ArticleId,
+    /// 大きいほど検索語によく一致している
+    pub score: f64,
+    /// 本文のうち検索語が最初に現れる付近の抜粋
+    pub snippet: Vec<SnippetFragment>,
+}
+
+/// 抜粋の一部。検索語に一致した部分は`highlighted`が`true`になる。
+#[derive(Serialize, Clone, Eq, PartialEq, Debug)]
+pub struct SnippetFragment {
+    pub text: String,
+    pub highlighted: bool,
+}
@@ -5,7 +5,6 @@
 #![allow(clippy::future_not_send)]
 #![deny(clippy::fallible_impl_from)]
 
-mod extension;
 mod service;
 mod migration;
 
@@ -43,12 +42,34 @@ async fn main() -> Result<()> {
             http_port,
             http_host,
             cloudflare_support,
-            read_bearer_token_from_stdin: _
+            read_bearer_token_from_stdin: _,
+            storage,
+            trash_retention_days,
+            keep_backups,
+            require_precondition,
         } => {
-            crate::service::rest::boot_http_server(http_port, &http_host, cloudflare_support).await
+            crate::service::rest::boot_http_server(http_port, &http_host, cloudflare_support, storage, trash_retention_days, keep_backups, require_precondition).await
         }
-        Commands::Import { file_path, article_id } => {
-            crate::service::import::import(&file_path, &article_id)
+        Commands::Import { file_path, article_id, storage, keep_backups } => {
+            crate::service::import::import(&file_path, &article_id, storage, keep_backups).await
+        }
+        Commands::Migrate { dry_run, keep_backups } => {
+            crate::service::migrate::migrate(dry_run, keep_backups)
+        }
+        Commands::RestoreBackup { backup, keep_backups } => {
+            crate::service::backup::restore_backup(backup.as_deref(), keep_backups)
+        }
+        Commands::HashToken { generate } => {
+            crate::service::hash_token::hash_token(generate)
+        }
+        Commands::AddToken { name, scopes, role } => {
+            crate::service::token::add_token(&name, scopes, role)
+        }
+        Commands::RotateToken { name, overlap_minutes } => {
+            crate::service::token::rotate_token(&name, overlap_minutes)
+        }
+        Commands::RevokeToken { name } => {
+            crate::service::token::revoke_token(&name)
         }
         Commands::Version { plain } => {
             const VERSION: &str = env!("CARGO_PKG_VERSION");
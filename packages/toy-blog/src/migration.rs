@@ -1,8 +1,50 @@
 //! data migration
+//!
+//! 記事ファイルは`version`フィールドにスキーマのバージョンを持つ。フィールドが無いものはバージョン0とみなす。
+//! 各移行手順は[`ArticleMigration`]として実装し、[`MigrationRegistry`]に古い順に登録する。
 
 use log::info;
 use serde_json::{Map, Value};
-use toy_blog_endpoint_model::Article;
+use thiserror::Error;
+
+/// フィールドの名前を静的に参照し、その名前をコンパイル時に確定する文字列リテラルへ焼く。
+/// 
+/// # Example
+/// 
+/// ```
+/// struct X {
+///     example: i32
+/// }
+/// 
+/// # fn main() {
+/// const Y: &str = name_of!(X::example);
+/// assert_eq!(Y, "example");
+/// # }
+/// ```
+macro_rules! name_of {
+    ($t:tt::$field:ident) => {
+        {
+            // #[allow(unused)]
+            let _ = |arg: &$t| { let _ = &arg.$field; };
+            stringify!($field)
+        }
+    };
+}
+
+mod tag_repr_version;
+mod access_level;
+mod tags;
+mod article_metadata;
+
+use tag_repr_version::AddTagVersion;
+use access_level::AddAccessLevel;
+use tags::AddTags;
+use article_metadata::AddArticleMetadata;
+
+/// このバージョンのtoy-blogが読み書きするスキーマのバージョン
+pub const CURRENT_SCHEMA_VERSION: u32 = 4;
+
+const VERSION_FIELD: &str = "version";
 
 trait SerdeJsonValueMoveExtension {
     ///
@@ -32,117 +74,225 @@ impl SerdeJsonValueMoveExtension for Value {
     }
 }
 
-trait ArticleMigration {
-    fn migrate(&self, raw_config: Value) -> Value;
-}
+/// 記事表の全ての記事に`key`が無ければ`default`で補う。すでにあるものは書き換えない。
+fn add_field_if_absent(raw_config: Value, key: &str, default: &Value) -> Result<Value, MigrationError> {
+    let mut top = raw_config
+        .into_object().map_err(|_| MigrationError::TopLevelIsNotObject)?;
 
-struct AddTagVersion;
+    let article_table = top
+        .get_mut("data").ok_or_else(|| MigrationError::MissingField { field: "data".to_string() })?
+        .as_object_mut().ok_or_else(|| MigrationError::UnexpectedType { field: "data".to_string(), expected: "an object" })?;
 
-impl ArticleMigration for AddTagVersion {
-    fn migrate(&self, raw_config: Value) -> Value {
-        let mut m = raw_config.into_object().expect("top level must be an object");
-        m.insert("version".to_string(), Value::from("1"));
+    for (article_id, article) in article_table.iter_mut() {
+        let article = article.as_object_mut()
+            .ok_or_else(|| MigrationError::UnexpectedType { field: format!("data.{article_id}"), expected: "an object" })?;
 
-        Value::from(m)
+        // すでに存在するならマイグレーションをスキップ
+        if !article.contains_key(key) {
+            info!("migration: {key} of {article_id} is now {default}.");
+            article.insert(key.to_string(), default.clone());
+        }
     }
+
+    Ok(Value::from(top))
 }
 
-/// フィールドの名前を静的に参照し、その名前をコンパイル時に確定する文字列リテラルへ焼く。
-/// 
-/// # Example
-/// 
-/// ```
-/// struct X {
-///     example: i32
-/// }
-/// 
-/// # fn main() {
-/// const Y: &str = name_of!(X::example);
-/// assert_eq!(Y, "example");
-/// # }
-/// ```
-macro_rules! name_of {
-    ($t:tt::$field:ident) => {
-        {
-            // #[allow(unused)]
-            let _ = |arg: &$t| arg.$field;
-            stringify!($field)
-        }
-    };
+#[derive(Debug, Error)]
+pub enum MigrationError {
+    #[error("top level must be an object")]
+    TopLevelIsNotObject,
+    #[error("schema version must be a non-negative integer, but got {_0}")]
+    InvalidVersion(Value),
+    #[error("schema version {found} is newer than {supported}, which this version supports")]
+    UnsupportedVersion {
+        found: u32,
+        supported: u32,
+    },
+    #[error("`{field}` is missing")]
+    MissingField {
+        field: String,
+    },
+    #[error("`{field}` must be {expected}")]
+    UnexpectedType {
+        field: String,
+        expected: &'static str,
+    },
 }
 
-struct AddAccessLevel;
-
-impl ArticleMigration for AddAccessLevel {
-    fn migrate(&self, raw_config: Value) -> Value {
-        let mut top = raw_config
-            .into_object().expect("top level must be an object");
-
-        #[allow(clippy::cast_possible_truncation)]
-        let version = match &top["version"] {
-            Value::Number(n) => n.as_u64().expect("schema version must be fit in u64") as i32,
-            Value::String(s) => s.parse::<i32>().expect("schema version must be fit in i32"),
-            otherwise => panic!("version must be number or string, but got {otherwise}")
-        };
-        
-        
-        if version >= 2 {
-            return Value::from(top)
-        }
+/// スキーマを一つ新しいバージョンへ移行する手順。
+pub trait ArticleMigration {
+    /// ログや`migrate --dry-run`で表示される名前
+    fn name(&self) -> &'static str;
 
-        top.insert("version".to_string(), Value::from(2));
+    /// この手順が受け付けるスキーマのバージョン
+    fn source_version(&self) -> u32;
 
-        let article_table = top
-            .get_mut("data").expect("article must exist")
-            .as_object_mut().expect("article table must be object");
+    /// この手順を適用した後のスキーマのバージョン
+    fn target_version(&self) -> u32;
 
-        article_table.iter_mut().for_each(|(key, article)| {
-            // すでに存在するならマイグレーションをスキップ
-            let article = article.as_object_mut().expect("article");
-            let field_to_add = name_of!(Article::visibility);
-            if !article.contains_key(field_to_add) {
-                info!("migration[AddAccess]: visibility of {key} is now public.");
-                article.insert(field_to_add.to_string(), Value::from("public"));
-            }
-        });
+    /// `raw_config`を移行する。`version`フィールドは[`MigrationRegistry`]が書き換えるので、ここで触る必要はない。
+    fn migrate(&self, raw_config: Value) -> Result<Value, MigrationError>;
+}
 
-        Value::from(top)
+/// 記事ファイルのスキーマのバージョンを読み取る。
+///
+/// 過去のバージョンは文字列で書き込んでいたため、数値として読めるならば文字列も受け付ける。
+pub fn schema_version(raw_config: &Value) -> Result<u32, MigrationError> {
+    let top = raw_config.as_object().ok_or(MigrationError::TopLevelIsNotObject)?;
+
+    match top.get(VERSION_FIELD) {
+        None => Ok(0),
+        Some(Value::Number(n)) => n.as_u64()
+            .and_then(|n| u32::try_from(n).ok())
+            .ok_or_else(|| MigrationError::InvalidVersion(Value::Number(n.clone()))),
+        Some(Value::String(s)) => s.parse()
+            .map_err(|_| MigrationError::InvalidVersion(Value::String(s.clone()))),
+        Some(otherwise) => Err(MigrationError::InvalidVersion(otherwise.clone())),
     }
 }
 
-pub fn migrate_article_repr(raw_article_table: Value) -> Value {
-    info!("migration: start");
-    let raw_article_table = AddTagVersion.migrate(raw_article_table);
+/// 移行手順を古い順に保持する。
+pub struct MigrationRegistry {
+    steps: Vec<Box<dyn ArticleMigration>>,
+}
 
-    let last = AddAccessLevel.migrate(raw_article_table);
-    info!("migration: finished");
-    last
+impl Default for MigrationRegistry {
+    fn default() -> Self {
+        Self {
+            steps: vec![
+                Box::new(AddTagVersion),
+                Box::new(AddAccessLevel),
+                Box::new(AddTags),
+                Box::new(AddArticleMetadata),
+            ],
+        }
+    }
+}
+
+impl MigrationRegistry {
+    /// `raw_config`に適用される手順を適用順に返す。最新のバージョンであれば空になる。
+    pub fn plan(&self, raw_config: &Value) -> Result<Vec<&dyn ArticleMigration>, MigrationError> {
+        let found = schema_version(raw_config)?;
+
+        if found > CURRENT_SCHEMA_VERSION {
+            return Err(MigrationError::UnsupportedVersion {
+                found,
+                supported: CURRENT_SCHEMA_VERSION,
+            })
+        }
+
+        Ok(self.steps.iter().map(AsRef::as_ref).skip_while(|step| step.source_version() < found).collect())
+    }
+
+    pub fn migrate(&self, raw_config: Value) -> Result<Value, MigrationError> {
+        info!("migration: start");
+        let plan = self.plan(&raw_config)?;
+
+        let migrated = plan.into_iter().try_fold(raw_config, |raw_config, step| {
+            info!("migration[{name}]: {from} -> {to}", name = step.name(), from = step.source_version(), to = step.target_version());
+            let mut top = step.migrate(raw_config)?
+                .into_object().map_err(|_| MigrationError::TopLevelIsNotObject)?;
+            top.insert(VERSION_FIELD.to_string(), Value::from(step.target_version()));
+
+            Ok(Value::from(top))
+        })?;
+
+        info!("migration: finished");
+        Ok(migrated)
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    // TODO: arbitrary data
-    use serde_json::json;
-    use crate::migration::{AddAccessLevel, ArticleMigration};
+    use serde_json::{json, Value};
+    use crate::migration::{ArticleMigration, CURRENT_SCHEMA_VERSION, MigrationError, MigrationRegistry, schema_version};
+    use crate::migration::access_level::AddAccessLevel;
+    use crate::migration::article_metadata::AddArticleMetadata;
+    use crate::migration::tag_repr_version::AddTagVersion;
+    use crate::migration::tags::AddTags;
+
+    /// 各手順に与える入力と、期待する出力
+    fn step_cases() -> Vec<(&'static dyn ArticleMigration, Value, Value)> {
+        vec![
+            (&AddTagVersion, json!({"data": {}}), json!({"data": {}})),
+            // 追加する
+            (&AddAccessLevel,
+                json!({"version": "1", "data": { "a": { "some_random_data_here": 42 } }}),
+                json!({"version": "1", "data": { "a": { "some_random_data_here": 42, "visibility": "public" } }})),
+            (&AddTags,
+                json!({"version": 2, "data": { "a": { "visibility": "public" } }}),
+                json!({"version": 2, "data": { "a": { "visibility": "public", "tags": [] } }})),
+            (&AddArticleMetadata,
+                json!({"version": 3, "data": { "a": { "content": "# Title\nbody", "tags": [] } }}),
+                json!({"version": 3, "data": { "a": { "content": "# Title\nbody", "tags": [], "title": null, "summary": null, "lang": null } }})),
+            // すでにあるものは書き換えない
+            (&AddAccessLevel,
+                json!({"version": 1, "data": { "a": { "visibility": "private" } }}),
+                json!({"version": 1, "data": { "a": { "visibility": "private" } }})),
+            (&AddTags,
+                json!({"version": 2, "data": { "a": { "tags": ["rust"] } }}),
+                json!({"version": 2, "data": { "a": { "tags": ["rust"] } }})),
+            (&AddArticleMetadata,
+                json!({"version": 3, "data": { "a": { "title": "Hello", "summary": null, "lang": "ja" } }}),
+                json!({"version": 3, "data": { "a": { "title": "Hello", "summary": null, "lang": "ja" } }})),
+        ]
+    }
+
+    #[test]
+    fn each_step_adds_only_absent_fields() {
+        for (step, input, expected) in step_cases() {
+            assert_eq!(step.migrate(input.clone()).unwrap(), expected, "{} on {input}", step.name());
+        }
+    }
+
+    #[test]
+    fn each_step_rejects_malformed_file() {
+        let steps: [&dyn ArticleMigration; 4] = [&AddTagVersion, &AddAccessLevel, &AddTags, &AddArticleMetadata];
+        for step in steps {
+            assert!(matches!(step.migrate(json!([])), Err(MigrationError::TopLevelIsNotObject)), "{}", step.name());
+        }
+
+        for step in &steps[1..] {
+            assert!(matches!(step.migrate(json!({"version": 1})), Err(MigrationError::MissingField { .. })), "{}", step.name());
+            assert!(matches!(step.migrate(json!({"version": 1, "data": []})), Err(MigrationError::UnexpectedType { .. })), "{}", step.name());
+            assert!(matches!(step.migrate(json!({"version": 1, "data": { "a": 42 }})), Err(MigrationError::UnexpectedType { .. })), "{}", step.name());
+        }
+    }
 
     #[test]
-    fn aal_early_return() {
-        let a = json!({"version": 2, "data": {}});
-        let b = AddAccessLevel.migrate(a);
-        assert_eq!(b, json!({"version": 2, "data": {}}));
+    fn steps_are_contiguous() {
+        let registry = MigrationRegistry::default();
+        let last = registry.steps.iter().try_fold(0, |version, step| {
+            (step.source_version() == version && step.target_version() == version + 1).then_some(step.target_version())
+        });
+
+        assert_eq!(last, Some(CURRENT_SCHEMA_VERSION));
     }
 
     #[test]
-    fn aal_add_visibility() {
-        let a = json!({"version": "1", "data": { "a": { "some_random_data_here": 42 } }});
-        let b = AddAccessLevel.migrate(a);
-        assert_eq!(b, json!({"version": 2, "data": { "a": { "some_random_data_here": 42, "visibility": "public" } }}));
+    fn unversioned_file_is_migrated_to_current() {
+        let a = json!({"data": { "a": { "some_random_data_here": 42 } }});
+        let b = MigrationRegistry::default().migrate(a).unwrap();
+        assert_eq!(b, json!({"version": 4, "data": { "a": { "some_random_data_here": 42, "visibility": "public", "tags": [], "title": null, "summary": null, "lang": null } }}));
     }
-    
+
+    #[test]
+    fn current_file_is_untouched() {
+        let a = json!({"version": 4, "data": {}});
+        assert!(MigrationRegistry::default().plan(&a).unwrap().is_empty());
+        assert_eq!(MigrationRegistry::default().migrate(a).unwrap(), json!({"version": 4, "data": {}}));
+    }
+
+    #[test]
+    fn newer_file_is_rejected() {
+        let res = MigrationRegistry::default().migrate(json!({"version": 5, "data": {}}));
+        assert!(matches!(res, Err(MigrationError::UnsupportedVersion { found: 5, .. })));
+    }
+
     #[test]
-    fn aal_do_not_destroy_visibility() {
-        let a = json!({"version": 2, "data": { "a": { "some_random_data_here": 42, "visibility": "private" } }});
-        let b = AddAccessLevel.migrate(a);
-        assert_eq!(b, json!({"version": 2, "data": { "a": { "some_random_data_here": 42, "visibility": "private" } }}));
+    fn legacy_string_version() {
+        assert_eq!(schema_version(&json!({"version": "1"})).unwrap(), 1);
+        assert!(matches!(schema_version(&json!({"version": "x"})), Err(MigrationError::InvalidVersion(_))));
+        assert!(matches!(schema_version(&json!([])), Err(MigrationError::TopLevelIsNotObject)));
     }
 }
@@ -0,0 +1,24 @@
+use serde_json::Value;
+use toy_blog_endpoint_model::Article;
+use crate::migration::{add_field_if_absent, ArticleMigration, MigrationError};
+
+/// 記事に`visibility`を導入する。既存の記事は全て公開されていたので`public`とする。
+pub struct AddAccessLevel;
+
+impl ArticleMigration for AddAccessLevel {
+    fn name(&self) -> &'static str {
+        "AddAccessLevel"
+    }
+
+    fn source_version(&self) -> u32 {
+        1
+    }
+
+    fn target_version(&self) -> u32 {
+        2
+    }
+
+    fn migrate(&self, raw_config: Value) -> Result<Value, MigrationError> {
+        add_field_if_absent(raw_config, name_of!(Article::visibility), &Value::from("public"))
+    }
+}
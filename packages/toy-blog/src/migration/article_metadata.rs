@@ -0,0 +1,26 @@
+use serde_json::Value;
+use toy_blog_endpoint_model::Article;
+use crate::migration::{add_field_if_absent, ArticleMigration, MigrationError};
+
+/// 記事に`title`・`summary`・`lang`を導入する。既存の記事の本文からは推測せず、全て`null`とする。
+pub struct AddArticleMetadata;
+
+impl ArticleMigration for AddArticleMetadata {
+    fn name(&self) -> &'static str {
+        "AddArticleMetadata"
+    }
+
+    fn source_version(&self) -> u32 {
+        3
+    }
+
+    fn target_version(&self) -> u32 {
+        4
+    }
+
+    fn migrate(&self, raw_config: Value) -> Result<Value, MigrationError> {
+        [name_of!(Article::title), name_of!(Article::summary), name_of!(Article::lang)]
+            .into_iter()
+            .try_fold(raw_config, |raw_config, field_to_add| add_field_if_absent(raw_config, field_to_add, &Value::Null))
+    }
+}
@@ -0,0 +1,26 @@
+use serde_json::Value;
+use crate::migration::{ArticleMigration, MigrationError, SerdeJsonValueMoveExtension};
+
+/// `version`フィールドを導入する。
+pub struct AddTagVersion;
+
+impl ArticleMigration for AddTagVersion {
+    fn name(&self) -> &'static str {
+        "AddTagVersion"
+    }
+
+    fn source_version(&self) -> u32 {
+        0
+    }
+
+    fn target_version(&self) -> u32 {
+        1
+    }
+
+    fn migrate(&self, raw_config: Value) -> Result<Value, MigrationError> {
+        // バージョンの書き込みはMigrationRegistryが行う
+        let m = raw_config.into_object().map_err(|_| MigrationError::TopLevelIsNotObject)?;
+
+        Ok(Value::from(m))
+    }
+}
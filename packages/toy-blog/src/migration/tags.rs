@@ -0,0 +1,24 @@
+use serde_json::Value;
+use toy_blog_endpoint_model::Article;
+use crate::migration::{add_field_if_absent, ArticleMigration, MigrationError};
+
+/// 記事に`tags`を導入する。既存の記事にはタグが付いていないので空とする。
+pub struct AddTags;
+
+impl ArticleMigration for AddTags {
+    fn name(&self) -> &'static str {
+        "AddTags"
+    }
+
+    fn source_version(&self) -> u32 {
+        2
+    }
+
+    fn target_version(&self) -> u32 {
+        3
+    }
+
+    fn migrate(&self, raw_config: Value) -> Result<Value, MigrationError> {
+        add_field_if_absent(raw_config, name_of!(Article::tags), &Value::Array(vec![]))
+    }
+}
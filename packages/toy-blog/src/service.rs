@@ -2,3 +2,8 @@ pub mod rest;
 mod persistence;
 pub mod cli;
 pub mod import;
+pub mod migrate;
+pub mod backup;
+pub mod hash_token;
+mod hash;
+pub mod token;
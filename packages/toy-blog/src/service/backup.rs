@@ -0,0 +1,178 @@
+use std::ffi::OsStr;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use anyhow::{bail, Context};
+use chrono::{DateTime, Utc};
+use fs2::FileExt;
+use log::info;
+use crate::service::persistence::replace_file_atomically;
+use crate::service::rest::ARTICLE_FILE_PATH;
+
+const BACKUP_DIRECTORY: &str = "backup";
+
+/// 保存しておくバックアップの既定の数
+pub const DEFAULT_BACKUPS_TO_KEEP: usize = 10;
+
+/// `path`のバックアップを置くディレクトリ。`data/article.json`であれば`data/backup`になる。
+fn backup_directory(path: &Path) -> PathBuf {
+    path.parent().unwrap_or_else(|| Path::new(".")).join(BACKUP_DIRECTORY)
+}
+
+/// `path`のバックアップの名前の接頭辞。`article.json`であれば`article-`になる。
+fn backup_prefix(path: &Path) -> String {
+    let stem = path.file_stem().and_then(OsStr::to_str).unwrap_or("article");
+
+    format!("{stem}-")
+}
+
+/// `path`のバックアップを古い順に返す。
+///
+/// バックアップの名前はUTCの日時を固定長で含むので、名前順に並べれば作成順になる。
+fn list_backups(path: &Path) -> Result<Vec<PathBuf>, anyhow::Error> {
+    let directory = backup_directory(path);
+    if !directory.exists() {
+        return Ok(vec![])
+    }
+
+    let prefix = backup_prefix(path);
+    let mut backups = std::fs::read_dir(&directory)
+        .with_context(|| format!("while reading {directory}", directory = directory.display()))?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    backups.retain(|backup| {
+        backup.extension() == Some(OsStr::new("json"))
+            && backup.file_name().and_then(OsStr::to_str).is_some_and(|name| name.starts_with(&prefix))
+    });
+    backups.sort();
+
+    Ok(backups)
+}
+
+fn create_backup_at(path: &Path, now: DateTime<Utc>, keep: usize) -> Result<PathBuf, anyhow::Error> {
+    let directory = backup_directory(path);
+    std::fs::create_dir_all(&directory)
+        .with_context(|| format!("while creating {directory}", directory = directory.display()))?;
+
+    let backup = directory.join(format!(
+        "{prefix}{timestamp}.json",
+        prefix = backup_prefix(path),
+        timestamp = now.format("%Y%m%dT%H%M%S%.6fZ"),
+    ));
+    let content = std::fs::read(path).with_context(|| format!("while reading {path}", path = path.display()))?;
+    replace_file_atomically(&backup, &content)
+        .with_context(|| format!("while writing {backup}", backup = backup.display()))?;
+    info!("backed up {path} to {backup}", path = path.display(), backup = backup.display());
+
+    prune_backups(path, keep)?;
+
+    Ok(backup)
+}
+
+/// `path`の現在の内容をバックアップし、古いバックアップを`keep`個まで減らす。
+pub(in crate::service) fn create_backup(path: &Path, keep: usize) -> Result<PathBuf, anyhow::Error> {
+    create_backup_at(path, Utc::now(), keep)
+}
+
+fn prune_backups(path: &Path, keep: usize) -> Result<(), anyhow::Error> {
+    let backups = list_backups(path)?;
+    let expired = backups.len().saturating_sub(keep);
+
+    for backup in &backups[..expired] {
+        std::fs::remove_file(backup).with_context(|| format!("while removing {backup}", backup = backup.display()))?;
+        info!("removed old backup {backup}", backup = backup.display());
+    }
+
+    Ok(())
+}
+
+fn restore_backup_of(path: &Path, backup: Option<&Path>, keep: usize) -> Result<PathBuf, anyhow::Error> {
+    let backup = if let Some(backup) = backup {
+        backup.to_path_buf()
+    } else {
+        let Some(latest) = list_backups(path)?.pop() else {
+            bail!("there is no backup of {path}", path = path.display())
+        };
+
+        latest
+    };
+
+    let content = std::fs::read(&backup).with_context(|| format!("while reading {backup}", backup = backup.display()))?;
+    serde_json::from_slice::<serde_json::Value>(&content)
+        .with_context(|| format!("{backup} is not a valid JSON", backup = backup.display()))?;
+
+    if path.exists() {
+        // サーバーが動いている間はファイルがロックされている
+        let current = File::open(path).with_context(|| format!("while opening {path}", path = path.display()))?;
+        if current.try_lock_exclusive().is_err() {
+            bail!("{path} is locked. Stop the server before restoring a backup.", path = path.display())
+        }
+        drop(current);
+
+        // 復元を取り消せるよう、現在の内容もバックアップしておく
+        create_backup(path, keep)?;
+    }
+
+    replace_file_atomically(path, &content).with_context(|| format!("while writing {path}", path = path.display()))?;
+
+    Ok(backup)
+}
+
+/// `restore-backup`サブコマンド。`backup`を省略した場合は最新のバックアップを復元する。
+pub fn restore_backup(backup: Option<&Path>, keep: usize) -> Result<(), anyhow::Error> {
+    let path = Path::new(ARTICLE_FILE_PATH);
+    let restored = restore_backup_of(path, backup, keep)?;
+    println!("restored {path} from {backup}.", path = path.display(), backup = restored.display());
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeDelta, Utc};
+    use crate::service::backup::{create_backup_at, list_backups, restore_backup_of};
+
+    #[test]
+    fn only_recent_backups_are_kept() {
+        let dir = tempfile::tempdir().expect("failed to initialize temporary directory");
+        let path = dir.path().join("article.json");
+        let now = Utc::now();
+
+        for i in 0..5 {
+            std::fs::write(&path, format!("{i}")).unwrap();
+            create_backup_at(&path, now + TimeDelta::seconds(i), 3).unwrap();
+        }
+
+        let contents = list_backups(&path).unwrap()
+            .iter()
+            .map(|backup| std::fs::read_to_string(backup).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(contents, ["2", "3", "4"]);
+    }
+
+    #[test]
+    fn restore_latest_backup() {
+        let dir = tempfile::tempdir().expect("failed to initialize temporary directory");
+        let path = dir.path().join("article.json");
+        let now = Utc::now() - TimeDelta::minutes(1);
+
+        std::fs::write(&path, r#"{"data":{}}"#).unwrap();
+        create_backup_at(&path, now, 10).unwrap();
+        std::fs::write(&path, r#"{"version":2,"data":{}}"#).unwrap();
+
+        restore_backup_of(&path, None, 10).unwrap();
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), r#"{"data":{}}"#);
+        // 復元前の内容もバックアップされている
+        let latest = list_backups(&path).unwrap().pop().unwrap();
+        assert_eq!(std::fs::read_to_string(latest).unwrap(), r#"{"version":2,"data":{}}"#);
+    }
+
+    #[test]
+    fn restore_without_backup_fails() {
+        let dir = tempfile::tempdir().expect("failed to initialize temporary directory");
+        let path = dir.path().join("article.json");
+        std::fs::write(&path, r#"{"data":{}}"#).unwrap();
+
+        assert!(restore_backup_of(&path, None, 10).is_err());
+    }
+}
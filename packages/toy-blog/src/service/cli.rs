@@ -1,6 +1,9 @@
 use std::path::PathBuf;
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
+use clap::builder::RangedU64ValueParser;
 use toy_blog_endpoint_model::ArticleId;
+use crate::service::backup::DEFAULT_BACKUPS_TO_KEEP;
+use crate::service::rest::auth::{Role, TokenScope};
 
 #[derive(Parser)]
 pub struct Args {
@@ -20,15 +23,87 @@ pub enum Commands {
         /// DEPRECATED, It will be removed in next major version. This switch is no-op.
         #[clap(long)]
         read_bearer_token_from_stdin: bool,
+        #[clap(long, value_enum, default_value_t = StorageBackend::Json)]
+        storage: StorageBackend,
+        /// ゴミ箱に移された記事を自動で完全に削除するまでの日数。指定しない場合は自動で削除しない。
+        #[clap(long)]
+        trash_retention_days: Option<u32>,
+        /// 記事ファイルの移行前に取るバックアップを何個まで残すか
+        #[clap(long, default_value_t = DEFAULT_BACKUPS_TO_KEEP, value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+        keep_backups: usize,
+        /// `If-Match`も`If-Unmodified-Since`も無い記事の更新・削除・公開範囲とIDの変更を拒否する
+        #[clap(long)]
+        require_precondition: bool,
     },
     Import {
         #[clap(long)]
         file_path: PathBuf,
         #[clap(long)]
         article_id: ArticleId,
+        #[clap(long, value_enum, default_value_t = StorageBackend::Json)]
+        storage: StorageBackend,
+        /// 記事ファイルの移行前に取るバックアップを何個まで残すか
+        #[clap(long, default_value_t = DEFAULT_BACKUPS_TO_KEEP, value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+        keep_backups: usize,
+    },
+    /// 記事ファイルを最新のスキーマへ移行する。サーバーの起動時にも自動で行われる。
+    Migrate {
+        /// 適用される手順と変更点を表示するだけで、書き込まない
+        #[clap(long)]
+        dry_run: bool,
+        /// 移行前に取るバックアップを何個まで残すか
+        #[clap(long, default_value_t = DEFAULT_BACKUPS_TO_KEEP, value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+        keep_backups: usize,
+    },
+    /// `data/backup`にあるバックアップから記事ファイルを復元する。サーバーを止めてから行うこと。
+    RestoreBackup {
+        /// 復元するバックアップ。省略した場合は最新のものを使う
+        #[clap(long)]
+        backup: Option<PathBuf>,
+        /// 復元前の記事ファイルもバックアップされる。その際にバックアップを何個まで残すか
+        #[clap(long, default_value_t = DEFAULT_BACKUPS_TO_KEEP, value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+        keep_backups: usize,
+    },
+    /// 標準入力から読んだトークンをソルト付きでハッシュし、`data/token.json`に書ける形式で出力する。
+    HashToken {
+        /// 標準入力から読む代わりに、ランダムなトークンを作る
+        #[clap(long)]
+        generate: bool,
+    },
+    /// ランダムなトークンを作り、`data/token.json`に加える。
+    AddToken {
+        #[clap(long)]
+        name: String,
+        /// 許可する操作。複数指定できる
+        #[clap(long = "scope")]
+        scopes: Vec<TokenScope>,
+        /// `author`は自分が作成した記事だけを、`admin`は全ての記事を変更できる
+        #[clap(long, default_value_t = Role::Author)]
+        role: Role,
+    },
+    /// トークンを新しく作り直す。古いトークンも`--overlap-minutes`の間は受け付けられる。
+    RotateToken {
+        #[clap(long)]
+        name: String,
+        #[clap(long, default_value_t = 60)]
+        overlap_minutes: u32,
+    },
+    /// トークンを`data/token.json`から取り除く。
+    RevokeToken {
+        #[clap(long)]
+        name: String,
     },
     Version {
         #[clap(long)]
         plain: bool,
     }
 }
+
+/// 記事の保存先
+#[derive(ValueEnum, Copy, Clone, Eq, PartialEq, Debug)]
+pub enum StorageBackend {
+    /// `data/article.json`
+    Json,
+    /// `data/article.sqlite3`
+    Sqlite,
+}
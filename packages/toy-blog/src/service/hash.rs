@@ -0,0 +1,23 @@
+use std::fmt::Write;
+use sha2::{Digest, Sha256};
+
+/// SHA-256ダイジェストを16進数で表したもの。
+pub(in crate::service) fn digest_of(value: &str) -> String {
+    let mut hex = String::with_capacity(64);
+    for byte in Sha256::digest(value.as_bytes()) {
+        write!(hex, "{byte:02x}").expect("writing to String never fails");
+    }
+
+    hex
+}
+
+#[cfg(test)]
+mod tests {
+    use super::digest_of;
+
+    #[test]
+    fn digest_is_lowercase_hex() {
+        assert_eq!(digest_of(""), "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+        assert_eq!(digest_of("secret"), "2bb80d537b1da3e38bd30361aa855686bde0eacd7162fef6a25fe97bf527a25b");
+    }
+}
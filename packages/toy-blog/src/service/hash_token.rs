@@ -0,0 +1,45 @@
+use std::io::stdin;
+use anyhow::bail;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use rand::RngCore;
+use rand::rngs::OsRng;
+
+/// `--generate`で作るトークンのバイト数
+const GENERATED_TOKEN_BYTES: usize = 32;
+
+/// 推測できないランダムなトークンを作る。
+pub(in crate::service) fn generate_token() -> String {
+    let mut bytes = [0; GENERATED_TOKEN_BYTES];
+    OsRng.fill_bytes(&mut bytes);
+
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// `hash-token`サブコマンド。
+pub fn hash_token(generate: bool) -> Result<(), anyhow::Error> {
+    let token = if generate {
+        let token = generate_token();
+        println!("token: {token}");
+
+        token
+    } else {
+        let mut buf = String::new();
+        stdin().read_line(&mut buf)?;
+        let token = buf.trim_end().to_string();
+        if token.is_empty() {
+            bail!("token must not be empty")
+        }
+
+        token
+    };
+
+    let hash = crate::service::rest::auth::hash_token(&token)?;
+    if generate {
+        println!("hash: {hash}");
+    } else {
+        println!("{hash}");
+    }
+
+    Ok(())
+}
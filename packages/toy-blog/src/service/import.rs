@@ -1,12 +1,11 @@
-use std::fs::File;
-use std::io::{BufReader, Read};
 use std::path::Path;
 use anyhow::bail;
 use log::{debug, info};
 use toy_blog_endpoint_model::{ArticleId, Visibility};
-use crate::service::rest::repository::GLOBAL_ARTICLE_REPOSITORY;
+use crate::service::cli::StorageBackend;
+use crate::service::rest::open_article_store;
 
-pub fn import(file_path: &Path, article_id: &ArticleId) -> Result<(), anyhow::Error> {
+pub async fn import(file_path: &Path, article_id: &ArticleId, storage: StorageBackend, keep_backups: usize) -> Result<(), anyhow::Error> {
     if !file_path.exists() {
         bail!("You can not import non-existent file")
     }
@@ -23,7 +22,7 @@ pub fn import(file_path: &Path, article_id: &ArticleId) -> Result<(), anyhow::Er
 
     match content {
         Ok(content) => {
-            GLOBAL_ARTICLE_REPOSITORY.get().expect("must be fully-initialized").create_entry(article_id, content, Visibility::Private)?;
+            open_article_store(storage, keep_backups).await?.create_entry(article_id, content, Visibility::Private, None)?;
             info!("Successfully imported as {article_id}.");
             Ok(())
         }
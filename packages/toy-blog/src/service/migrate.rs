@@ -0,0 +1,121 @@
+use std::fs::File;
+use std::path::Path;
+use anyhow::{bail, Context};
+use fs2::FileExt;
+use log::info;
+use serde_json::Value;
+use similar::TextDiff;
+use crate::migration::{MigrationRegistry, schema_version};
+use crate::service::backup::create_backup;
+use crate::service::persistence::replace_file_atomically;
+use crate::service::rest::ARTICLE_FILE_PATH;
+
+fn read_article_file(path: &Path) -> Result<Value, anyhow::Error> {
+    let file = File::open(path).with_context(|| format!("while opening {path}", path = path.display()))?;
+
+    serde_json::from_reader(file).with_context(|| format!("while parsing {path}", path = path.display()))
+}
+
+/// 記事ファイルを最新のスキーマへ移行して書き戻す。
+///
+/// 移行によって内容が変わる場合、書き戻す前に[`create_backup`]でバックアップを取り、古いものは`keep_backups`個まで減らす。
+/// 他のプロセス (サーバーなど) がファイルをロックしている場合は何もせずに失敗する。
+pub(in crate::service) fn migrate_article_file(path: &Path, keep_backups: usize) -> Result<(), anyhow::Error> {
+    // 書き戻すまでロックを持ち続け、移行の途中で書き換えられないようにする
+    let lock = File::open(path).with_context(|| format!("while opening {path}", path = path.display()))?;
+    if lock.try_lock_exclusive().is_err() {
+        bail!("{path} is locked. Stop the server before migrating.", path = path.display())
+    }
+
+    let raw_config = read_article_file(path)?;
+    let migrated_data = MigrationRegistry::default()
+        .migrate(raw_config.clone())
+        .context("while migrating article file")?;
+
+    if migrated_data == raw_config {
+        info!("already migrated");
+        return Ok(())
+    }
+
+    create_backup(path, keep_backups).context("while backing up article file")?;
+
+    let serialized = serde_json::to_vec(&migrated_data).context("failed to serialize config")?;
+    replace_file_atomically(path, &serialized).context("failed to write over existing config")?;
+    drop(lock);
+    info!("migrated");
+
+    Ok(())
+}
+
+/// `migrate`サブコマンド。`dry_run`が真ならば、適用される手順と変更点を表示するだけで書き込まない。
+pub fn migrate(dry_run: bool, keep_backups: usize) -> Result<(), anyhow::Error> {
+    let path = Path::new(ARTICLE_FILE_PATH);
+    let raw_config = read_article_file(path)?;
+    let registry = MigrationRegistry::default();
+    let plan = registry.plan(&raw_config)?;
+
+    if plan.is_empty() {
+        println!("{path} is up to date (schema version {version}).", path = path.display(), version = schema_version(&raw_config)?);
+        return Ok(())
+    }
+
+    println!("planned steps:");
+    for (i, step) in plan.iter().enumerate() {
+        println!("  {n}. {name} ({from} -> {to})", n = i + 1, name = step.name(), from = step.source_version(), to = step.target_version());
+    }
+
+    if !dry_run {
+        migrate_article_file(path, keep_backups)?;
+        println!("migrated {path}.", path = path.display());
+        return Ok(())
+    }
+
+    let before = serde_json::to_string_pretty(&raw_config)? + "\n";
+    let after = serde_json::to_string_pretty(&registry.migrate(raw_config)?)? + "\n";
+    let diff = TextDiff::from_lines(&before, &after)
+        .unified_diff()
+        .header(&format!("{path} (current)", path = path.display()), &format!("{path} (migrated)", path = path.display()))
+        .to_string();
+    print!("{diff}");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use fs2::FileExt;
+    use crate::service::migrate::migrate_article_file;
+
+    #[test]
+    fn backup_is_taken_only_when_changed() {
+        let dir = tempfile::tempdir().expect("failed to initialize temporary directory");
+        let path = dir.path().join("article.json");
+        let original = r#"{"data":{}}"#;
+        std::fs::write(&path, original).unwrap();
+
+        migrate_article_file(&path, 10).unwrap();
+        migrate_article_file(&path, 10).unwrap();
+
+        let backups = std::fs::read_dir(dir.path().join("backup")).unwrap()
+            .map(|entry| std::fs::read_to_string(entry.unwrap().path()).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(backups, [original]);
+        assert_ne!(std::fs::read_to_string(&path).unwrap(), original);
+    }
+
+    #[test]
+    fn locked_file_is_not_migrated() {
+        let dir = tempfile::tempdir().expect("failed to initialize temporary directory");
+        let path = dir.path().join("article.json");
+        let original = r#"{"data":{}}"#;
+        std::fs::write(&path, original).unwrap();
+
+        let held = File::open(&path).unwrap();
+        held.lock_exclusive().unwrap();
+
+        assert!(migrate_article_file(&path, 10).is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), original);
+        assert!(!dir.path().join("backup").exists());
+    }
+}
@@ -1,25 +1,169 @@
-use std::collections::{HashMap};
+use std::collections::{BTreeSet, HashMap};
 use std::fmt::Debug;
-use std::fs::File;
+use std::fs::{File, Metadata};
 use std::io::{BufReader, Read, Seek, SeekFrom, Write};
 use std::ops::{Deref, DerefMut};
 use std::path::{Path, PathBuf};
 use std::string::FromUtf8Error;
 use std::sync::{Arc, RwLock};
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::time::Duration;
-use chrono::Local;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, SystemTime};
+use chrono::{DateTime, Local};
 use fs2::FileExt;
-use log::{debug, error, info, trace};
+use log::{debug, error, info, trace, warn};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
-use toy_blog_endpoint_model::{ArticleId, FlatId, ListArticleResponse, Visibility};
+use crate::migration::CURRENT_SCHEMA_VERSION;
+use toy_blog_endpoint_model::{ArticleDraft, ArticleId, ArticleMetadata, ArticleRevision, ArticleRevisionNumber, ArticleShareLink, FlatId, ListArticleResponse, ShareLinkId, TrashedArticle, Visibility};
 
+#[cfg(test)]
+mod in_memory;
+mod search;
+mod sqlite;
+
+#[cfg(test)]
+pub use in_memory::InMemoryArticleStore;
+pub use search::SearchIndexedStore;
+pub use sqlite::SqliteArticleStore;
+
+/// 書き込みの直前に、その時点の記事に対して評価される条件。
+///
+/// 偽を返すと書き込みは[`PersistenceError::PreconditionFailed`]で失敗し、何も変わらない。
+pub type Expectation<'a> = &'a dyn Fn(&Article) -> bool;
+
+/// 記事が`expected`を満たさなければ[`PersistenceError::PreconditionFailed`]を返す。
+///
+/// 記事が無い場合は評価せず、記事が無いときの扱いは各操作に任せる。
+fn check_expectation(article: Option<&Article>, expected: Expectation) -> Result<(), PersistenceError> {
+    match article {
+        Some(article) if !expected(article) => Err(PersistenceError::PreconditionFailed),
+        _ => Ok(()),
+    }
+}
+
+/// 記事の保存先を抽象化したもの。
+///
+/// REST APIのハンドラーはこのトレイトを通してのみ記事を読み書きする。
+pub trait ArticleStore: Send + Sync {
+    /// `created_by`は記事を作成したアカウント。コマンドラインから取り込んだ記事などでは`None`になる。
+    fn create_entry(&self, article_id: &ArticleId, article_content: String, visibility: Visibility, created_by: Option<&str>) -> Result<(), PersistenceError>;
+
+    /// it is not guaranteed that the elements are sorted in particular order.
+    fn entries(&self) -> Result<Vec<(ArticleId, Article)>, PersistenceError>;
+
+    /// `from`以降`until`より前に作成された記事を返す。順序は[`Self::entries`]と同様に保証されない。
+    fn entries_created_between(&self, from: DateTime<Local>, until: DateTime<Local>) -> Result<Vec<(ArticleId, Article)>, PersistenceError> {
+        Ok(self.entries()?
+            .into_iter()
+            .filter(|(_, article)| from <= article.created_at && article.created_at < until)
+            .collect())
+    }
+
+    /// `updated_by`は本文を更新したアカウント。
+    fn update_entry(&self, article_id: &ArticleId, article_content: String, updated_by: Option<&str>) -> Result<(), PersistenceError> {
+        self.update_entry_if(article_id, &|_| true, article_content, updated_by)
+    }
+
+    /// [`Self::update_entry`]と同じだが、書き込む直前の記事が`expected`を満たす場合だけ更新する。
+    fn update_entry_if(&self, article_id: &ArticleId, expected: Expectation, article_content: String, updated_by: Option<&str>) -> Result<(), PersistenceError>;
+
+    /// 条件の無い[`Self::change_visibility_if`]。テストでだけ使う。
+    #[cfg(test)]
+    fn change_visibility(&self, article_id: &ArticleId, new_visibility: Visibility) -> Result<(), PersistenceError> {
+        self.change_visibility_if(article_id, &|_| true, new_visibility)
+    }
+
+    /// 書き込む直前の記事が`expected`を満たす場合だけ、公開範囲を変える。公開の予約は取り消される。
+    fn change_visibility_if(&self, article_id: &ArticleId, expected: Expectation, new_visibility: Visibility) -> Result<(), PersistenceError>;
+
+    /// 記事を`publish_at`に公開するよう予約する。`None`の場合は予約を取り消す。
+    fn schedule_publication(&self, article_id: &ArticleId, publish_at: Option<DateTime<Local>>) -> Result<(), PersistenceError>;
+
+    /// 予約された日時が`now`以前の記事を公開して予約を消し、公開した記事のIDを返す。
+    fn publish_due(&self, now: DateTime<Local>) -> Result<Vec<ArticleId>, PersistenceError>;
+
+    /// 記事のタグを`tags`で置き換える。本文の更新ではないので、更新日時や版は変わらない。
+    fn change_tags(&self, article_id: &ArticleId, tags: BTreeSet<String>) -> Result<(), PersistenceError>;
+
+    /// 記事の題名・要約・言語を`metadata`で置き換える。一覧に表示されるので更新日時は進めるが、版は増えない。
+    fn change_metadata(&self, article_id: &ArticleId, metadata: ArticleMetadata) -> Result<(), PersistenceError>;
+
+    /// 記事の下書きを`draft`で置き換える。`None`の場合は下書きを捨てる。本文・更新日時・版は変わらない。
+    fn change_draft(&self, article_id: &ArticleId, draft: Option<ArticleDraft>) -> Result<(), PersistenceError>;
+
+    /// 下書きを本文にして下書きを捨て、公開する前の記事を返す。[`Self::update_entry`]と同じく新しい版が作られ、更新日時が進む。
+    ///
+    /// 途中で失敗した場合は何も変わらない。記事か下書きが無いときは[`PersistenceError::AbsentValue`]を返す。
+    fn publish_draft(&self, article_id: &ArticleId, published_by: Option<&str>) -> Result<Article, PersistenceError>;
+
+    fn read_snapshot(&self, article_id: &ArticleId) -> Result<Article, PersistenceError>;
+
+    /// 記事の版を古い順に返す。[`Self::update_entry`]のたびに新しい版が追加される。
+    fn revisions(&self, article_id: &ArticleId) -> Result<Vec<ArticleRevision>, PersistenceError>;
+
+    fn exists(&self, article_id: &ArticleId) -> Result<bool, PersistenceError>;
+
+    /// 条件の無い[`Self::remove_if`]。テストでだけ使う。
+    #[cfg(test)]
+    fn remove(&self, article_id: &ArticleId) -> Result<(), PersistenceError> {
+        self.remove_if(article_id, &|_| true)
+    }
+
+    /// 書き込む直前の記事が`expected`を満たす場合だけ、記事をゴミ箱へ移す。記事の版もあわせて移され、[`Self::restore`]で元に戻せる。
+    /// 共有リンクは取り消される。
+    fn remove_if(&self, article_id: &ArticleId, expected: Expectation) -> Result<(), PersistenceError>;
+
+    /// 条件の無い[`Self::rename_if`]。テストでだけ使う。
+    #[cfg(test)]
+    fn rename(&self, old_id: &ArticleId, new_id: ArticleId) -> Result<(), PersistenceError> {
+        self.rename_if(old_id, &|_| true, new_id)
+    }
+
+    /// 書き込む直前の記事が`expected`を満たす場合だけ、記事のIDを変える。
+    /// `new_id`の記事が既に存在するときは[`PersistenceError::DuplicatedId`]を返し、何もしない。
+    fn rename_if(&self, old_id: &ArticleId, expected: Expectation, new_id: ArticleId) -> Result<(), PersistenceError>;
+
+    /// 記事の共有リンクを作成された順に返す。期限の切れたものも含む。
+    fn share_links(&self, article_id: &ArticleId) -> Result<Vec<ArticleShareLink>, PersistenceError>;
+
+    fn add_share_link(&self, article_id: &ArticleId, link: ArticleShareLink) -> Result<(), PersistenceError>;
+
+    /// 共有リンクを取り消す。記事か共有リンクが存在しないときは[`PersistenceError::AbsentValue`]を返す。
+    fn revoke_share_link(&self, article_id: &ArticleId, link_id: &ShareLinkId) -> Result<(), PersistenceError>;
+
+    /// ゴミ箱の中身を返す。順序は保証されない。
+    fn trashed_entries(&self) -> Result<Vec<(ArticleId, TrashedArticle)>, PersistenceError>;
+
+    /// ゴミ箱から記事を戻す。同じIDの記事が既に存在するときは[`PersistenceError::DuplicatedId`]を返し、何もしない。
+    fn restore(&self, article_id: &ArticleId) -> Result<(), PersistenceError>;
+
+    /// ゴミ箱から記事を完全に削除する。
+    fn purge(&self, article_id: &ArticleId) -> Result<(), PersistenceError>;
+
+    /// `threshold`より前にゴミ箱へ移された記事を完全に削除し、削除した数を返す。
+    fn purge_trashed_before(&self, threshold: DateTime<Local>) -> Result<usize, PersistenceError>;
+
+    /// このストアを経由せずに書き換えられた記事を読み込むたびに変わる値。
+    ///
+    /// [`SearchIndexedStore`]は、この値が変わったら索引を作り直す。外部での変更を検知しないストアでは変わらない。
+    fn external_generation(&self) -> Result<u64, PersistenceError> {
+        Ok(0)
+    }
+}
+
+/// JSONファイルに記事を格納する[`ArticleStore`]。
+///
+/// ファイルの内容はメモリ上にキャッシュされる。障害対応などでファイルが外部から書き換えられた場合に備え、
+/// 読み書きのたびに更新日時・サイズ・inodeを最後に確認したものと比べ、変わっていればファイルを読み直す。
 #[derive(Debug, Clone)]
 pub struct ArticleRepository {
     cache: Arc<RwLock<FileScheme>>,
     invalidated: Arc<AtomicBool>,
     file_lock: Arc<RwLock<NamedLockedFile>>,
+    /// 最後に読み込んだ、もしくは書き込んだ時点のファイルの状態
+    fingerprint: Arc<RwLock<FileFingerprint>>,
+    /// 外部で書き換えられたファイルを読み直した回数
+    reloads: Arc<AtomicU64>,
 }
 
 impl ArticleRepository {
@@ -47,162 +191,303 @@ impl ArticleRepository {
             path.as_ref().to_path_buf(),
             Duration::new(10, 0)
         ).await.expect("failed to lock file");
+        let fingerprint = FileFingerprint::of(&lock.file.metadata().expect("failed to read metadata"));
 
         Self {
             cache: Arc::new(RwLock::new(Self::parse_file_as_json_static(&mut lock).expect("crash"))),
             invalidated: Arc::new(AtomicBool::new(false)),
             file_lock: Arc::new(RwLock::new(lock)),
+            fingerprint: Arc::new(RwLock::new(fingerprint)),
+            reloads: Arc::new(AtomicU64::new(0)),
         }
     }
 
     fn invalidate(&self) {
-        self.invalidated.store(false, Ordering::SeqCst);
+        self.invalidated.store(true, Ordering::SeqCst);
     }
 
-    fn reconstruct_cache(&self) {
+    /// ファイルが最後に確認した時から変わっていれば、キャッシュを無効にする。
+    fn detect_external_change(&self, path: &Path) -> Result<(), PersistenceError> {
+        let current = FileFingerprint::of(&std::fs::metadata(path)?);
+
+        if current != *self.fingerprint.read().expect("fingerprint is poisoned") {
+            warn!("{path} was modified externally, reloading", path = path.display());
+            self.invalidate();
+        }
+
+        Ok(())
+    }
+
+    /// キャッシュが無効であればファイルを読み直す。
+    ///
+    /// エディタはファイルをリネームで置き換えることがあるため、開き直してロックを取り直す。
+    /// 読み直しに失敗した場合、キャッシュは無効なまま残り、次の呼び出しで再び読み直しが試みられる。
+    fn reconstruct_cache_locked(&self, locked: &mut NamedLockedFile) -> Result<(), PersistenceError> {
+        self.detect_external_change(&locked.path)?;
+
         if self.invalidated.load(Ordering::SeqCst) {
-            *self.cache.write().expect("cache lock is poisoned") =
-                Self::parse_file_as_json_static(&mut self.file_lock.write().expect("poisoned")).expect("crash");
+            locked.reopen()?;
+            let fingerprint = FileFingerprint::of(&locked.file.metadata()?);
+            let reloaded = Self::parse_file_as_json_static(locked)?;
+
+            *self.cache.write().expect("cache lock is poisoned") = reloaded;
+            *self.fingerprint.write().expect("fingerprint is poisoned") = fingerprint;
             self.invalidated.store(false, Ordering::SeqCst);
+            self.reloads.fetch_add(1, Ordering::SeqCst);
+            info!("reloaded {path}", path = locked.path.display());
         }
+
+        Ok(())
     }
 
-    fn save(&self) -> Result<(), serde_json::Error> {
-        let r = &mut **self.file_lock.write().expect("file lock is poisoned");
-        r.rewind().expect("seek");
-        serde_json::to_writer(
-            r,
-            &&*self.cache.read().expect("cache is poisoned")
-        )?;
-        debug!("saved");
+    /// 読み取りの前に呼ぶ。読み直せなかった場合は古いキャッシュを使い続ける。
+    fn reconstruct_cache(&self) {
+        let path = self.file_lock.read().expect("file lock is poisoned").path.clone();
+        if let Err(e) = self.detect_external_change(&path) {
+            error!("failed to inspect {path}, serving stale cache: {e}", path = path.display());
+            return
+        }
 
-        Ok(())
+        // 変更が無ければ、読み取り同士が書き込みロックで直列化されることはない
+        if !self.invalidated.load(Ordering::SeqCst) {
+            return
+        }
+
+        let mut locked = self.file_lock.write().expect("file lock is poisoned");
+        let reloaded = self.reconstruct_cache_locked(&mut locked);
+        drop(locked);
+
+        if let Err(e) = reloaded {
+            error!("failed to reload {path}, serving stale cache: {e}", path = path.display());
+        }
     }
 
-    pub fn create_entry(&self, article_id: &ArticleId, article_content: String, visibility: Visibility) -> Result<(), PersistenceError> {
-        self.invalidate();
+    /// ファイルの最新の内容に`operation`を適用して保存する。
+    ///
+    /// 外部での変更は適用前に読み込まれるので、それを上書きすることはない。
+    /// 適用してから保存するまでの間にファイルが書き換えられた場合は、保存せずに[`PersistenceError::Conflict`]を返す。
+    /// いずれの場合も、保存に失敗したときはキャッシュを変更しない。
+    fn modify<T>(&self, operation: impl FnOnce(&mut FileScheme) -> Result<T, PersistenceError>) -> Result<T, PersistenceError> {
+        // ファイルのロックを握っている間は他の書き込みが割り込まない
+        let mut locked = self.file_lock.write().expect("file lock is poisoned");
+        self.reconstruct_cache_locked(&mut locked)?;
 
-        let current_date = Local::now();
-        self.cache.write().expect("lock is poisoned").data.insert(article_id.clone(), Article {
-                created_at: current_date,
-                updated_at: current_date,
-                // visible: false,
-                content: article_content,
-                visibility,
-            });
+        let mut updated = self.cache.read().expect("cache is poisoned").clone();
+        let ret = operation(&mut updated)?;
+
+        self.save(&mut locked, &updated)?;
+        *self.cache.write().expect("cache is poisoned") = updated;
+        drop(locked);
+
+        Ok(ret)
+    }
+
+    /// 書き込み途中で落ちても元のファイルが壊れないよう、一時ファイルに書き出してから置き換える。
+    fn save(&self, locked: &mut NamedLockedFile, scheme: &FileScheme) -> Result<(), PersistenceError> {
+        let serialized = serde_json::to_vec(scheme)?;
 
+        let current = FileFingerprint::of(&std::fs::metadata(&locked.path)?);
+        if current != *self.fingerprint.read().expect("fingerprint is poisoned") {
+            warn!("{path} was modified while writing, refusing to overwrite", path = locked.path.display());
+            self.invalidate();
+            return Err(PersistenceError::Conflict)
+        }
+
+        let replaced = NamedLockedFile::replace_atomically(locked.path.clone(), |f| f.write_all(&serialized))?;
+        *self.fingerprint.write().expect("fingerprint is poisoned") = FileFingerprint::of(&replaced.file.metadata()?);
+        // 古いファイルのロックはここで外れるが、新しいファイルはリネームより前からロックされている
+        *locked = replaced;
+        debug!("saved");
 
-        self.save()?;
         Ok(())
     }
 
-    /// it is not guaranteed that the elements are sorted in particular order.
-    pub fn entries(&self) -> Vec<(ArticleId, Article)> {
-        self.reconstruct_cache();
+    fn parse_file_as_json_static(locked: &mut NamedLockedFile) -> Result<FileScheme, PersistenceError> {
+        locked.file.seek(SeekFrom::Start(0)).expect(".");
 
-        self.cache.read().expect("cache is poisoned").deref().data
-            .iter()
-            .map(|x| (x.0.clone(), x.1.clone()))
-            .collect()
+        let mut read_all = BufReader::new(&mut locked.file);
+        let mut buf = vec![];
+        read_all.read_to_end(&mut buf)?;
+        let got = String::from_utf8(buf)?;
+        debug!("parsed");
+        trace!("got: {got}", got = &got);
+
+        let j = serde_json::from_str(got.as_str()).inspect_err(|e| {
+            error!("{e}", e = e);
+        })?;
+
+        Ok(j)
     }
+}
 
-    pub fn update_entry(&self, article_id: &ArticleId, article_content: String) -> Result<(), PersistenceError> {
-        self.invalidate();
-        let mut m = self.cache.write().expect("cache is poisoned");
-        let find = m.data.get_mut(article_id);
-        let Some(article) = find else {
-            return Err(PersistenceError::AbsentValue)
-        };
+impl ArticleStore for ArticleRepository {
+    fn create_entry(&self, article_id: &ArticleId, article_content: String, visibility: Visibility, created_by: Option<&str>) -> Result<(), PersistenceError> {
+        self.modify(|scheme| {
+            scheme.create_entry(article_id, article_content, visibility, created_by);
+            Ok(())
+        })
+    }
 
-        let current_date = Local::now();
-        article.updated_at = current_date;
-        article.content = article_content;
-        drop(m);
+    fn entries(&self) -> Result<Vec<(ArticleId, Article)>, PersistenceError> {
+        self.reconstruct_cache();
 
-        self.save()?;
-        Ok(())
+        Ok(self.cache.read().expect("cache is poisoned").entries())
+    }
+
+    fn update_entry_if(&self, article_id: &ArticleId, expected: Expectation, article_content: String, updated_by: Option<&str>) -> Result<(), PersistenceError> {
+        self.modify(|scheme| {
+            check_expectation(scheme.data.get(article_id), expected)?;
+            scheme.update_entry(article_id, article_content, updated_by)
+        })
     }
 
     // TODO: there's bug that the engine cannot change its visibility.
-    pub fn change_visibility(&self, article_id: &ArticleId, new_visibility: Visibility) -> Result<(), PersistenceError> {
+    fn change_visibility_if(&self, article_id: &ArticleId, expected: Expectation, new_visibility: Visibility) -> Result<(), PersistenceError> {
         info!("calling change_visibility");
-        self.invalidate();
 
-        self.cache.write().expect("poisoned").deref_mut().data.get_mut(article_id)
-            .ok_or(PersistenceError::AbsentValue)?.visibility = new_visibility;
+        self.modify(|scheme| {
+            check_expectation(scheme.data.get(article_id), expected)?;
+            scheme.change_visibility(article_id, new_visibility)
+        })
+    }
 
-        self.save()?;
+    fn change_tags(&self, article_id: &ArticleId, tags: BTreeSet<String>) -> Result<(), PersistenceError> {
+        self.modify(|scheme| scheme.change_tags(article_id, tags))
+    }
 
-        Ok(())
+    fn schedule_publication(&self, article_id: &ArticleId, publish_at: Option<DateTime<Local>>) -> Result<(), PersistenceError> {
+        self.modify(|scheme| scheme.schedule_publication(article_id, publish_at))
     }
 
-    pub fn read_snapshot(&self, article_id: &ArticleId) -> Result<Article, PersistenceError> {
+    fn publish_due(&self, now: DateTime<Local>) -> Result<Vec<ArticleId>, PersistenceError> {
         self.reconstruct_cache();
 
-        let article = self.cache.read().expect("cache is poisoned").deref().data
-            .get(article_id)
-            .cloned()
-            .ok_or(PersistenceError::AbsentValue)?;
+        let due = self.cache.read().expect("cache is poisoned").data
+            .values()
+            .any(|article| article.publish_at.is_some_and(|publish_at| publish_at <= now));
+
+        // 定期的に呼ばれるので、公開する記事が無ければファイルに書き込まない
+        if !due {
+            return Ok(vec![])
+        }
 
-        Ok(article)
+        self.modify(|scheme| Ok(scheme.publish_due(now)))
     }
 
-    pub fn exists(&self, article_id: &ArticleId) -> bool {
+    fn change_metadata(&self, article_id: &ArticleId, metadata: ArticleMetadata) -> Result<(), PersistenceError> {
+        self.modify(|scheme| scheme.change_metadata(article_id, metadata))
+    }
+
+    fn change_draft(&self, article_id: &ArticleId, draft: Option<ArticleDraft>) -> Result<(), PersistenceError> {
+        self.modify(|scheme| scheme.change_draft(article_id, draft))
+    }
+
+    fn publish_draft(&self, article_id: &ArticleId, published_by: Option<&str>) -> Result<Article, PersistenceError> {
+        self.modify(|scheme| scheme.publish_draft(article_id, published_by))
+    }
+
+    fn read_snapshot(&self, article_id: &ArticleId) -> Result<Article, PersistenceError> {
+        self.reconstruct_cache();
+
+        self.cache.read().expect("cache is poisoned").read_snapshot(article_id)
+    }
+
+    fn revisions(&self, article_id: &ArticleId) -> Result<Vec<ArticleRevision>, PersistenceError> {
+        self.reconstruct_cache();
+
+        self.cache.read().expect("cache is poisoned").revisions(article_id)
+    }
+
+    fn exists(&self, article_id: &ArticleId) -> Result<bool, PersistenceError> {
         self.reconstruct_cache();
 
-        self.cache.read().expect("cache is poisoned").deref().data.contains_key(article_id)
+        Ok(self.cache.read().expect("cache is poisoned").data.contains_key(article_id))
     }
 
-    pub fn remove(&self, article_id: &ArticleId) -> Result<(), PersistenceError> {
+    fn remove_if(&self, article_id: &ArticleId, expected: Expectation) -> Result<(), PersistenceError> {
         info!("calling remove");
 
-        self.invalidate();
+        self.modify(|scheme| {
+            check_expectation(scheme.data.get(article_id), expected)?;
+            scheme.remove(article_id);
+            Ok(())
+        })
+    }
 
-        self.cache.write().expect("cache is poisoned").deref_mut().data.remove(article_id);
+    fn rename_if(&self, old_id: &ArticleId, expected: Expectation, new_id: ArticleId) -> Result<(), PersistenceError> {
+        self.modify(|scheme| {
+            check_expectation(scheme.data.get(old_id), expected)?;
+            scheme.rename(old_id, new_id)
+        })
+    }
 
-        self.save()?;
+    fn share_links(&self, article_id: &ArticleId) -> Result<Vec<ArticleShareLink>, PersistenceError> {
+        self.reconstruct_cache();
 
-        Ok(())
+        self.cache.read().expect("cache is poisoned").share_links(article_id)
     }
 
-    fn parse_file_as_json_static(locked: &mut NamedLockedFile) -> Result<FileScheme, PersistenceError> {
-        locked.file.seek(SeekFrom::Start(0)).expect(".");
+    fn add_share_link(&self, article_id: &ArticleId, link: ArticleShareLink) -> Result<(), PersistenceError> {
+        self.modify(|scheme| scheme.add_share_link(article_id, link))
+    }
 
-        let mut read_all = BufReader::new(&mut locked.file);
-        let mut buf = vec![];
-        read_all.read_to_end(&mut buf)?;
-        let got = String::from_utf8(buf)?;
-        debug!("parsed");
-        trace!("got: {got}", got = &got);
+    fn revoke_share_link(&self, article_id: &ArticleId, link_id: &ShareLinkId) -> Result<(), PersistenceError> {
+        self.modify(|scheme| scheme.revoke_share_link(article_id, link_id))
+    }
 
-        let j = serde_json::from_str(got.as_str()).inspect_err(|e| {
-            error!("{e}", e = e);
-        })?;
+    fn trashed_entries(&self) -> Result<Vec<(ArticleId, TrashedArticle)>, PersistenceError> {
+        self.reconstruct_cache();
 
-        Ok(j)
+        Ok(self.cache.read().expect("cache is poisoned").trashed_entries())
     }
 
-    pub fn rename(&self, old_id: &ArticleId, new_id: ArticleId) -> Result<(), PersistenceError> {
-        self.invalidate();
-        #[allow(clippy::significant_drop_tightening)]
-        let not_found_old_id = !self.cache.read().expect("cache is poisoned").deref().data.contains_key(&new_id);
+    fn restore(&self, article_id: &ArticleId) -> Result<(), PersistenceError> {
+        self.modify(|scheme| scheme.restore(article_id))
+    }
 
-        let mut exclusive_dummy_atomic_guard = self.cache.write().expect("cache is poisoned");
+    fn purge(&self, article_id: &ArticleId) -> Result<(), PersistenceError> {
+        self.modify(|scheme| scheme.purge(article_id))
+    }
 
-        if not_found_old_id {
-            let x = exclusive_dummy_atomic_guard.data.remove(old_id);
+    fn purge_trashed_before(&self, threshold: DateTime<Local>) -> Result<usize, PersistenceError> {
+        self.reconstruct_cache();
 
-            let Some(old_article) = x else {
-                return Err(PersistenceError::AbsentValue);
-            };
+        let expired = self.cache.read().expect("cache is poisoned").trashed_entries()
+            .iter()
+            .any(|(_, trashed)| trashed.deleted_at < threshold);
 
-            exclusive_dummy_atomic_guard.data.insert(new_id, old_article);
-            drop(exclusive_dummy_atomic_guard);
+        if !expired {
+            return Ok(0)
         }
 
-        self.save()?;
+        self.modify(|scheme| Ok(scheme.purge_trashed_before(threshold)))
+    }
 
-        Ok(())
+    fn external_generation(&self) -> Result<u64, PersistenceError> {
+        self.reconstruct_cache();
+
+        Ok(self.reloads.load(Ordering::SeqCst))
+    }
+}
+
+/// ファイルが書き換えられたかどうかを判定するための情報
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+struct FileFingerprint {
+    modified: Option<SystemTime>,
+    len: u64,
+    /// リネームで置き換えられた場合、更新日時とサイズが同じでもinodeは変わる
+    #[cfg(unix)]
+    inode: u64,
+}
+
+impl FileFingerprint {
+    fn of(metadata: &Metadata) -> Self {
+        Self {
+            modified: metadata.modified().ok(),
+            len: metadata.len(),
+            #[cfg(unix)]
+            inode: std::os::unix::fs::MetadataExt::ino(metadata),
+        }
     }
 }
 
@@ -215,26 +500,313 @@ pub enum PersistenceError {
     Utf8(#[from] FromUtf8Error),
     #[error("JSON deserialize error: {_0}")]
     JsonDeserialize(#[from] serde_json::Error),
+    #[error("SQLite: {_0}")]
+    Sqlite(#[from] rusqlite::Error),
     #[error("Absent value")]
     AbsentValue,
+    #[error("Duplicated ID")]
+    DuplicatedId,
+    #[error("the file was modified by another process during the write")]
+    Conflict,
+    #[error("the article does not satisfy the expectation")]
+    PreconditionFailed,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct FileScheme {
     // 設計上の選択: Vec<(ArticleId, Article)> でも機能要件は満たせるが、非効率な線形探索することになり遅い。
     // かといって、IDの先頭文字ごとにコレクションを分けるのはHashMapの再発明になるので不適当。
     // そこで、記事群全体をHashMapで囲うことで非効率な線形探索を避けている。
     // ハッシュ関数についての要件は現状存在しないためデフォルトのRandomStateを使う。こうすることによりHashDOSと
     // 呼ばれる細工されたクエリを処理しようとすることで計算資源を枯渇させる攻撃から自動的に守られる。
-    pub(in crate::service) data: HashMap<ArticleId, Article>
+    /// スキーマのバージョン。読み込む前に[`crate::migration`]で最新にしておくこと。
+    #[serde(default = "current_schema_version")]
+    version: u32,
+    pub(in crate::service) data: HashMap<ArticleId, Article>,
+    /// 記事の過去の版。この機能より前に作られ、一度も更新されていない記事は含まれない。
+    #[serde(default)]
+    revisions: HashMap<ArticleId, Vec<ArticleRevision>>,
+    /// ゴミ箱に移された記事。
+    #[serde(default)]
+    trash: HashMap<ArticleId, TrashEntry>,
+    /// 限定公開の記事の共有リンク。
+    #[serde(default)]
+    share_links: HashMap<ArticleId, Vec<ArticleShareLink>>,
+}
+
+const fn current_schema_version() -> u32 {
+    CURRENT_SCHEMA_VERSION
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct TrashEntry {
+    #[serde(flatten)]
+    trashed: TrashedArticle,
+    #[serde(default)]
+    revisions: Vec<ArticleRevision>,
 }
 
 impl FileScheme {
     fn empty() -> Self {
         Self {
+            version: CURRENT_SCHEMA_VERSION,
             data: HashMap::new(),
+            revisions: HashMap::new(),
+            trash: HashMap::new(),
+            share_links: HashMap::new(),
         }
     }
+
+    fn create_entry(&mut self, article_id: &ArticleId, article_content: String, visibility: Visibility, created_by: Option<&str>) {
+        let current_date = Local::now();
+        self.share_links.remove(article_id);
+        self.revisions.insert(article_id.clone(), vec![ArticleRevision {
+            revision: ArticleRevisionNumber::FIRST,
+            created_at: current_date,
+            content: article_content.clone(),
+        }]);
+        self.data.insert(article_id.clone(), Article {
+            created_at: current_date,
+            updated_at: current_date,
+            // visible: false,
+            content: article_content,
+            visibility,
+            created_by: created_by.map(ToString::to_string),
+            updated_by: created_by.map(ToString::to_string),
+            tags: BTreeSet::new(),
+            title: None,
+            summary: None,
+            lang: None,
+            publish_at: None,
+            draft: None,
+        });
+    }
+
+    fn entries(&self) -> Vec<(ArticleId, Article)> {
+        self.data
+            .iter()
+            .map(|x| (x.0.clone(), x.1.clone()))
+            .collect()
+    }
+
+    fn update_entry(&mut self, article_id: &ArticleId, article_content: String, updated_by: Option<&str>) -> Result<(), PersistenceError> {
+        let Some(article) = self.data.get_mut(article_id) else {
+            return Err(PersistenceError::AbsentValue)
+        };
+
+        let history = self.revisions.entry(article_id.clone()).or_default();
+        if history.is_empty() {
+            history.push(ArticleRevision {
+                revision: ArticleRevisionNumber::FIRST,
+                created_at: article.updated_at,
+                content: article.content.clone(),
+            });
+        }
+
+        let current_date = Local::now();
+        let revision = history.last().and_then(|x| x.revision.next()).expect("revision number overflow");
+        history.push(ArticleRevision {
+            revision,
+            created_at: current_date,
+            content: article_content.clone(),
+        });
+
+        article.updated_at = current_date;
+        article.content = article_content;
+        article.updated_by = updated_by.map(ToString::to_string);
+
+        Ok(())
+    }
+
+    fn change_visibility(&mut self, article_id: &ArticleId, new_visibility: Visibility) -> Result<(), PersistenceError> {
+        let article = self.data.get_mut(article_id).ok_or(PersistenceError::AbsentValue)?;
+        article.visibility = new_visibility;
+        article.publish_at = None;
+
+        Ok(())
+    }
+
+    fn schedule_publication(&mut self, article_id: &ArticleId, publish_at: Option<DateTime<Local>>) -> Result<(), PersistenceError> {
+        self.data.get_mut(article_id)
+            .ok_or(PersistenceError::AbsentValue)?.publish_at = publish_at;
+
+        Ok(())
+    }
+
+    fn publish_due(&mut self, now: DateTime<Local>) -> Vec<ArticleId> {
+        self.data.iter_mut()
+            .filter(|(_, article)| article.publish_at.is_some_and(|publish_at| publish_at <= now))
+            .map(|(id, article)| {
+                article.visibility = Visibility::Public;
+                article.publish_at = None;
+                id.clone()
+            })
+            .collect()
+    }
+
+    fn change_tags(&mut self, article_id: &ArticleId, tags: BTreeSet<String>) -> Result<(), PersistenceError> {
+        self.data.get_mut(article_id)
+            .ok_or(PersistenceError::AbsentValue)?.tags = tags;
+
+        Ok(())
+    }
+
+    fn change_metadata(&mut self, article_id: &ArticleId, metadata: ArticleMetadata) -> Result<(), PersistenceError> {
+        let article = self.data.get_mut(article_id).ok_or(PersistenceError::AbsentValue)?;
+        let ArticleMetadata { title, summary, lang } = metadata;
+        article.title = title;
+        article.summary = summary;
+        article.lang = lang;
+        article.updated_at = Local::now();
+
+        Ok(())
+    }
+
+    fn change_draft(&mut self, article_id: &ArticleId, draft: Option<ArticleDraft>) -> Result<(), PersistenceError> {
+        self.data.get_mut(article_id)
+            .ok_or(PersistenceError::AbsentValue)?.draft = draft;
+
+        Ok(())
+    }
+
+    fn publish_draft(&mut self, article_id: &ArticleId, published_by: Option<&str>) -> Result<Article, PersistenceError> {
+        let previous = self.read_snapshot(article_id)?;
+        let Some(draft) = &previous.draft else {
+            return Err(PersistenceError::AbsentValue)
+        };
+
+        self.update_entry(article_id, draft.content.clone(), published_by)?;
+        self.change_draft(article_id, None)?;
+
+        Ok(previous)
+    }
+
+    fn read_snapshot(&self, article_id: &ArticleId) -> Result<Article, PersistenceError> {
+        self.data
+            .get(article_id)
+            .cloned()
+            .ok_or(PersistenceError::AbsentValue)
+    }
+
+    fn revisions(&self, article_id: &ArticleId) -> Result<Vec<ArticleRevision>, PersistenceError> {
+        let article = self.data.get(article_id).ok_or(PersistenceError::AbsentValue)?;
+
+        match self.revisions.get(article_id) {
+            Some(history) if !history.is_empty() => Ok(history.clone()),
+            // 履歴を持たない記事は現在の本文を最初の版とみなす
+            _ => Ok(vec![ArticleRevision {
+                revision: ArticleRevisionNumber::FIRST,
+                created_at: article.updated_at,
+                content: article.content.clone(),
+            }]),
+        }
+    }
+
+    fn remove(&mut self, article_id: &ArticleId) {
+        let Some(article) = self.data.remove(article_id) else {
+            return
+        };
+        self.share_links.remove(article_id);
+
+        // 以前に同じIDでゴミ箱へ移された記事は上書きされる
+        self.trash.insert(article_id.clone(), TrashEntry {
+            trashed: TrashedArticle {
+                deleted_at: Local::now(),
+                article,
+            },
+            revisions: self.revisions.remove(article_id).unwrap_or_default(),
+        });
+    }
+
+    fn rename(&mut self, old_id: &ArticleId, new_id: ArticleId) -> Result<(), PersistenceError> {
+        if self.data.contains_key(&new_id) {
+            return Err(PersistenceError::DuplicatedId)
+        }
+
+        let Some(old_article) = self.data.remove(old_id) else {
+            return Err(PersistenceError::AbsentValue);
+        };
+
+        if let Some(history) = self.revisions.remove(old_id) {
+            self.revisions.insert(new_id.clone(), history);
+        }
+        if let Some(links) = self.share_links.remove(old_id) {
+            self.share_links.insert(new_id.clone(), links);
+        }
+        self.data.insert(new_id, old_article);
+
+        Ok(())
+    }
+
+    fn share_links(&self, article_id: &ArticleId) -> Result<Vec<ArticleShareLink>, PersistenceError> {
+        if !self.data.contains_key(article_id) {
+            return Err(PersistenceError::AbsentValue)
+        }
+
+        Ok(self.share_links.get(article_id).cloned().unwrap_or_default())
+    }
+
+    fn add_share_link(&mut self, article_id: &ArticleId, link: ArticleShareLink) -> Result<(), PersistenceError> {
+        if !self.data.contains_key(article_id) {
+            return Err(PersistenceError::AbsentValue)
+        }
+
+        self.share_links.entry(article_id.clone()).or_default().push(link);
+
+        Ok(())
+    }
+
+    fn revoke_share_link(&mut self, article_id: &ArticleId, link_id: &ShareLinkId) -> Result<(), PersistenceError> {
+        let links = self.share_links.get_mut(article_id).ok_or(PersistenceError::AbsentValue)?;
+        let before = links.len();
+        links.retain(|x| &x.id != link_id);
+        if links.len() == before {
+            return Err(PersistenceError::AbsentValue)
+        }
+
+        if links.is_empty() {
+            self.share_links.remove(article_id);
+        }
+
+        Ok(())
+    }
+
+    fn trashed_entries(&self) -> Vec<(ArticleId, TrashedArticle)> {
+        self.trash
+            .iter()
+            .map(|(id, entry)| (id.clone(), entry.trashed.clone()))
+            .collect()
+    }
+
+    fn restore(&mut self, article_id: &ArticleId) -> Result<(), PersistenceError> {
+        if self.data.contains_key(article_id) {
+            return Err(PersistenceError::DuplicatedId)
+        }
+
+        let Some(entry) = self.trash.remove(article_id) else {
+            return Err(PersistenceError::AbsentValue)
+        };
+
+        if entry.revisions.is_empty() {
+            self.revisions.remove(article_id);
+        } else {
+            self.revisions.insert(article_id.clone(), entry.revisions);
+        }
+        self.data.insert(article_id.clone(), entry.trashed.article);
+
+        Ok(())
+    }
+
+    fn purge(&mut self, article_id: &ArticleId) -> Result<(), PersistenceError> {
+        self.trash.remove(article_id).map(|_| ()).ok_or(PersistenceError::AbsentValue)
+    }
+
+    fn purge_trashed_before(&mut self, threshold: DateTime<Local>) -> usize {
+        let before = self.trash.len();
+        self.trash.retain(|_, entry| entry.trashed.deleted_at >= threshold);
+
+        before - self.trash.len()
+    }
 }
 
 impl From<FileScheme> for ListArticleResponse {
@@ -264,14 +836,82 @@ impl NamedLockedFile {
                 file: f,
                 path,
             })
-        }).into_inner().await
+        }).await.map_err(|_| FileLockError::Timeout)?
+    }
+
+    /// `path`を開き直してロックを取り直す。外部でファイルが置き換えられた場合に使う。
+    ///
+    /// ロックを取れない場合は待たずに失敗し、元のファイルのロックを取り直す。
+    fn reopen(&mut self) -> std::io::Result<()> {
+        let f = File::options().read(true).write(true).open(&self.path)?;
+        // 同じファイルであっても別のハンドルからはロックを取れないので、先に外しておく
+        self.file.unlock()?;
+
+        if let Err(e) = f.try_lock_exclusive() {
+            if let Err(relock) = self.file.try_lock_exclusive() {
+                error!("unable to relock article entry ({path}): {relock:?}", path = self.path.display());
+            }
+
+            return Err(e)
+        }
+
+        self.file = f;
+
+        Ok(())
+    }
+
+    /// `path`と同じディレクトリに一時ファイルを作り、`write`で書き込んでfsyncした後に`path`へリネームする。
+    ///
+    /// 一時ファイルはリネームより前に排他ロックされるので、`path`がロックされていない瞬間はない。
+    /// `write`が失敗した場合は`path`に触れず、一時ファイルも削除される。
+    fn replace_atomically(path: PathBuf, write: impl FnOnce(&mut File) -> std::io::Result<()>) -> std::io::Result<Self> {
+        let directory = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+
+        let mut temporary = tempfile::Builder::new()
+            .prefix(".toy-blog-")
+            .suffix(".tmp")
+            .tempfile_in(&directory)?;
+        temporary.as_file().lock_exclusive()?;
+        if let Ok(metadata) = std::fs::metadata(&path) {
+            temporary.as_file().set_permissions(metadata.permissions())?;
+        }
+
+        write(temporary.as_file_mut())?;
+        temporary.as_file().sync_all()?;
+
+        let file = temporary.persist(&path).map_err(|e| e.error)?;
+        sync_directory(&directory)?;
+
+        Ok(Self {
+            file,
+            path,
+        })
     }
 }
 
+/// `path`を`content`で置き換える。書き込み途中で落ちても、`path`は元の内容か`content`のどちらかになる。
+pub(in crate::service) fn replace_file_atomically(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    NamedLockedFile::replace_atomically(path.to_path_buf(), |f| f.write_all(content)).map(drop)
+}
+
+#[cfg(unix)]
+fn sync_directory(directory: &Path) -> std::io::Result<()> {
+    File::open(directory)?.sync_all()
+}
+
+#[cfg(not(unix))]
+#[allow(clippy::unnecessary_wraps)]
+fn sync_directory(_: &Path) -> std::io::Result<()> {
+    Ok(())
+}
+
 impl Drop for NamedLockedFile {
     fn drop(&mut self) {
         if let Some(x) = self.file.unlock().err() {
-            error!("unable to unlock article entry, ignoring error. detail: {x:?}");
+            error!("unable to unlock article entry ({path}), ignoring error. detail: {x:?}", path = self.path.display());
         }
     }
 }
@@ -301,8 +941,12 @@ enum FileLockError {
 #[cfg(test)]
 mod tests {
     use fern::colors::ColoredLevelConfig;
-    use toy_blog_endpoint_model::{ArticleId, Visibility};
-    use crate::service::persistence::ArticleRepository;
+    use toy_blog_endpoint_model::{Article, ArticleId, Visibility};
+    use std::fs::File;
+    use std::io::Write;
+    use fs2::FileExt;
+    use std::sync::Arc;
+    use crate::service::persistence::{ArticleRepository, ArticleStore, NamedLockedFile, PersistenceError, SearchIndexedStore};
 
     fn setup_logger() -> anyhow::Result<()> {
         let colors = ColoredLevelConfig::new();
@@ -340,14 +984,216 @@ mod tests {
                 let m = tempfile::NamedTempFile::new().expect("failed to initialize temporary file");
                 ArticleRepository::init(m.path());
                 let temp_repo = ArticleRepository::new(m.path()).await;
-                temp_repo.create_entry(&ArticleId::new("12345".to_string()), "12345 Hello".to_string(), Visibility::Private).expect("failed to save");
-                temp_repo.create_entry(&ArticleId::new("23456".to_string()), "23456 Hello".to_string(), Visibility::Private).expect("failed to save");
+                temp_repo.create_entry(&ArticleId::new("12345".to_string()), "12345 Hello".to_string(), Visibility::Private, None).expect("failed to save");
+                temp_repo.create_entry(&ArticleId::new("23456".to_string()), "23456 Hello".to_string(), Visibility::Private, None).expect("failed to save");
                 drop(temp_repo);
                 let temp_repo = ArticleRepository::new(m.path()).await;
-                let y = temp_repo.entries().iter().find(|x| x.0 == ArticleId::new("12345".to_string())).expect("12345").1.content == "12345 Hello";
+                let y = temp_repo.entries().unwrap().iter().find(|x| x.0 == ArticleId::new("12345".to_string())).expect("12345").1.content == "12345 Hello";
                 assert!(y);
-                let y = temp_repo.entries().iter().find(|x| x.0 == ArticleId::new("23456".to_string())).expect("23456").1.content == "23456 Hello";
+                let y = temp_repo.entries().unwrap().iter().find(|x| x.0 == ArticleId::new("23456".to_string())).expect("23456").1.content == "23456 Hello";
                 assert!(y);
             });
     }
+
+    #[test]
+    fn interrupted_write_does_not_touch_original_file() {
+        let dir = tempfile::tempdir().expect("failed to initialize temporary directory");
+        let path = dir.path().join("article.json");
+        std::fs::write(&path, r#"{"data":{}}"#).unwrap();
+
+        let res = NamedLockedFile::replace_atomically(path.clone(), |f| {
+            f.write_all(br#"{"data":{"12345":"#)?;
+            Err(std::io::Error::other("simulated crash"))
+        });
+
+        assert!(res.is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), r#"{"data":{}}"#);
+        // 一時ファイルは残らない
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn shorter_document_does_not_leave_trailing_garbage() {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(async {
+                let dir = tempfile::tempdir().expect("failed to initialize temporary directory");
+                let path = dir.path().join("article.json");
+                let temp_repo = ArticleRepository::new(&path).await;
+                let long = ArticleId::new("long".to_string());
+                temp_repo.create_entry(&long, "long content ".repeat(100), Visibility::Private, None).expect("failed to save");
+                temp_repo.create_entry(&ArticleId::new("short".to_string()), "short".to_string(), Visibility::Private, None).expect("failed to save");
+                temp_repo.remove(&long).expect("failed to save");
+                drop(temp_repo);
+
+                let temp_repo = ArticleRepository::new(&path).await;
+                assert_eq!(temp_repo.entries().unwrap().len(), 1);
+            });
+    }
+
+    #[test]
+    fn file_is_still_locked_after_save() {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(async {
+                let dir = tempfile::tempdir().expect("failed to initialize temporary directory");
+                let path = dir.path().join("article.json");
+                let temp_repo = ArticleRepository::new(&path).await;
+                temp_repo.create_entry(&ArticleId::new("12345".to_string()), "12345".to_string(), Visibility::Private, None).expect("failed to save");
+
+                assert!(File::open(&path).unwrap().try_lock_exclusive().is_err());
+                drop(temp_repo);
+                assert!(File::open(&path).unwrap().try_lock_exclusive().is_ok());
+            });
+    }
+
+    fn external_document(id: &str, content: &str) -> String {
+        serde_json::json!({
+            "data": {
+                id: {
+                    "created_at": "2024-01-01T00:00:00+09:00",
+                    "updated_at": "2024-01-01T00:00:00+09:00",
+                    "content": content,
+                    "visibility": "public"
+                }
+            }
+        }).to_string()
+    }
+
+    #[test]
+    fn edit_in_place_is_reloaded() {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(async {
+                let dir = tempfile::tempdir().expect("failed to initialize temporary directory");
+                let path = dir.path().join("article.json");
+                let temp_repo = ArticleRepository::new(&path).await;
+                temp_repo.create_entry(&ArticleId::new("12345".to_string()), "12345".to_string(), Visibility::Private, None).expect("failed to save");
+
+                std::fs::write(&path, external_document("edited", "by hand")).unwrap();
+
+                assert!(!temp_repo.exists(&ArticleId::new("12345".to_string())).unwrap());
+                assert_eq!(temp_repo.read_snapshot(&ArticleId::new("edited".to_string())).unwrap().content, "by hand");
+                assert!(File::open(&path).unwrap().try_lock_exclusive().is_err());
+            });
+    }
+
+    #[test]
+    fn replaced_file_is_reloaded_and_locked() {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(async {
+                let dir = tempfile::tempdir().expect("failed to initialize temporary directory");
+                let path = dir.path().join("article.json");
+                let temp_repo = ArticleRepository::new(&path).await;
+
+                // エディタがするように、別のファイルに書いてからリネームする
+                let swap = dir.path().join("article.json.swp");
+                std::fs::write(&swap, external_document("edited", "by hand")).unwrap();
+                std::fs::rename(&swap, &path).unwrap();
+
+                assert_eq!(temp_repo.entries().unwrap().len(), 1);
+                assert!(File::open(&path).unwrap().try_lock_exclusive().is_err());
+            });
+    }
+
+    #[test]
+    fn conditional_write_sees_concurrent_update() {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(async {
+                let dir = tempfile::tempdir().expect("failed to initialize temporary directory");
+                let path = dir.path().join("article.json");
+                let temp_repo = ArticleRepository::new(&path).await;
+                let id = ArticleId::new("12345".to_string());
+                temp_repo.create_entry(&id, "first".to_string(), Visibility::Private, None).expect("failed to save");
+
+                let read = temp_repo.read_snapshot(&id).unwrap();
+                temp_repo.update_entry(&id, "concurrent".to_string(), None).expect("failed to save");
+                let unchanged = |current: &Article| current.content == read.content;
+
+                assert!(matches!(temp_repo.update_entry_if(&id, &unchanged, "stale".to_string(), None), Err(PersistenceError::PreconditionFailed)));
+                assert!(matches!(temp_repo.change_visibility_if(&id, &unchanged, Visibility::Public), Err(PersistenceError::PreconditionFailed)));
+                assert!(matches!(temp_repo.remove_if(&id, &unchanged), Err(PersistenceError::PreconditionFailed)));
+                assert!(matches!(temp_repo.rename_if(&id, &unchanged, ArticleId::new("23456".to_string())), Err(PersistenceError::PreconditionFailed)));
+
+                let article = temp_repo.read_snapshot(&id).unwrap();
+                assert_eq!(article.content, "concurrent");
+                assert_eq!(article.visibility, Visibility::Private);
+            });
+    }
+
+    #[test]
+    fn search_index_follows_external_edit() {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(async {
+                let dir = tempfile::tempdir().expect("failed to initialize temporary directory");
+                let path = dir.path().join("article.json");
+                let store = SearchIndexedStore::new(Arc::new(ArticleRepository::new(&path).await)).unwrap();
+                store.create_entry(&ArticleId::new("12345".to_string()), "written through the store".to_string(), Visibility::Public, None).expect("failed to save");
+
+                std::fs::write(&path, external_document("edited", "written by hand")).unwrap();
+
+                let hits = store.search("hand", 20).unwrap();
+                assert_eq!(hits.iter().map(|x| x.id.0.as_str()).collect::<Vec<_>>(), ["edited"]);
+                assert!(store.search("store", 20).unwrap().is_empty());
+            });
+    }
+
+    #[test]
+    fn write_keeps_external_edit() {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(async {
+                let dir = tempfile::tempdir().expect("failed to initialize temporary directory");
+                let path = dir.path().join("article.json");
+                let temp_repo = ArticleRepository::new(&path).await;
+
+                std::fs::write(&path, external_document("edited", "by hand")).unwrap();
+                temp_repo.create_entry(&ArticleId::new("12345".to_string()), "12345".to_string(), Visibility::Private, None).expect("failed to save");
+                drop(temp_repo);
+
+                let temp_repo = ArticleRepository::new(&path).await;
+                assert_eq!(temp_repo.entries().unwrap().len(), 2);
+            });
+    }
+
+    #[test]
+    fn write_racing_with_external_edit_is_rejected() {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(async {
+                let dir = tempfile::tempdir().expect("failed to initialize temporary directory");
+                let path = dir.path().join("article.json");
+                let temp_repo = ArticleRepository::new(&path).await;
+
+                let res = temp_repo.modify(|scheme| {
+                    std::fs::write(&path, external_document("edited", "by hand")).unwrap();
+                    scheme.create_entry(&ArticleId::new("12345".to_string()), "12345".to_string(), Visibility::Private, None);
+                    Ok(())
+                });
+
+                assert!(matches!(res, Err(PersistenceError::Conflict)));
+                assert_eq!(std::fs::read_to_string(&path).unwrap(), external_document("edited", "by hand"));
+                assert!(!temp_repo.exists(&ArticleId::new("12345".to_string())).unwrap());
+                assert!(temp_repo.exists(&ArticleId::new("edited".to_string())).unwrap());
+            });
+    }
 }
@@ -0,0 +1,299 @@
+use std::collections::BTreeSet;
+use std::sync::RwLock;
+use chrono::{DateTime, Local};
+use toy_blog_endpoint_model::{Article, ArticleDraft, ArticleId, ArticleMetadata, ArticleRevision, ArticleShareLink, ShareLinkId, TrashedArticle, Visibility};
+use crate::service::persistence::{check_expectation, ArticleStore, Expectation, FileScheme, PersistenceError};
+
+/// ファイルに書き出さない[`ArticleStore`]。テストで使う。
+#[derive(Debug)]
+pub struct InMemoryArticleStore {
+    inner: RwLock<FileScheme>,
+}
+
+impl InMemoryArticleStore {
+    pub fn new() -> Self {
+        Self {
+            inner: RwLock::new(FileScheme::empty()),
+        }
+    }
+}
+
+impl ArticleStore for InMemoryArticleStore {
+    fn create_entry(&self, article_id: &ArticleId, article_content: String, visibility: Visibility, created_by: Option<&str>) -> Result<(), PersistenceError> {
+        self.inner.write().expect("poisoned").create_entry(article_id, article_content, visibility, created_by);
+        Ok(())
+    }
+
+    fn entries(&self) -> Result<Vec<(ArticleId, Article)>, PersistenceError> {
+        Ok(self.inner.read().expect("poisoned").entries())
+    }
+
+    fn update_entry_if(&self, article_id: &ArticleId, expected: Expectation, article_content: String, updated_by: Option<&str>) -> Result<(), PersistenceError> {
+        let mut inner = self.inner.write().expect("poisoned");
+        check_expectation(inner.data.get(article_id), expected)?;
+        inner.update_entry(article_id, article_content, updated_by)
+    }
+
+    fn change_visibility_if(&self, article_id: &ArticleId, expected: Expectation, new_visibility: Visibility) -> Result<(), PersistenceError> {
+        let mut inner = self.inner.write().expect("poisoned");
+        check_expectation(inner.data.get(article_id), expected)?;
+        inner.change_visibility(article_id, new_visibility)
+    }
+
+    fn schedule_publication(&self, article_id: &ArticleId, publish_at: Option<DateTime<Local>>) -> Result<(), PersistenceError> {
+        self.inner.write().expect("poisoned").schedule_publication(article_id, publish_at)
+    }
+
+    fn publish_due(&self, now: DateTime<Local>) -> Result<Vec<ArticleId>, PersistenceError> {
+        Ok(self.inner.write().expect("poisoned").publish_due(now))
+    }
+
+    fn change_tags(&self, article_id: &ArticleId, tags: BTreeSet<String>) -> Result<(), PersistenceError> {
+        self.inner.write().expect("poisoned").change_tags(article_id, tags)
+    }
+
+    fn change_metadata(&self, article_id: &ArticleId, metadata: ArticleMetadata) -> Result<(), PersistenceError> {
+        self.inner.write().expect("poisoned").change_metadata(article_id, metadata)
+    }
+
+    fn change_draft(&self, article_id: &ArticleId, draft: Option<ArticleDraft>) -> Result<(), PersistenceError> {
+        self.inner.write().expect("poisoned").change_draft(article_id, draft)
+    }
+
+    fn publish_draft(&self, article_id: &ArticleId, published_by: Option<&str>) -> Result<Article, PersistenceError> {
+        self.inner.write().expect("poisoned").publish_draft(article_id, published_by)
+    }
+
+    fn read_snapshot(&self, article_id: &ArticleId) -> Result<Article, PersistenceError> {
+        self.inner.read().expect("poisoned").read_snapshot(article_id)
+    }
+
+    fn revisions(&self, article_id: &ArticleId) -> Result<Vec<ArticleRevision>, PersistenceError> {
+        self.inner.read().expect("poisoned").revisions(article_id)
+    }
+
+    fn exists(&self, article_id: &ArticleId) -> Result<bool, PersistenceError> {
+        Ok(self.inner.read().expect("poisoned").data.contains_key(article_id))
+    }
+
+    fn remove_if(&self, article_id: &ArticleId, expected: Expectation) -> Result<(), PersistenceError> {
+        let mut inner = self.inner.write().expect("poisoned");
+        check_expectation(inner.data.get(article_id), expected)?;
+        inner.remove(article_id);
+        drop(inner);
+        Ok(())
+    }
+
+    fn rename_if(&self, old_id: &ArticleId, expected: Expectation, new_id: ArticleId) -> Result<(), PersistenceError> {
+        let mut inner = self.inner.write().expect("poisoned");
+        check_expectation(inner.data.get(old_id), expected)?;
+        inner.rename(old_id, new_id)
+    }
+
+    fn share_links(&self, article_id: &ArticleId) -> Result<Vec<ArticleShareLink>, PersistenceError> {
+        self.inner.read().expect("poisoned").share_links(article_id)
+    }
+
+    fn add_share_link(&self, article_id: &ArticleId, link: ArticleShareLink) -> Result<(), PersistenceError> {
+        self.inner.write().expect("poisoned").add_share_link(article_id, link)
+    }
+
+    fn revoke_share_link(&self, article_id: &ArticleId, link_id: &ShareLinkId) -> Result<(), PersistenceError> {
+        self.inner.write().expect("poisoned").revoke_share_link(article_id, link_id)
+    }
+
+    fn trashed_entries(&self) -> Result<Vec<(ArticleId, TrashedArticle)>, PersistenceError> {
+        Ok(self.inner.read().expect("poisoned").trashed_entries())
+    }
+
+    fn restore(&self, article_id: &ArticleId) -> Result<(), PersistenceError> {
+        self.inner.write().expect("poisoned").restore(article_id)
+    }
+
+    fn purge(&self, article_id: &ArticleId) -> Result<(), PersistenceError> {
+        self.inner.write().expect("poisoned").purge(article_id)
+    }
+
+    fn purge_trashed_before(&self, threshold: DateTime<Local>) -> Result<usize, PersistenceError> {
+        Ok(self.inner.write().expect("poisoned").purge_trashed_before(threshold))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Local, TimeDelta};
+    use toy_blog_endpoint_model::{ArticleDraft, ArticleId, ArticleShareLink, ShareLinkId, Visibility};
+    use crate::service::persistence::{ArticleStore, InMemoryArticleStore, PersistenceError};
+
+    #[test]
+    fn rename_moves_article() {
+        let store = InMemoryArticleStore::new();
+        let old_id = ArticleId::new("old".to_string());
+        let new_id = ArticleId::new("new".to_string());
+        store.create_entry(&old_id, "content".to_string(), Visibility::Public, None).unwrap();
+        store.rename(&old_id, new_id.clone()).unwrap();
+
+        assert!(!store.exists(&old_id).unwrap());
+        assert_eq!(store.read_snapshot(&new_id).unwrap().content, "content");
+    }
+
+    #[test]
+    fn rename_absent_article_fails() {
+        let store = InMemoryArticleStore::new();
+        let res = store.rename(&ArticleId::new("a".to_string()), ArticleId::new("b".to_string()));
+
+        assert!(matches!(res, Err(PersistenceError::AbsentValue)));
+    }
+
+    #[test]
+    fn rename_to_existing_id_fails() {
+        let store = InMemoryArticleStore::new();
+        let a = ArticleId::new("a".to_string());
+        let b = ArticleId::new("b".to_string());
+        store.create_entry(&a, "a".to_string(), Visibility::Public, None).unwrap();
+        store.create_entry(&b, "b".to_string(), Visibility::Public, None).unwrap();
+
+        assert!(matches!(store.rename(&a, b.clone()), Err(PersistenceError::DuplicatedId)));
+        assert_eq!(store.read_snapshot(&a).unwrap().content, "a");
+        assert_eq!(store.read_snapshot(&b).unwrap().content, "b");
+    }
+
+    #[test]
+    fn update_appends_revision() {
+        let store = InMemoryArticleStore::new();
+        let id = ArticleId::new("a".to_string());
+        store.create_entry(&id, "first".to_string(), Visibility::Public, None).unwrap();
+        store.update_entry(&id, "second".to_string(), None).unwrap();
+        store.rename(&id, ArticleId::new("b".to_string())).unwrap();
+
+        let revisions = store.revisions(&ArticleId::new("b".to_string())).unwrap();
+        let contents = revisions.iter().map(|x| (x.revision.into_inner(), x.content.as_str())).collect::<Vec<_>>();
+        assert_eq!(contents, [(1, "first"), (2, "second")]);
+    }
+
+    #[test]
+    fn article_without_history_has_single_revision() {
+        let store = InMemoryArticleStore::new();
+        let id = ArticleId::new("a".to_string());
+        store.create_entry(&id, "first".to_string(), Visibility::Public, None).unwrap();
+        store.inner.write().unwrap().revisions.clear();
+
+        assert_eq!(store.revisions(&id).unwrap().len(), 1);
+        store.update_entry(&id, "second".to_string(), None).unwrap();
+        let contents = store.revisions(&id).unwrap().into_iter().map(|x| x.content).collect::<Vec<_>>();
+        assert_eq!(contents, ["first", "second"]);
+    }
+
+    #[test]
+    fn remove_moves_article_to_trash() {
+        let store = InMemoryArticleStore::new();
+        let id = ArticleId::new("a".to_string());
+        store.create_entry(&id, "first".to_string(), Visibility::Public, None).unwrap();
+        store.update_entry(&id, "second".to_string(), None).unwrap();
+        store.remove(&id).unwrap();
+
+        assert!(!store.exists(&id).unwrap());
+        assert_eq!(store.trashed_entries().unwrap().len(), 1);
+
+        store.restore(&id).unwrap();
+        assert!(store.trashed_entries().unwrap().is_empty());
+        assert_eq!(store.revisions(&id).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn restore_refuses_conflicting_id() {
+        let store = InMemoryArticleStore::new();
+        let id = ArticleId::new("a".to_string());
+        store.create_entry(&id, "old".to_string(), Visibility::Public, None).unwrap();
+        store.remove(&id).unwrap();
+        store.create_entry(&id, "new".to_string(), Visibility::Public, None).unwrap();
+
+        assert!(matches!(store.restore(&id), Err(PersistenceError::DuplicatedId)));
+        assert_eq!(store.read_snapshot(&id).unwrap().content, "new");
+        assert_eq!(store.trashed_entries().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn purge_trashed_before_keeps_recent_entries() {
+        let store = InMemoryArticleStore::new();
+        let id = ArticleId::new("a".to_string());
+        store.create_entry(&id, "content".to_string(), Visibility::Public, None).unwrap();
+        store.remove(&id).unwrap();
+
+        assert_eq!(store.purge_trashed_before(Local::now() - TimeDelta::days(1)).unwrap(), 0);
+        assert_eq!(store.purge_trashed_before(Local::now() + TimeDelta::days(1)).unwrap(), 1);
+        assert!(matches!(store.purge(&id), Err(PersistenceError::AbsentValue)));
+    }
+
+    #[test]
+    fn share_links_follow_article() {
+        let store = InMemoryArticleStore::new();
+        let id = ArticleId::new("a".to_string());
+        let link_id = ShareLinkId("link".to_string());
+        store.create_entry(&id, "content".to_string(), Visibility::Restricted, None).unwrap();
+        store.add_share_link(&id, ArticleShareLink {
+            id: link_id.clone(),
+            digest: String::new(),
+            created_at: Local::now(),
+            expires_at: None,
+        }).unwrap();
+
+        let renamed = ArticleId::new("b".to_string());
+        store.rename(&id, renamed.clone()).unwrap();
+        assert_eq!(store.share_links(&renamed).unwrap().len(), 1);
+
+        store.revoke_share_link(&renamed, &link_id).unwrap();
+        assert!(store.share_links(&renamed).unwrap().is_empty());
+        assert!(matches!(store.revoke_share_link(&renamed, &link_id), Err(PersistenceError::AbsentValue)));
+        assert!(matches!(store.share_links(&id), Err(PersistenceError::AbsentValue)));
+    }
+
+    #[test]
+    fn scheduled_article_is_published_when_due() {
+        let store = InMemoryArticleStore::new();
+        let due = ArticleId::new("due".to_string());
+        let later = ArticleId::new("later".to_string());
+        let now = Local::now();
+        store.create_entry(&due, String::new(), Visibility::Private, None).unwrap();
+        store.create_entry(&later, String::new(), Visibility::Private, None).unwrap();
+        store.schedule_publication(&due, Some(now - TimeDelta::minutes(1))).unwrap();
+        store.schedule_publication(&later, Some(now + TimeDelta::days(1))).unwrap();
+
+        assert_eq!(store.publish_due(now).unwrap(), std::slice::from_ref(&due));
+        assert!(store.publish_due(now).unwrap().is_empty());
+        let published = store.read_snapshot(&due).unwrap();
+        assert_eq!(published.visibility, Visibility::Public);
+        assert_eq!(published.publish_at, None);
+        assert_eq!(store.read_snapshot(&later).unwrap().visibility, Visibility::Private);
+
+        // 手動で公開範囲を変えると予約は取り消される
+        store.change_visibility(&later, Visibility::Restricted).unwrap();
+        assert_eq!(store.read_snapshot(&later).unwrap().publish_at, None);
+    }
+
+    #[test]
+    fn draft_does_not_touch_content() {
+        let store = InMemoryArticleStore::new();
+        let id = ArticleId::new("a".to_string());
+        store.create_entry(&id, "published".to_string(), Visibility::Public, None).unwrap();
+        let before = store.read_snapshot(&id).unwrap();
+        store.change_draft(&id, Some(ArticleDraft {
+            content: "draft".to_string(),
+            updated_at: Local::now(),
+            updated_by: None,
+        })).unwrap();
+
+        let after = store.read_snapshot(&id).unwrap();
+        assert_eq!(after.content, "published");
+        assert_eq!(after.updated_at, before.updated_at);
+        assert_eq!(store.revisions(&id).unwrap().len(), 1);
+
+        let renamed = ArticleId::new("b".to_string());
+        store.rename(&id, renamed.clone()).unwrap();
+        assert_eq!(store.read_snapshot(&renamed).unwrap().draft.map(|x| x.content).as_deref(), Some("draft"));
+
+        store.change_draft(&renamed, None).unwrap();
+        assert_eq!(store.read_snapshot(&renamed).unwrap().draft, None);
+        assert!(matches!(store.change_draft(&id, None), Err(PersistenceError::AbsentValue)));
+    }
+}
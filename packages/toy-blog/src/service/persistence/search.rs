@@ -0,0 +1,440 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::ops::Range;
+use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicU64, Ordering};
+use chrono::{DateTime, Local};
+use toy_blog_endpoint_model::{Article, ArticleDraft, ArticleId, ArticleMetadata, ArticleRevision, ArticleShareLink, SearchHit, ShareLinkId, SnippetFragment, TrashedArticle, Visibility};
+use crate::service::persistence::{ArticleStore, Expectation, PersistenceError};
+
+/// BM25のパラメーター
+const K1: f64 = 1.2;
+const B: f64 = 0.75;
+/// 抜粋で最初に一致した部分より前に含める文字数
+const SNIPPET_LEADING_CHARS: usize = 30;
+const SNIPPET_CHARS: usize = 120;
+
+/// 全角英数字を半角に、英字を小文字にそろえる。文字数は変えないので、元の本文と位置が対応する。
+fn normalize_char(c: char) -> char {
+    let c = match c {
+        '\u{FF01}'..='\u{FF5E}' => char::from_u32(u32::from(c) - 0xFEE0).unwrap_or(c),
+        '\u{3000}' => ' ',
+        _ => c,
+    };
+
+    let mut lower = c.to_lowercase();
+    match (lower.next(), lower.next()) {
+        (Some(lower), None) => lower,
+        _ => c,
+    }
+}
+
+fn normalize(text: &str) -> Vec<char> {
+    text.chars().map(normalize_char).collect()
+}
+
+/// 文字や数字が連続した部分。日本語は分かち書きされないので、これ以上は区切らない。
+fn runs(chars: &[char]) -> impl Iterator<Item = &[char]> {
+    chars.split(|c| !c.is_alphanumeric()).filter(|run| !run.is_empty())
+}
+
+/// 1文字と、隣り合う2文字を索引の単位にする。
+fn tokens(run: &[char]) -> impl Iterator<Item = String> + '_ {
+    run.iter().map(char::to_string)
+        .chain(run.windows(2).map(|pair| pair.iter().collect()))
+}
+
+fn occurrences<'a>(haystack: &'a [char], needle: &'a [char]) -> impl Iterator<Item = Range<usize>> + 'a {
+    haystack.windows(needle.len())
+        .enumerate()
+        .filter(move |(_, window)| *window == needle)
+        .map(move |(start, _)| start..start + needle.len())
+}
+
+#[derive(Debug)]
+struct IndexedDocument {
+    content: Vec<char>,
+    normalized: Vec<char>,
+    token_count: usize,
+    visibility: Visibility,
+}
+
+/// 記事の本文の転置索引。
+#[derive(Debug, Default)]
+pub struct SearchIndex {
+    documents: HashMap<ArticleId, IndexedDocument>,
+    /// 索引の単位ごとに、それを含む記事と出現回数
+    postings: HashMap<String, HashMap<ArticleId, u32>>,
+}
+
+impl SearchIndex {
+    fn insert(&mut self, article_id: &ArticleId, article: &Article) {
+        self.remove(article_id);
+
+        let normalized = normalize(&article.content);
+        let mut token_count = 0;
+        for token in runs(&normalized).flat_map(tokens) {
+            *self.postings.entry(token).or_default().entry(article_id.clone()).or_default() += 1;
+            token_count += 1;
+        }
+
+        self.documents.insert(article_id.clone(), IndexedDocument {
+            content: article.content.chars().collect(),
+            normalized,
+            token_count,
+            visibility: article.visibility,
+        });
+    }
+
+    fn remove(&mut self, article_id: &ArticleId) {
+        let Some(document) = self.documents.remove(article_id) else { return };
+
+        for token in runs(&document.normalized).flat_map(tokens) {
+            if let Some(posting) = self.postings.get_mut(&token) {
+                posting.remove(article_id);
+                if posting.is_empty() {
+                    self.postings.remove(&token);
+                }
+            }
+        }
+    }
+
+    /// `query`の全ての語を含む公開記事を、よく一致している順に最大`limit`件返す。
+    ///
+    /// 語は空白や記号で区切られる。各語は記事の中にそのまま現れていなければならない。
+    #[allow(clippy::cast_precision_loss)]
+    pub fn search(&self, query: &str, limit: usize) -> Vec<SearchHit> {
+        let query = normalize(query);
+        let terms = runs(&query).collect::<Vec<_>>();
+        let query_tokens = terms.iter().flat_map(|term| tokens(term)).collect::<HashSet<_>>();
+        if query_tokens.is_empty() {
+            return vec![]
+        }
+
+        let Some(postings) = query_tokens.iter().map(|token| self.postings.get(token)).collect::<Option<Vec<_>>>() else {
+            return vec![]
+        };
+
+        let document_count = self.documents.len() as f64;
+        let average_length = self.documents.values().map(|x| x.token_count).sum::<usize>() as f64 / document_count;
+        let rarest = postings.iter().min_by_key(|x| x.len()).expect("query has at least one token");
+
+        let mut hits = rarest.keys()
+            .filter(|id| postings.iter().all(|posting| posting.contains_key(*id)))
+            .filter_map(|id| self.documents.get(id).map(|document| (id, document)))
+            .filter(|(_, document)| document.visibility == Visibility::Public)
+            // 2文字ずつの一致だけでは語として現れているとは限らない
+            .filter(|(_, document)| terms.iter().all(|term| occurrences(&document.normalized, term).next().is_some()))
+            .map(|(id, document)| {
+                let length_normalization = K1 * B.mul_add(document.token_count as f64 / average_length, 1.0 - B);
+                let score = postings.iter()
+                    .map(|posting| {
+                        let frequency = f64::from(posting[id]);
+                        let idf = ((document_count - posting.len() as f64 + 0.5) / (posting.len() as f64 + 0.5)).ln_1p();
+                        idf * frequency * (K1 + 1.0) / (frequency + length_normalization)
+                    })
+                    .sum();
+
+                SearchHit {
+                    id: id.clone(),
+                    score,
+                    snippet: snippet(document, &terms),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        hits.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.id.0.cmp(&b.id.0)));
+        hits.truncate(limit);
+
+        hits
+    }
+}
+
+/// 最初に一致した部分の付近を切り出し、一致した部分を強調する。
+fn snippet(document: &IndexedDocument, terms: &[&[char]]) -> Vec<SnippetFragment> {
+    let mut found = terms.iter()
+        .flat_map(|term| occurrences(&document.normalized, term))
+        .collect::<Vec<_>>();
+    found.sort_by_key(|x| (x.start, x.end));
+
+    let start = found.first().map_or(0, |x| x.start.saturating_sub(SNIPPET_LEADING_CHARS));
+    let end = (start + SNIPPET_CHARS).min(document.content.len());
+    let text = |range: Range<usize>| -> String {
+        document.content[range].iter().map(|&c| if c.is_whitespace() { ' ' } else { c }).collect()
+    };
+
+    let mut fragments = vec![];
+    let mut cursor = start;
+    for matched in found {
+        let matched = matched.start.max(cursor)..matched.end.min(end);
+        if matched.is_empty() {
+            continue
+        }
+        if cursor < matched.start {
+            fragments.push(SnippetFragment { text: text(cursor..matched.start), highlighted: false });
+        }
+        cursor = matched.end;
+        fragments.push(SnippetFragment { text: text(matched), highlighted: true });
+    }
+    if cursor < end {
+        fragments.push(SnippetFragment { text: text(cursor..end), highlighted: false });
+    }
+
+    if start > 0 {
+        fragments.insert(0, SnippetFragment { text: "…".to_string(), highlighted: false });
+    }
+    if end < document.content.len() {
+        fragments.push(SnippetFragment { text: "…".to_string(), highlighted: false });
+    }
+
+    fragments
+}
+
+/// 書き込みのたびに[`SearchIndex`]を更新する[`ArticleStore`]。
+///
+/// 書き込みは全て`inner`に委ね、成功した後で書き込まれた記事を読み直して索引に反映する。
+/// このラッパーを経由しない書き込み（別の`Arc`からの[`ArticleStore::publish_due`]や`import`サブコマンド、手での編集）は見えないので、
+/// `inner`が外部での変更を読み込んだ場合は、検索の前に索引を全て作り直す。
+pub struct SearchIndexedStore {
+    inner: Arc<dyn ArticleStore>,
+    index: RwLock<SearchIndex>,
+    /// 索引を作った時点の`inner`の[`ArticleStore::external_generation`]
+    indexed_generation: AtomicU64,
+}
+
+impl SearchIndexedStore {
+    pub fn new(inner: Arc<dyn ArticleStore>) -> Result<Self, PersistenceError> {
+        let generation = inner.external_generation()?;
+        let index = Self::build_index(&*inner)?;
+
+        Ok(Self {
+            inner,
+            index: RwLock::new(index),
+            indexed_generation: AtomicU64::new(generation),
+        })
+    }
+
+    fn build_index(inner: &dyn ArticleStore) -> Result<SearchIndex, PersistenceError> {
+        let mut index = SearchIndex::default();
+        for (id, article) in inner.entries()? {
+            index.insert(&id, &article);
+        }
+
+        Ok(index)
+    }
+
+    pub fn search(&self, query: &str, limit: usize) -> Result<Vec<SearchHit>, PersistenceError> {
+        self.rebuild_if_reloaded()?;
+
+        Ok(self.index.read().expect("search index is poisoned").search(query, limit))
+    }
+
+    /// `inner`が外部での変更を読み込んでいれば、索引を全て作り直す。
+    fn rebuild_if_reloaded(&self) -> Result<(), PersistenceError> {
+        let generation = self.inner.external_generation()?;
+        if generation == self.indexed_generation.load(Ordering::SeqCst) {
+            return Ok(())
+        }
+
+        let mut index = self.index.write().expect("search index is poisoned");
+        // 待っている間に他のスレッドが作り直しているかもしれない
+        if generation == self.indexed_generation.load(Ordering::SeqCst) {
+            return Ok(())
+        }
+
+        *index = Self::build_index(&*self.inner)?;
+        self.indexed_generation.store(generation, Ordering::SeqCst);
+        drop(index);
+
+        Ok(())
+    }
+
+    fn reindex(&self, article_id: &ArticleId) {
+        let mut index = self.index.write().expect("search index is poisoned");
+        match self.inner.read_snapshot(article_id) {
+            Ok(article) => index.insert(article_id, &article),
+            Err(_) => index.remove(article_id),
+        }
+    }
+
+    /// `result`が成功していれば`article_id`を索引し直す。
+    fn reindex_if_ok<T>(&self, article_id: &ArticleId, result: Result<T, PersistenceError>) -> Result<T, PersistenceError> {
+        if result.is_ok() {
+            self.reindex(article_id);
+        }
+
+        result
+    }
+}
+
+impl ArticleStore for SearchIndexedStore {
+    fn create_entry(&self, article_id: &ArticleId, article_content: String, visibility: Visibility, created_by: Option<&str>) -> Result<(), PersistenceError> {
+        self.reindex_if_ok(article_id, self.inner.create_entry(article_id, article_content, visibility, created_by))
+    }
+
+    fn entries(&self) -> Result<Vec<(ArticleId, Article)>, PersistenceError> {
+        self.inner.entries()
+    }
+
+    fn entries_created_between(&self, from: DateTime<Local>, until: DateTime<Local>) -> Result<Vec<(ArticleId, Article)>, PersistenceError> {
+        self.inner.entries_created_between(from, until)
+    }
+
+    fn update_entry_if(&self, article_id: &ArticleId, expected: Expectation, article_content: String, updated_by: Option<&str>) -> Result<(), PersistenceError> {
+        self.reindex_if_ok(article_id, self.inner.update_entry_if(article_id, expected, article_content, updated_by))
+    }
+
+    fn change_visibility_if(&self, article_id: &ArticleId, expected: Expectation, new_visibility: Visibility) -> Result<(), PersistenceError> {
+        self.reindex_if_ok(article_id, self.inner.change_visibility_if(article_id, expected, new_visibility))
+    }
+
+    fn schedule_publication(&self, article_id: &ArticleId, publish_at: Option<DateTime<Local>>) -> Result<(), PersistenceError> {
+        self.inner.schedule_publication(article_id, publish_at)
+    }
+
+    fn publish_due(&self, now: DateTime<Local>) -> Result<Vec<ArticleId>, PersistenceError> {
+        let published = self.inner.publish_due(now)?;
+        for article_id in &published {
+            self.reindex(article_id);
+        }
+
+        Ok(published)
+    }
+
+    fn change_tags(&self, article_id: &ArticleId, tags: BTreeSet<String>) -> Result<(), PersistenceError> {
+        // タグは索引に含めていない
+        self.inner.change_tags(article_id, tags)
+    }
+
+    fn change_metadata(&self, article_id: &ArticleId, metadata: ArticleMetadata) -> Result<(), PersistenceError> {
+        // 題名と要約も索引に含めていない
+        self.inner.change_metadata(article_id, metadata)
+    }
+
+    fn change_draft(&self, article_id: &ArticleId, draft: Option<ArticleDraft>) -> Result<(), PersistenceError> {
+        // 下書きは公開されていないので、索引に含めない
+        self.inner.change_draft(article_id, draft)
+    }
+
+    fn publish_draft(&self, article_id: &ArticleId, published_by: Option<&str>) -> Result<Article, PersistenceError> {
+        self.reindex_if_ok(article_id, self.inner.publish_draft(article_id, published_by))
+    }
+
+    fn read_snapshot(&self, article_id: &ArticleId) -> Result<Article, PersistenceError> {
+        self.inner.read_snapshot(article_id)
+    }
+
+    fn revisions(&self, article_id: &ArticleId) -> Result<Vec<ArticleRevision>, PersistenceError> {
+        self.inner.revisions(article_id)
+    }
+
+    fn exists(&self, article_id: &ArticleId) -> Result<bool, PersistenceError> {
+        self.inner.exists(article_id)
+    }
+
+    fn remove_if(&self, article_id: &ArticleId, expected: Expectation) -> Result<(), PersistenceError> {
+        self.reindex_if_ok(article_id, self.inner.remove_if(article_id, expected))
+    }
+
+    fn rename_if(&self, old_id: &ArticleId, expected: Expectation, new_id: ArticleId) -> Result<(), PersistenceError> {
+        let result = self.inner.rename_if(old_id, expected, new_id.clone());
+        self.reindex_if_ok(old_id, result)?;
+        self.reindex(&new_id);
+
+        Ok(())
+    }
+
+    fn share_links(&self, article_id: &ArticleId) -> Result<Vec<ArticleShareLink>, PersistenceError> {
+        self.inner.share_links(article_id)
+    }
+
+    fn add_share_link(&self, article_id: &ArticleId, link: ArticleShareLink) -> Result<(), PersistenceError> {
+        self.inner.add_share_link(article_id, link)
+    }
+
+    fn revoke_share_link(&self, article_id: &ArticleId, link_id: &ShareLinkId) -> Result<(), PersistenceError> {
+        self.inner.revoke_share_link(article_id, link_id)
+    }
+
+    fn trashed_entries(&self) -> Result<Vec<(ArticleId, TrashedArticle)>, PersistenceError> {
+        self.inner.trashed_entries()
+    }
+
+    fn restore(&self, article_id: &ArticleId) -> Result<(), PersistenceError> {
+        self.reindex_if_ok(article_id, self.inner.restore(article_id))
+    }
+
+    fn purge(&self, article_id: &ArticleId) -> Result<(), PersistenceError> {
+        self.inner.purge(article_id)
+    }
+
+    fn purge_trashed_before(&self, threshold: DateTime<Local>) -> Result<usize, PersistenceError> {
+        self.inner.purge_trashed_before(threshold)
+    }
+
+    fn external_generation(&self) -> Result<u64, PersistenceError> {
+        self.inner.external_generation()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use toy_blog_endpoint_model::{ArticleId, SnippetFragment, Visibility};
+    use crate::service::persistence::{ArticleStore, InMemoryArticleStore};
+    use super::SearchIndexedStore;
+
+    fn id(s: &str) -> ArticleId {
+        ArticleId::new(s.to_string())
+    }
+
+    fn hit_ids(store: &SearchIndexedStore, query: &str) -> Vec<String> {
+        store.search(query, 20).unwrap().into_iter().map(|x| x.id.0).collect()
+    }
+
+    #[test]
+    fn japanese_text_is_searchable_without_spaces() {
+        let store = SearchIndexedStore::new(Arc::new(InMemoryArticleStore::new())).unwrap();
+        store.create_entry(&id("tokyo"), "今日は東京都庁へ行った。".to_string(), Visibility::Public, None).unwrap();
+        store.create_entry(&id("kyoto"), "京都で東の空を見た。京都は良い。".to_string(), Visibility::Public, None).unwrap();
+
+        assert_eq!(hit_ids(&store, "東京"), ["tokyo"]);
+        assert_eq!(hit_ids(&store, "京都"), ["kyoto", "tokyo"], "more occurrences should rank higher");
+        // 「京都東」は2文字ずつなら京都の記事にも含まれるが、語としては含まれない
+        assert!(hit_ids(&store, "京都東").is_empty());
+        assert_eq!(hit_ids(&store, "東京 今日"), ["tokyo"]);
+    }
+
+    #[test]
+    fn index_follows_writes() {
+        let store = SearchIndexedStore::new(Arc::new(InMemoryArticleStore::new())).unwrap();
+        store.create_entry(&id("a"), "Rust is fun".to_string(), Visibility::Private, None).unwrap();
+        assert!(hit_ids(&store, "rust").is_empty(), "private articles must not be found");
+
+        store.change_visibility(&id("a"), Visibility::Public).unwrap();
+        assert_eq!(hit_ids(&store, "ＲＵＳＴ"), ["a"]);
+
+        store.update_entry(&id("a"), "Go is fun".to_string(), None).unwrap();
+        assert!(hit_ids(&store, "rust").is_empty());
+
+        store.rename(&id("a"), id("b")).unwrap();
+        assert_eq!(hit_ids(&store, "go"), ["b"]);
+
+        store.remove(&id("b")).unwrap();
+        assert!(hit_ids(&store, "go").is_empty());
+        store.restore(&id("b")).unwrap();
+        assert_eq!(hit_ids(&store, "go"), ["b"]);
+    }
+
+    #[test]
+    fn snippet_highlights_matches() {
+        let store = SearchIndexedStore::new(Arc::new(InMemoryArticleStore::new())).unwrap();
+        let content = format!("{}\n検索の例。もう一度検索。", "前置き".repeat(20));
+        store.create_entry(&id("a"), content, Visibility::Public, None).unwrap();
+
+        let snippet = &store.search("検索", 20).unwrap()[0].snippet;
+        let highlighted = snippet.iter().filter(|x| x.highlighted).map(|x| x.text.as_str()).collect::<Vec<_>>();
+        assert_eq!(highlighted, ["検索", "検索"]);
+        assert_eq!(snippet.first(), Some(&SnippetFragment { text: "…".to_string(), highlighted: false }));
+        assert!(snippet.iter().all(|x| !x.text.contains('\n')));
+    }
+}
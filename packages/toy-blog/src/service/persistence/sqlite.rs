@@ -0,0 +1,965 @@
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::sync::Mutex;
+use chrono::{DateTime, Local};
+use log::info;
+use rusqlite::{Connection, OptionalExtension, params, Row, Transaction};
+use toy_blog_endpoint_model::{Article, ArticleDraft, ArticleId, ArticleMetadata, ArticleRevision, ArticleRevisionNumber, ArticleShareLink, ShareLinkId, TrashedArticle, Visibility};
+use crate::service::persistence::{check_expectation, ArticleStore, Expectation, PersistenceError};
+
+/// [SQLite](https://www.sqlite.org/)に記事を格納する[`ArticleStore`]。
+///
+/// JSONファイルと異なり、書き込みのたびに全記事を書き直すことはない。
+/// 日時はUNIX時刻のマイクロ秒で保持し、作成日時にインデックスを張っているので期間での絞り込みが速い。
+#[derive(Debug)]
+pub struct SqliteArticleStore {
+    connection: Mutex<Connection>,
+}
+
+const SCHEMA: &str = r"
+CREATE TABLE IF NOT EXISTS article (
+    id TEXT PRIMARY KEY NOT NULL,
+    content TEXT NOT NULL,
+    visibility TEXT NOT NULL,
+    created_at INTEGER NOT NULL,
+    updated_at INTEGER NOT NULL,
+    created_by TEXT,
+    updated_by TEXT,
+    tags TEXT NOT NULL DEFAULT '[]',
+    title TEXT,
+    summary TEXT,
+    lang TEXT,
+    publish_at INTEGER,
+    draft TEXT
+);
+CREATE INDEX IF NOT EXISTS article_created_at ON article(created_at);
+CREATE INDEX IF NOT EXISTS article_visibility ON article(visibility);
+CREATE TABLE IF NOT EXISTS article_revision (
+    article_id TEXT NOT NULL,
+    revision INTEGER NOT NULL,
+    created_at INTEGER NOT NULL,
+    content TEXT NOT NULL,
+    PRIMARY KEY (article_id, revision)
+);
+CREATE TABLE IF NOT EXISTS article_trash (
+    id TEXT PRIMARY KEY NOT NULL,
+    content TEXT NOT NULL,
+    visibility TEXT NOT NULL,
+    created_at INTEGER NOT NULL,
+    updated_at INTEGER NOT NULL,
+    deleted_at INTEGER NOT NULL,
+    created_by TEXT,
+    updated_by TEXT,
+    tags TEXT NOT NULL DEFAULT '[]',
+    title TEXT,
+    summary TEXT,
+    lang TEXT,
+    publish_at INTEGER,
+    draft TEXT
+);
+CREATE INDEX IF NOT EXISTS article_trash_deleted_at ON article_trash(deleted_at);
+CREATE TABLE IF NOT EXISTS article_trash_revision (
+    article_id TEXT NOT NULL,
+    revision INTEGER NOT NULL,
+    created_at INTEGER NOT NULL,
+    content TEXT NOT NULL,
+    PRIMARY KEY (article_id, revision)
+);
+CREATE TABLE IF NOT EXISTS article_share_link (
+    article_id TEXT NOT NULL,
+    id TEXT NOT NULL,
+    digest TEXT NOT NULL,
+    created_at INTEGER NOT NULL,
+    expires_at INTEGER,
+    PRIMARY KEY (article_id, id)
+);
+";
+
+impl SqliteArticleStore {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, PersistenceError> {
+        info!("opening article database: {path}", path = path.as_ref().display());
+        let connection = Connection::open(path)?;
+        connection.execute_batch(SCHEMA)?;
+        // 著者を記録する前に作られたデータベースには列が無い
+        for table in ["article", "article_trash"] {
+            for column in ["created_by", "updated_by"] {
+                add_column_if_absent(&connection, table, column, "TEXT")?;
+            }
+            // タグを付けられるようになる前に作られたデータベースには列が無い
+            add_column_if_absent(&connection, table, "tags", "TEXT NOT NULL DEFAULT '[]'")?;
+            for column in ["title", "summary", "lang"] {
+                add_column_if_absent(&connection, table, column, "TEXT")?;
+            }
+            add_column_if_absent(&connection, table, "publish_at", "INTEGER")?;
+            add_column_if_absent(&connection, table, "draft", "TEXT")?;
+        }
+        // 古いデータベースでは列を加えた後でないとインデックスを張れない
+        connection.execute_batch("CREATE INDEX IF NOT EXISTS article_publish_at ON article(publish_at);")?;
+
+        Ok(Self {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    #[allow(clippy::significant_drop_tightening)]
+    fn query_entries(&self, sql: &str, params: impl rusqlite::Params) -> Result<Vec<(ArticleId, Article)>, PersistenceError> {
+        let connection = self.connection.lock().expect("connection is poisoned");
+        let mut statement = connection.prepare_cached(sql)?;
+        let rows = statement.query_map(params, read_row)?;
+
+        Ok(rows.collect::<Result<Vec<_>, _>>()?)
+    }
+
+    #[allow(clippy::significant_drop_tightening)]
+    fn query_trash(&self) -> Result<Vec<(ArticleId, TrashedArticle)>, PersistenceError> {
+        let connection = self.connection.lock().expect("connection is poisoned");
+        let mut statement = connection.prepare_cached(
+            "SELECT id, content, visibility, created_at, updated_at, created_by, updated_by, tags, title, summary, lang, publish_at, draft, deleted_at FROM article_trash",
+        )?;
+        let rows = statement.query_map([], |row| {
+            let (id, article) = read_row(row)?;
+
+            Ok((id, TrashedArticle {
+                deleted_at: datetime_from_sql(row.get(13)?)?,
+                article,
+            }))
+        })?;
+
+        Ok(rows.collect::<Result<Vec<_>, _>>()?)
+    }
+}
+
+fn add_column_if_absent(connection: &Connection, table: &str, column: &str, declaration: &str) -> rusqlite::Result<()> {
+    let exists = connection.prepare(&format!("SELECT 1 FROM pragma_table_info('{table}') WHERE name = ?1"))?
+        .exists(params![column])?;
+
+    if !exists {
+        connection.execute(&format!("ALTER TABLE {table} ADD COLUMN {column} {declaration}"), [])?;
+    }
+
+    Ok(())
+}
+
+const fn visibility_to_sql(visibility: Visibility) -> &'static str {
+    match visibility {
+        Visibility::Public => "public",
+        Visibility::Restricted => "restricted",
+        Visibility::Private => "private",
+    }
+}
+
+fn visibility_from_sql(s: &str) -> rusqlite::Result<Visibility> {
+    match s {
+        "public" => Ok(Visibility::Public),
+        "restricted" => Ok(Visibility::Restricted),
+        "private" => Ok(Visibility::Private),
+        other => Err(rusqlite::Error::InvalidColumnType(2, other.to_string(), rusqlite::types::Type::Text)),
+    }
+}
+
+/// タグはJSONの配列として1つの列に格納する
+fn tags_to_sql(tags: &BTreeSet<String>) -> String {
+    serde_json::to_string(tags).expect("tags must be serializable")
+}
+
+fn tags_from_sql(s: &str) -> rusqlite::Result<BTreeSet<String>> {
+    serde_json::from_str(s).map_err(|e| rusqlite::Error::FromSqlConversionFailure(7, rusqlite::types::Type::Text, Box::new(e)))
+}
+
+fn draft_to_sql(draft: &ArticleDraft) -> String {
+    serde_json::to_string(draft).expect("draft must be serializable")
+}
+
+fn draft_from_sql(s: &str) -> rusqlite::Result<ArticleDraft> {
+    serde_json::from_str(s).map_err(|e| rusqlite::Error::FromSqlConversionFailure(12, rusqlite::types::Type::Text, Box::new(e)))
+}
+
+fn datetime_from_sql(micros: i64) -> rusqlite::Result<DateTime<Local>> {
+    DateTime::from_timestamp_micros(micros)
+        .map(|x| x.with_timezone(&Local))
+        .ok_or(rusqlite::Error::IntegralValueOutOfRange(3, micros))
+}
+
+fn read_revision_row(row: &Row) -> rusqlite::Result<ArticleRevision> {
+    let revision = row.get::<_, u32>(0)?;
+
+    Ok(ArticleRevision {
+        revision: ArticleRevisionNumber::try_from(revision)
+            .map_err(|()| rusqlite::Error::IntegralValueOutOfRange(0, revision.into()))?,
+        created_at: datetime_from_sql(row.get(1)?)?,
+        content: row.get(2)?,
+    })
+}
+
+fn read_row(row: &Row) -> rusqlite::Result<(ArticleId, Article)> {
+    Ok((
+        ArticleId::new(row.get(0)?),
+        Article {
+            content: row.get(1)?,
+            visibility: visibility_from_sql(&row.get::<_, String>(2)?)?,
+            created_at: datetime_from_sql(row.get(3)?)?,
+            updated_at: datetime_from_sql(row.get(4)?)?,
+            created_by: row.get(5)?,
+            updated_by: row.get(6)?,
+            tags: tags_from_sql(&row.get::<_, String>(7)?)?,
+            title: row.get(8)?,
+            summary: row.get(9)?,
+            lang: row.get(10)?,
+            publish_at: row.get::<_, Option<i64>>(11)?.map(datetime_from_sql).transpose()?,
+            draft: row.get::<_, Option<String>>(12)?.as_deref().map(draft_from_sql).transpose()?,
+        }
+    ))
+}
+
+fn select_article(connection: &Connection, article_id: &ArticleId) -> Result<Option<Article>, PersistenceError> {
+    let article = connection.query_row(
+        "SELECT id, content, visibility, created_at, updated_at, created_by, updated_by, tags, title, summary, lang, publish_at, draft FROM article WHERE id = ?1",
+        params![article_id.0],
+        read_row,
+    ).optional()?;
+
+    Ok(article.map(|(_, article)| article))
+}
+
+/// トランザクションの中で記事を読み直し、`expected`を満たしているか確かめる。
+fn check_expectation_in(transaction: &Transaction, article_id: &ArticleId, expected: Expectation) -> Result<(), PersistenceError> {
+    check_expectation(select_article(transaction, article_id)?.as_ref(), expected)
+}
+
+/// 本文を`article_content`に更新し、新しい版を追加する。コミットは呼び出し側で行う。
+fn update_content(transaction: &Transaction, article_id: &ArticleId, article_content: &str, updated_by: Option<&str>) -> Result<(), PersistenceError> {
+    let current_date = Local::now().timestamp_micros();
+
+    let Some((old_content, old_updated_at)) = transaction.query_row(
+        "SELECT content, updated_at FROM article WHERE id = ?1",
+        params![article_id.0],
+        |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?)),
+    ).optional()? else {
+        return Err(PersistenceError::AbsentValue)
+    };
+
+    let latest_revision = transaction.query_row(
+        "SELECT MAX(revision) FROM article_revision WHERE article_id = ?1",
+        params![article_id.0],
+        |row| row.get::<_, Option<u32>>(0),
+    )?;
+
+    let latest_revision = if let Some(latest_revision) = latest_revision {
+        latest_revision
+    } else {
+        // 履歴を持たない記事は現在の本文を最初の版とみなす
+        transaction.execute(
+            "INSERT INTO article_revision (article_id, revision, created_at, content) VALUES (?1, 1, ?2, ?3)",
+            params![article_id.0, old_updated_at, old_content],
+        )?;
+        1
+    };
+
+    transaction.execute(
+        "INSERT INTO article_revision (article_id, revision, created_at, content) VALUES (?1, ?2, ?3, ?4)",
+        params![article_id.0, latest_revision + 1, current_date, article_content],
+    )?;
+    transaction.execute(
+        "UPDATE article SET content = ?2, updated_at = ?3, updated_by = ?4 WHERE id = ?1",
+        params![article_id.0, article_content, current_date, updated_by],
+    )?;
+
+    Ok(())
+}
+
+impl ArticleStore for SqliteArticleStore {
+    #[allow(clippy::significant_drop_tightening)]
+    fn create_entry(&self, article_id: &ArticleId, article_content: String, visibility: Visibility, created_by: Option<&str>) -> Result<(), PersistenceError> {
+        let current_date = Local::now().timestamp_micros();
+        let mut connection = self.connection.lock().expect("connection is poisoned");
+        let transaction = connection.transaction()?;
+        transaction.execute(
+            "INSERT OR REPLACE INTO article (id, content, visibility, created_at, updated_at, created_by, updated_by) VALUES (?1, ?2, ?3, ?4, ?4, ?5, ?5)",
+            params![article_id.0, article_content, visibility_to_sql(visibility), current_date, created_by],
+        )?;
+        transaction.execute(
+            "DELETE FROM article_revision WHERE article_id = ?1",
+            params![article_id.0],
+        )?;
+        transaction.execute(
+            "INSERT INTO article_revision (article_id, revision, created_at, content) VALUES (?1, 1, ?2, ?3)",
+            params![article_id.0, current_date, article_content],
+        )?;
+        transaction.execute(
+            "DELETE FROM article_share_link WHERE article_id = ?1",
+            params![article_id.0],
+        )?;
+        transaction.commit()?;
+
+        Ok(())
+    }
+
+    fn entries(&self) -> Result<Vec<(ArticleId, Article)>, PersistenceError> {
+        self.query_entries(
+            "SELECT id, content, visibility, created_at, updated_at, created_by, updated_by, tags, title, summary, lang, publish_at, draft FROM article",
+            [],
+        )
+    }
+
+    fn entries_created_between(&self, from: DateTime<Local>, until: DateTime<Local>) -> Result<Vec<(ArticleId, Article)>, PersistenceError> {
+        self.query_entries(
+            "SELECT id, content, visibility, created_at, updated_at, created_by, updated_by, tags, title, summary, lang, publish_at, draft FROM article WHERE ?1 <= created_at AND created_at < ?2",
+            params![from.timestamp_micros(), until.timestamp_micros()],
+        )
+    }
+
+    #[allow(clippy::significant_drop_tightening)]
+    fn update_entry_if(&self, article_id: &ArticleId, expected: Expectation, article_content: String, updated_by: Option<&str>) -> Result<(), PersistenceError> {
+        let mut connection = self.connection.lock().expect("connection is poisoned");
+        let transaction = connection.transaction()?;
+
+        check_expectation_in(&transaction, article_id, expected)?;
+        update_content(&transaction, article_id, &article_content, updated_by)?;
+        transaction.commit()?;
+
+        Ok(())
+    }
+
+    #[allow(clippy::significant_drop_tightening)]
+    fn change_visibility_if(&self, article_id: &ArticleId, expected: Expectation, new_visibility: Visibility) -> Result<(), PersistenceError> {
+        let mut connection = self.connection.lock().expect("connection is poisoned");
+        let transaction = connection.transaction()?;
+
+        check_expectation_in(&transaction, article_id, expected)?;
+        let updated = transaction.execute(
+            "UPDATE article SET visibility = ?2, publish_at = NULL WHERE id = ?1",
+            params![article_id.0, visibility_to_sql(new_visibility)],
+        )?;
+
+        if updated == 0 {
+            return Err(PersistenceError::AbsentValue)
+        }
+
+        transaction.commit()?;
+
+        Ok(())
+    }
+
+    fn schedule_publication(&self, article_id: &ArticleId, publish_at: Option<DateTime<Local>>) -> Result<(), PersistenceError> {
+        let updated = self.connection.lock().expect("connection is poisoned").execute(
+            "UPDATE article SET publish_at = ?2 WHERE id = ?1",
+            params![article_id.0, publish_at.map(|x| x.timestamp_micros())],
+        )?;
+
+        if updated == 0 {
+            return Err(PersistenceError::AbsentValue)
+        }
+
+        Ok(())
+    }
+
+    #[allow(clippy::significant_drop_tightening)]
+    fn publish_due(&self, now: DateTime<Local>) -> Result<Vec<ArticleId>, PersistenceError> {
+        let now = now.timestamp_micros();
+        let mut connection = self.connection.lock().expect("connection is poisoned");
+        let transaction = connection.transaction()?;
+
+        let due = transaction.prepare_cached("SELECT id FROM article WHERE publish_at <= ?1")?
+            .query_map(params![now], |row| Ok(ArticleId::new(row.get(0)?)))?
+            .collect::<Result<Vec<_>, _>>()?;
+        transaction.execute(
+            "UPDATE article SET visibility = ?2, publish_at = NULL WHERE publish_at <= ?1",
+            params![now, visibility_to_sql(Visibility::Public)],
+        )?;
+        transaction.commit()?;
+
+        Ok(due)
+    }
+
+    fn change_tags(&self, article_id: &ArticleId, tags: BTreeSet<String>) -> Result<(), PersistenceError> {
+        let updated = self.connection.lock().expect("connection is poisoned").execute(
+            "UPDATE article SET tags = ?2 WHERE id = ?1",
+            params![article_id.0, tags_to_sql(&tags)],
+        )?;
+
+        if updated == 0 {
+            return Err(PersistenceError::AbsentValue)
+        }
+
+        Ok(())
+    }
+
+    fn change_metadata(&self, article_id: &ArticleId, metadata: ArticleMetadata) -> Result<(), PersistenceError> {
+        let updated = self.connection.lock().expect("connection is poisoned").execute(
+            "UPDATE article SET title = ?2, summary = ?3, lang = ?4, updated_at = ?5 WHERE id = ?1",
+            params![article_id.0, metadata.title, metadata.summary, metadata.lang, Local::now().timestamp_micros()],
+        )?;
+
+        if updated == 0 {
+            return Err(PersistenceError::AbsentValue)
+        }
+
+        Ok(())
+    }
+
+    fn change_draft(&self, article_id: &ArticleId, draft: Option<ArticleDraft>) -> Result<(), PersistenceError> {
+        let updated = self.connection.lock().expect("connection is poisoned").execute(
+            "UPDATE article SET draft = ?2 WHERE id = ?1",
+            params![article_id.0, draft.as_ref().map(draft_to_sql)],
+        )?;
+
+        if updated == 0 {
+            return Err(PersistenceError::AbsentValue)
+        }
+
+        Ok(())
+    }
+
+    #[allow(clippy::significant_drop_tightening)]
+    fn publish_draft(&self, article_id: &ArticleId, published_by: Option<&str>) -> Result<Article, PersistenceError> {
+        let mut connection = self.connection.lock().expect("connection is poisoned");
+        let transaction = connection.transaction()?;
+
+        let Some(previous) = select_article(&transaction, article_id)? else {
+            return Err(PersistenceError::AbsentValue)
+        };
+        let Some(draft) = &previous.draft else {
+            return Err(PersistenceError::AbsentValue)
+        };
+
+        update_content(&transaction, article_id, &draft.content, published_by)?;
+        transaction.execute("UPDATE article SET draft = NULL WHERE id = ?1", params![article_id.0])?;
+        transaction.commit()?;
+
+        Ok(previous)
+    }
+
+    fn read_snapshot(&self, article_id: &ArticleId) -> Result<Article, PersistenceError> {
+        select_article(&self.connection.lock().expect("connection is poisoned"), article_id)?
+            .ok_or(PersistenceError::AbsentValue)
+    }
+
+    #[allow(clippy::significant_drop_tightening)]
+    fn revisions(&self, article_id: &ArticleId) -> Result<Vec<ArticleRevision>, PersistenceError> {
+        let article = self.read_snapshot(article_id)?;
+
+        let connection = self.connection.lock().expect("connection is poisoned");
+        let mut statement = connection.prepare_cached(
+            "SELECT revision, created_at, content FROM article_revision WHERE article_id = ?1 ORDER BY revision",
+        )?;
+        let history = statement.query_map(params![article_id.0], read_revision_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if history.is_empty() {
+            // 履歴を持たない記事は現在の本文を最初の版とみなす
+            return Ok(vec![ArticleRevision {
+                revision: ArticleRevisionNumber::FIRST,
+                created_at: article.updated_at,
+                content: article.content,
+            }])
+        }
+
+        Ok(history)
+    }
+
+    fn exists(&self, article_id: &ArticleId) -> Result<bool, PersistenceError> {
+        let found = self.connection.lock().expect("connection is poisoned").query_row(
+            "SELECT 1 FROM article WHERE id = ?1",
+            params![article_id.0],
+            |_| Ok(()),
+        ).optional()?;
+
+        Ok(found.is_some())
+    }
+
+    #[allow(clippy::significant_drop_tightening)]
+    fn remove_if(&self, article_id: &ArticleId, expected: Expectation) -> Result<(), PersistenceError> {
+        info!("calling remove");
+
+        let mut connection = self.connection.lock().expect("connection is poisoned");
+        let transaction = connection.transaction()?;
+        check_expectation_in(&transaction, article_id, expected)?;
+        // 以前に同じIDでゴミ箱へ移された記事は上書きされる
+        transaction.execute(
+            "DELETE FROM article_trash WHERE id = ?1",
+            params![article_id.0],
+        )?;
+        transaction.execute(
+            "DELETE FROM article_trash_revision WHERE article_id = ?1",
+            params![article_id.0],
+        )?;
+        let moved = transaction.execute(
+            "INSERT INTO article_trash (id, content, visibility, created_at, updated_at, created_by, updated_by, tags, title, summary, lang, publish_at, draft, deleted_at) \
+             SELECT id, content, visibility, created_at, updated_at, created_by, updated_by, tags, title, summary, lang, publish_at, draft, ?2 FROM article WHERE id = ?1",
+            params![article_id.0, Local::now().timestamp_micros()],
+        )?;
+
+        if moved == 0 {
+            // 記事が存在しないときはゴミ箱にも触れない
+            return Ok(())
+        }
+
+        transaction.execute(
+            "INSERT INTO article_trash_revision (article_id, revision, created_at, content) \
+             SELECT article_id, revision, created_at, content FROM article_revision WHERE article_id = ?1",
+            params![article_id.0],
+        )?;
+        transaction.execute(
+            "DELETE FROM article WHERE id = ?1",
+            params![article_id.0],
+        )?;
+        transaction.execute(
+            "DELETE FROM article_revision WHERE article_id = ?1",
+            params![article_id.0],
+        )?;
+        transaction.execute(
+            "DELETE FROM article_share_link WHERE article_id = ?1",
+            params![article_id.0],
+        )?;
+        transaction.commit()?;
+
+        Ok(())
+    }
+
+    #[allow(clippy::significant_drop_tightening)]
+    fn rename_if(&self, old_id: &ArticleId, expected: Expectation, new_id: ArticleId) -> Result<(), PersistenceError> {
+        let mut connection = self.connection.lock().expect("connection is poisoned");
+        let transaction = connection.transaction()?;
+        check_expectation_in(&transaction, old_id, expected)?;
+
+        let new_id_is_taken = transaction.query_row(
+            "SELECT 1 FROM article WHERE id = ?1",
+            params![new_id.0],
+            |_| Ok(()),
+        ).optional()?.is_some();
+
+        if new_id_is_taken {
+            return Err(PersistenceError::DuplicatedId)
+        }
+
+        let updated = transaction.execute(
+            "UPDATE article SET id = ?2 WHERE id = ?1",
+            params![old_id.0, new_id.0],
+        )?;
+
+        if updated == 0 {
+            return Err(PersistenceError::AbsentValue)
+        }
+
+        transaction.execute(
+            "UPDATE article_revision SET article_id = ?2 WHERE article_id = ?1",
+            params![old_id.0, new_id.0],
+        )?;
+        transaction.execute(
+            "UPDATE article_share_link SET article_id = ?2 WHERE article_id = ?1",
+            params![old_id.0, new_id.0],
+        )?;
+
+        transaction.commit()?;
+
+        Ok(())
+    }
+
+    #[allow(clippy::significant_drop_tightening)]
+    fn share_links(&self, article_id: &ArticleId) -> Result<Vec<ArticleShareLink>, PersistenceError> {
+        if !self.exists(article_id)? {
+            return Err(PersistenceError::AbsentValue)
+        }
+
+        let connection = self.connection.lock().expect("connection is poisoned");
+        let mut statement = connection.prepare_cached(
+            "SELECT id, digest, created_at, expires_at FROM article_share_link WHERE article_id = ?1 ORDER BY created_at, id",
+        )?;
+        let links = statement.query_map(params![article_id.0], |row| {
+            Ok(ArticleShareLink {
+                id: ShareLinkId(row.get(0)?),
+                digest: row.get(1)?,
+                created_at: datetime_from_sql(row.get(2)?)?,
+                expires_at: row.get::<_, Option<i64>>(3)?.map(datetime_from_sql).transpose()?,
+            })
+        })?;
+
+        Ok(links.collect::<Result<Vec<_>, _>>()?)
+    }
+
+    fn add_share_link(&self, article_id: &ArticleId, link: ArticleShareLink) -> Result<(), PersistenceError> {
+        let added = self.connection.lock().expect("connection is poisoned").execute(
+            "INSERT INTO article_share_link (article_id, id, digest, created_at, expires_at) \
+             SELECT id, ?2, ?3, ?4, ?5 FROM article WHERE id = ?1",
+            params![
+                article_id.0,
+                link.id.0,
+                link.digest,
+                link.created_at.timestamp_micros(),
+                link.expires_at.map(|x| x.timestamp_micros()),
+            ],
+        )?;
+
+        if added == 0 {
+            return Err(PersistenceError::AbsentValue)
+        }
+
+        Ok(())
+    }
+
+    fn revoke_share_link(&self, article_id: &ArticleId, link_id: &ShareLinkId) -> Result<(), PersistenceError> {
+        let deleted = self.connection.lock().expect("connection is poisoned").execute(
+            "DELETE FROM article_share_link WHERE article_id = ?1 AND id = ?2",
+            params![article_id.0, link_id.0],
+        )?;
+
+        if deleted == 0 {
+            return Err(PersistenceError::AbsentValue)
+        }
+
+        Ok(())
+    }
+
+    fn trashed_entries(&self) -> Result<Vec<(ArticleId, TrashedArticle)>, PersistenceError> {
+        self.query_trash()
+    }
+
+    #[allow(clippy::significant_drop_tightening)]
+    fn restore(&self, article_id: &ArticleId) -> Result<(), PersistenceError> {
+        let mut connection = self.connection.lock().expect("connection is poisoned");
+        let transaction = connection.transaction()?;
+
+        let conflicted = transaction.query_row(
+            "SELECT 1 FROM article WHERE id = ?1",
+            params![article_id.0],
+            |_| Ok(()),
+        ).optional()?.is_some();
+
+        if conflicted {
+            return Err(PersistenceError::DuplicatedId)
+        }
+
+        let restored = transaction.execute(
+            "INSERT INTO article (id, content, visibility, created_at, updated_at, created_by, updated_by, tags, title, summary, lang, publish_at, draft) \
+             SELECT id, content, visibility, created_at, updated_at, created_by, updated_by, tags, title, summary, lang, publish_at, draft FROM article_trash WHERE id = ?1",
+            params![article_id.0],
+        )?;
+
+        if restored == 0 {
+            return Err(PersistenceError::AbsentValue)
+        }
+
+        transaction.execute(
+            "DELETE FROM article_revision WHERE article_id = ?1",
+            params![article_id.0],
+        )?;
+        transaction.execute(
+            "INSERT INTO article_revision (article_id, revision, created_at, content) \
+             SELECT article_id, revision, created_at, content FROM article_trash_revision WHERE article_id = ?1",
+            params![article_id.0],
+        )?;
+        transaction.execute(
+            "DELETE FROM article_trash WHERE id = ?1",
+            params![article_id.0],
+        )?;
+        transaction.execute(
+            "DELETE FROM article_trash_revision WHERE article_id = ?1",
+            params![article_id.0],
+        )?;
+        transaction.commit()?;
+
+        Ok(())
+    }
+
+    #[allow(clippy::significant_drop_tightening)]
+    fn purge(&self, article_id: &ArticleId) -> Result<(), PersistenceError> {
+        let mut connection = self.connection.lock().expect("connection is poisoned");
+        let transaction = connection.transaction()?;
+
+        let purged = transaction.execute(
+            "DELETE FROM article_trash WHERE id = ?1",
+            params![article_id.0],
+        )?;
+
+        if purged == 0 {
+            return Err(PersistenceError::AbsentValue)
+        }
+
+        transaction.execute(
+            "DELETE FROM article_trash_revision WHERE article_id = ?1",
+            params![article_id.0],
+        )?;
+        transaction.commit()?;
+
+        Ok(())
+    }
+
+    #[allow(clippy::significant_drop_tightening)]
+    fn purge_trashed_before(&self, threshold: DateTime<Local>) -> Result<usize, PersistenceError> {
+        let threshold = threshold.timestamp_micros();
+        let mut connection = self.connection.lock().expect("connection is poisoned");
+        let transaction = connection.transaction()?;
+
+        transaction.execute(
+            "DELETE FROM article_trash_revision WHERE article_id IN (SELECT id FROM article_trash WHERE deleted_at < ?1)",
+            params![threshold],
+        )?;
+        let purged = transaction.execute(
+            "DELETE FROM article_trash WHERE deleted_at < ?1",
+            params![threshold],
+        )?;
+        transaction.commit()?;
+
+        Ok(purged)
+    }
+
+    fn external_generation(&self) -> Result<u64, PersistenceError> {
+        // 他の接続がコミットするたびに変わる。この接続自身の書き込みでは変わらない。
+        let version: i64 = self.connection.lock().expect("connection is poisoned")
+            .query_row("PRAGMA data_version", [], |row| row.get(0))?;
+
+        Ok(version.unsigned_abs())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+    use chrono::{Duration, Local};
+    use toy_blog_endpoint_model::{Article, ArticleDraft, ArticleId, ArticleMetadata, ArticleShareLink, ShareLinkId, Visibility};
+    use crate::service::persistence::{ArticleStore, PersistenceError, SqliteArticleStore};
+
+    #[test]
+    fn round_trip() {
+        let m = tempfile::NamedTempFile::new().expect("failed to initialize temporary file");
+        let store = SqliteArticleStore::open(m.path()).unwrap();
+        let id = ArticleId::new("12345".to_string());
+        store.create_entry(&id, "12345 Hello".to_string(), Visibility::Private, Some("alice")).unwrap();
+        store.update_entry(&id, "12345 Bye".to_string(), Some("bob")).unwrap();
+        store.change_visibility(&id, Visibility::Public).unwrap();
+        store.change_tags(&id, BTreeSet::from(["rust".to_string(), "日記".to_string()])).unwrap();
+        store.change_metadata(&id, ArticleMetadata {
+            title: Some("挨拶".to_string()),
+            summary: None,
+            lang: Some("ja".to_string()),
+        }).unwrap();
+        let draft = ArticleDraft {
+            content: "12345 Draft".to_string(),
+            updated_at: Local::now(),
+            updated_by: Some("alice".to_string()),
+        };
+        store.change_draft(&id, Some(draft.clone())).unwrap();
+        drop(store);
+
+        let store = SqliteArticleStore::open(m.path()).unwrap();
+        let article = store.read_snapshot(&id).unwrap();
+        assert_eq!(article.content, "12345 Bye");
+        assert_eq!(article.visibility, Visibility::Public);
+        assert!(article.created_at <= article.updated_at);
+        assert_eq!(article.created_by.as_deref(), Some("alice"));
+        assert_eq!(article.updated_by.as_deref(), Some("bob"));
+        assert_eq!(article.tags, BTreeSet::from(["rust".to_string(), "日記".to_string()]));
+        assert_eq!(article.draft.as_ref(), Some(&draft));
+
+        store.remove(&id).unwrap();
+        store.restore(&id).unwrap();
+        let restored = store.read_snapshot(&id).unwrap();
+        assert_eq!(restored.tags.len(), 2);
+        assert_eq!(restored.title.as_deref(), Some("挨拶"));
+        assert_eq!(restored.lang.as_deref(), Some("ja"));
+        assert_eq!(restored.draft, Some(draft));
+    }
+
+    #[test]
+    fn database_without_author_columns_is_upgraded() {
+        let m = tempfile::NamedTempFile::new().expect("failed to initialize temporary file");
+        rusqlite::Connection::open(m.path()).unwrap().execute_batch(
+            "CREATE TABLE article (id TEXT PRIMARY KEY NOT NULL, content TEXT NOT NULL, visibility TEXT NOT NULL, created_at INTEGER NOT NULL, updated_at INTEGER NOT NULL);
+             INSERT INTO article VALUES ('12345', 'old', 'public', 0, 0);"
+        ).unwrap();
+
+        let store = SqliteArticleStore::open(m.path()).unwrap();
+        let id = ArticleId::new("12345".to_string());
+        assert_eq!(store.read_snapshot(&id).unwrap().created_by, None);
+        assert!(store.read_snapshot(&id).unwrap().tags.is_empty());
+        assert_eq!(store.read_snapshot(&id).unwrap().title, None);
+        store.update_entry(&id, "new".to_string(), Some("alice")).unwrap();
+        assert_eq!(store.read_snapshot(&id).unwrap().updated_by.as_deref(), Some("alice"));
+    }
+
+    #[test]
+    fn due_articles_are_published() {
+        let m = tempfile::NamedTempFile::new().expect("failed to initialize temporary file");
+        let store = SqliteArticleStore::open(m.path()).unwrap();
+        let due = ArticleId::new("due".to_string());
+        let later = ArticleId::new("later".to_string());
+        let now = Local::now();
+        for (id, publish_at) in [(&due, now - Duration::minutes(1)), (&later, now + Duration::days(1))] {
+            store.create_entry(id, String::new(), Visibility::Private, None).unwrap();
+            store.schedule_publication(id, Some(publish_at)).unwrap();
+        }
+
+        assert_eq!(store.publish_due(now).unwrap(), std::slice::from_ref(&due));
+        let published = store.read_snapshot(&due).unwrap();
+        assert_eq!(published.visibility, Visibility::Public);
+        assert_eq!(published.publish_at, None);
+        assert!(store.publish_due(now).unwrap().is_empty());
+
+        store.change_visibility(&later, Visibility::Restricted).unwrap();
+        assert_eq!(store.read_snapshot(&later).unwrap().publish_at, None);
+    }
+
+    #[test]
+    fn absent_article() {
+        let m = tempfile::NamedTempFile::new().expect("failed to initialize temporary file");
+        let store = SqliteArticleStore::open(m.path()).unwrap();
+        let id = ArticleId::new("12345".to_string());
+
+        assert!(!store.exists(&id).unwrap());
+        assert!(matches!(store.read_snapshot(&id), Err(PersistenceError::AbsentValue)));
+        assert!(matches!(store.update_entry(&id, String::new(), None), Err(PersistenceError::AbsentValue)));
+        assert!(matches!(store.rename(&id, ArticleId::new("23456".to_string())), Err(PersistenceError::AbsentValue)));
+        assert!(matches!(store.change_tags(&id, BTreeSet::new()), Err(PersistenceError::AbsentValue)));
+    }
+
+    #[test]
+    fn created_between_is_half_open() {
+        let m = tempfile::NamedTempFile::new().expect("failed to initialize temporary file");
+        let store = SqliteArticleStore::open(m.path()).unwrap();
+        let id = ArticleId::new("12345".to_string());
+        store.create_entry(&id, "12345".to_string(), Visibility::Public, None).unwrap();
+        let created_at = store.read_snapshot(&id).unwrap().created_at;
+
+        let hit = store.entries_created_between(created_at, created_at + Duration::seconds(1)).unwrap();
+        assert_eq!(hit.len(), 1);
+        let miss = store.entries_created_between(created_at - Duration::seconds(1), created_at).unwrap();
+        assert!(miss.is_empty());
+        let miss = store.entries_created_between(Local::now() + Duration::days(1), Local::now() + Duration::days(2)).unwrap();
+        assert!(miss.is_empty());
+    }
+
+    #[test]
+    fn update_appends_revision() {
+        let m = tempfile::NamedTempFile::new().expect("failed to initialize temporary file");
+        let store = SqliteArticleStore::open(m.path()).unwrap();
+        let id = ArticleId::new("12345".to_string());
+        store.create_entry(&id, "first".to_string(), Visibility::Private, None).unwrap();
+        store.update_entry(&id, "second".to_string(), None).unwrap();
+        let new_id = ArticleId::new("23456".to_string());
+        store.rename(&id, new_id.clone()).unwrap();
+
+        let revisions = store.revisions(&new_id).unwrap();
+        let contents = revisions.iter().map(|x| (x.revision.into_inner(), x.content.as_str())).collect::<Vec<_>>();
+        assert_eq!(contents, [(1, "first"), (2, "second")]);
+        assert!(matches!(store.revisions(&id), Err(PersistenceError::AbsentValue)));
+    }
+
+    #[test]
+    fn rename_to_existing_id_fails() {
+        let m = tempfile::NamedTempFile::new().expect("failed to initialize temporary file");
+        let store = SqliteArticleStore::open(m.path()).unwrap();
+        let a = ArticleId::new("12345".to_string());
+        let b = ArticleId::new("23456".to_string());
+        store.create_entry(&a, "a".to_string(), Visibility::Private, None).unwrap();
+        store.create_entry(&b, "b".to_string(), Visibility::Private, None).unwrap();
+
+        assert!(matches!(store.rename(&a, b.clone()), Err(PersistenceError::DuplicatedId)));
+        assert_eq!(store.read_snapshot(&a).unwrap().content, "a");
+        assert_eq!(store.read_snapshot(&b).unwrap().content, "b");
+    }
+
+
+    #[test]
+    fn remove_and_restore_keep_revisions() {
+        let m = tempfile::NamedTempFile::new().expect("failed to initialize temporary file");
+        let store = SqliteArticleStore::open(m.path()).unwrap();
+        let id = ArticleId::new("12345".to_string());
+        store.create_entry(&id, "first".to_string(), Visibility::Private, None).unwrap();
+        store.update_entry(&id, "second".to_string(), None).unwrap();
+        store.remove(&id).unwrap();
+
+        assert!(!store.exists(&id).unwrap());
+        assert_eq!(store.trashed_entries().unwrap().len(), 1);
+
+        store.create_entry(&id, "other".to_string(), Visibility::Private, None).unwrap();
+        assert!(matches!(store.restore(&id), Err(PersistenceError::DuplicatedId)));
+        store.remove(&id).unwrap();
+        store.purge(&id).unwrap();
+        assert!(store.trashed_entries().unwrap().is_empty());
+        assert!(matches!(store.restore(&id), Err(PersistenceError::AbsentValue)));
+    }
+
+    #[test]
+    fn purge_trashed_before_keeps_recent_entries() {
+        let m = tempfile::NamedTempFile::new().expect("failed to initialize temporary file");
+        let store = SqliteArticleStore::open(m.path()).unwrap();
+        let id = ArticleId::new("12345".to_string());
+        store.create_entry(&id, "first".to_string(), Visibility::Private, None).unwrap();
+        store.update_entry(&id, "second".to_string(), None).unwrap();
+        store.remove(&id).unwrap();
+
+        assert_eq!(store.purge_trashed_before(Local::now() - Duration::days(1)).unwrap(), 0);
+        store.restore(&id).unwrap();
+        assert_eq!(store.revisions(&id).unwrap().len(), 2);
+        store.remove(&id).unwrap();
+        assert_eq!(store.purge_trashed_before(Local::now() + Duration::days(1)).unwrap(), 1);
+        assert!(store.trashed_entries().unwrap().is_empty());
+    }
+
+    #[test]
+    fn conditional_write_sees_concurrent_update() {
+        let m = tempfile::NamedTempFile::new().expect("failed to initialize temporary file");
+        let store = SqliteArticleStore::open(m.path()).unwrap();
+        let id = ArticleId::new("12345".to_string());
+        store.create_entry(&id, "first".to_string(), Visibility::Private, None).unwrap();
+
+        let read = store.read_snapshot(&id).unwrap();
+        store.update_entry(&id, "concurrent".to_string(), None).unwrap();
+        let unchanged = |current: &Article| current.content == read.content;
+
+        assert!(matches!(store.update_entry_if(&id, &unchanged, "stale".to_string(), None), Err(PersistenceError::PreconditionFailed)));
+        assert!(matches!(store.change_visibility_if(&id, &unchanged, Visibility::Public), Err(PersistenceError::PreconditionFailed)));
+        assert!(matches!(store.remove_if(&id, &unchanged), Err(PersistenceError::PreconditionFailed)));
+        assert!(matches!(store.rename_if(&id, &unchanged, ArticleId::new("23456".to_string())), Err(PersistenceError::PreconditionFailed)));
+
+        let article = store.read_snapshot(&id).unwrap();
+        assert_eq!(article.content, "concurrent");
+        assert_eq!(article.visibility, Visibility::Private);
+        assert_eq!(store.revisions(&id).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn publish_draft_appends_revision_and_clears_draft() {
+        let m = tempfile::NamedTempFile::new().expect("failed to initialize temporary file");
+        let store = SqliteArticleStore::open(m.path()).unwrap();
+        let id = ArticleId::new("12345".to_string());
+        store.create_entry(&id, "first".to_string(), Visibility::Private, None).unwrap();
+        assert!(matches!(store.publish_draft(&id, None), Err(PersistenceError::AbsentValue)));
+
+        store.change_draft(&id, Some(ArticleDraft { content: "second".to_string(), updated_at: Local::now(), updated_by: None })).unwrap();
+        let previous = store.publish_draft(&id, Some("alice")).unwrap();
+        assert_eq!(previous.content, "first");
+
+        let article = store.read_snapshot(&id).unwrap();
+        assert_eq!(article.content, "second");
+        assert_eq!(article.updated_by.as_deref(), Some("alice"));
+        assert!(article.draft.is_none());
+        assert_eq!(store.revisions(&id).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn share_links_are_dropped_with_article() {
+        let m = tempfile::NamedTempFile::new().expect("failed to initialize temporary file");
+        let store = SqliteArticleStore::open(m.path()).unwrap();
+        let id = ArticleId::new("12345".to_string());
+        let link = ArticleShareLink {
+            id: ShareLinkId("link".to_string()),
+            digest: "00".to_string(),
+            created_at: Local::now(),
+            expires_at: Some(Local::now() + Duration::hours(1)),
+        };
+        assert!(matches!(store.add_share_link(&id, link.clone()), Err(PersistenceError::AbsentValue)));
+
+        store.create_entry(&id, "first".to_string(), Visibility::Restricted, None).unwrap();
+        store.add_share_link(&id, link.clone()).unwrap();
+        let links = store.share_links(&id).unwrap();
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].id, link.id);
+        assert_eq!(links[0].expires_at.map(|x| x.timestamp_micros()), link.expires_at.map(|x| x.timestamp_micros()));
+
+        store.remove(&id).unwrap();
+        store.restore(&id).unwrap();
+        assert!(store.share_links(&id).unwrap().is_empty());
+        assert!(matches!(store.revoke_share_link(&id, &link.id), Err(PersistenceError::AbsentValue)));
+    }
+}
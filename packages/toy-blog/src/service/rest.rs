@@ -1,27 +1,38 @@
 mod api;
+mod audit;
 mod cors;
-pub(in crate::service) mod repository;
-mod auth;
+pub(in crate::service) mod auth;
 mod exposed_representation_format;
 mod header;
+mod precondition;
+mod throttle;
 
-use std::fs::File;
 use std::io::stdin;
 use std::net::{IpAddr, Ipv4Addr};
 use std::path::Path;
-use actix_web::{App, HttpResponseBuilder, HttpServer};
-use actix_web::dev::{ServiceRequest, ServiceResponse};
+use std::sync::Arc;
+use std::time::Duration;
+use actix_web::{App, HttpMessage, HttpRequest, HttpResponseBuilder, HttpServer};
+use actix_web::web::Data;
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse};
+use actix_web::http::header::{AUTHORIZATION, RETRY_AFTER};
 use actix_web::http::StatusCode;
 use actix_web::middleware::Logger;
-use anyhow::Context;
-use log::info;
-use serde_json::Value;
+use anyhow::{bail, Context};
+use chrono::{Local, TimeDelta};
+use log::{error, info, warn};
 use inner_no_leak::ComposeInternalError;
-use crate::service::persistence::ArticleRepository;
-use crate::service::rest::api::{article, meta};
+use toy_blog_endpoint_model::{AuditAction, Visibility};
+use crate::service::cli::StorageBackend;
+use crate::service::migrate::migrate_article_file;
+use crate::service::persistence::{ArticleRepository, ArticleStore, SearchIndexedStore, SqliteArticleStore};
+use crate::service::rest::api::{article, draft, meta, revision, schedule, search, share, tag, trash};
+use crate::service::rest::api::audit as audit_api;
+use crate::service::rest::audit::{AuditEvent, AuditLog};
 use crate::service::rest::api::list::{article_id_list, article_id_list_by_year, article_id_list_by_year_and_month};
-use crate::service::rest::auth::WRITE_TOKEN;
-use crate::service::rest::repository::GLOBAL_ARTICLE_REPOSITORY;
+use crate::service::rest::auth::{NamedToken, ReloadableTokenStore, token_name_for_log, TokenLookup, TOKEN_STORE};
+use crate::service::rest::precondition::PreconditionPolicy;
+use crate::service::rest::throttle::{AuthFailureThrottle, Verdict};
 use actix_web::web::scope as prefixed_service;
 use actix_web_httpauth::extractors::bearer::Config as BearerAuthConfig;
 use futures_util::future::LocalBoxFuture;
@@ -44,30 +55,203 @@ mod inner_no_leak {
     }
 }
 
-async fn migrate_and_load(path: impl AsRef<Path>) -> ArticleRepository {
+pub(in crate::service) const ARTICLE_FILE_PATH: &str = "data/article.json";
+pub(in crate::service) const TOKEN_FILE_PATH: &str = "data/token.json";
+const AUDIT_LOG_PATH: &str = "data/audit.jsonl";
+/// 標準入力から与えられたトークンの名前
+const STDIN_TOKEN_NAME: &str = "stdin";
+const ARTICLE_DATABASE_PATH: &str = "data/article.sqlite3";
+
+async fn migrate_and_load(path: impl AsRef<Path>, keep_backups: usize) -> Result<ArticleRepository, anyhow::Error> {
     ArticleRepository::create_default_file_if_absent(path.as_ref());
-    {
-        #[allow(unused_qualifications)]
-            let migrated_data = crate::migration::migrate_article_repr(
-            serde_json::from_reader::<_, Value>(File::open(path.as_ref()).expect("failed to read existing config"))
-                .expect("failed to deserialize config")
-        );
-
-        info!("migrated");
-
-        serde_json::to_writer(
-            File::options().write(true).truncate(true).open(path.as_ref()).expect("failed to write over existing config"),
-            &migrated_data
-        )
-            .expect("failed to serialize config");
+    migrate_article_file(path.as_ref(), keep_backups)?;
+
+    Ok(ArticleRepository::new(path.as_ref()).await)
+}
+
+/// `keep_backups`は、記事ファイルの移行前に取るバックアップを何個まで残すか。
+pub(in crate::service) async fn open_article_store(storage: StorageBackend, keep_backups: usize) -> Result<Arc<dyn ArticleStore>, anyhow::Error> {
+    let store: Arc<dyn ArticleStore> = match storage {
+        StorageBackend::Json => Arc::new(migrate_and_load(ARTICLE_FILE_PATH, keep_backups).await?),
+        StorageBackend::Sqlite => {
+            // JSONの記事を置いたまま空のデータベースで起動すると、記事がすべて消えたように見えてしまう
+            if Path::new(ARTICLE_FILE_PATH).exists() && !Path::new(ARTICLE_DATABASE_PATH).exists() {
+                bail!(
+                    "{ARTICLE_FILE_PATH} exists but {ARTICLE_DATABASE_PATH} does not; the SQLite backend does not read articles from {ARTICLE_FILE_PATH}. \
+                    Keep using `--storage json`, or move {ARTICLE_FILE_PATH} away to start with an empty database."
+                )
+            }
+
+            Arc::new(SqliteArticleStore::open(ARTICLE_DATABASE_PATH).context("while opening article database")?)
+        }
+    };
+
+    Ok(store)
+}
+
+/// ゴミ箱の中身を自動で削除するかどうかを確認する間隔
+const TRASH_PURGE_INTERVAL: Duration = Duration::from_hours(1);
+
+/// `retention`より前にゴミ箱へ移された記事を定期的に完全に削除する。
+fn spawn_trash_purger(repo: Arc<dyn ArticleStore>, retention: TimeDelta) {
+    actix_web::rt::spawn(async move {
+        let mut interval = actix_web::rt::time::interval(TRASH_PURGE_INTERVAL);
+
+        loop {
+            interval.tick().await;
+
+            match repo.purge_trashed_before(Local::now() - retention) {
+                Ok(0) => {}
+                Ok(purged) => info!("purged {purged} article(s) from trash"),
+                Err(e) => error!("failed to purge trash: {e}"),
+            }
+        }
+    });
+}
+
+/// 公開の予約を確認する間隔。記事は予約された日時からこの間隔以内に公開される。
+const PUBLICATION_CHECK_INTERVAL: Duration = Duration::from_mins(1);
+/// 予約された記事の公開を監査ログに記録する際の、トークンの名前の代わり
+const PUBLICATION_SCHEDULER_NAME: &str = "scheduler";
+
+/// 予約された日時になった記事を定期的に公開する。予約はリポジトリに保存されているので、
+/// サーバーが止まっている間に予約の日時が過ぎた記事は、起動した直後に公開される。
+fn spawn_publication_scheduler(repo: Arc<dyn ArticleStore>, audit: Arc<AuditLog>) {
+    actix_web::rt::spawn(async move {
+        let mut interval = actix_web::rt::time::interval(PUBLICATION_CHECK_INTERVAL);
+
+        loop {
+            interval.tick().await;
+
+            match repo.publish_due(Local::now()) {
+                Ok(published) => for article_id in published {
+                    info!("published scheduled article {article_id}");
+                    audit.record_unattended(PUBLICATION_SCHEDULER_NAME, AuditEvent {
+                        visibility: Some(Visibility::Public),
+                        ..AuditEvent::new(AuditAction::Publish, &article_id)
+                    });
+                },
+                Err(e) => error!("failed to publish scheduled articles: {e}"),
+            }
+        }
+    });
+}
+
+/// `data/token.json`と標準入力から与えられたトークンを読み込む。
+fn load_token_store(bearer_token: &str) -> Result<ReloadableTokenStore, anyhow::Error> {
+    // 標準入力から与えられたトークンは、互換性のために全ての操作を許可する。
+    // 標準入力が空の場合は`data/token.json`のトークンだけを使う
+    let fixed_tokens = if bearer_token.is_empty() {
+        vec![]
+    } else {
+        vec![NamedToken::with_all_scopes(STDIN_TOKEN_NAME.to_string(), bearer_token)]
+    };
+    let token_store = ReloadableTokenStore::load(TOKEN_FILE_PATH, fixed_tokens)?;
+    if token_store.current().is_empty() {
+        bail!("no token is configured. Give one from stdin, or write hashed ones to {TOKEN_FILE_PATH}.")
     }
 
-    ArticleRepository::new(path.as_ref()).await
+    Ok(token_store)
 }
 
-pub async fn boot_http_server(port: u16, host: &str, proxied_by_cloudflare: bool) -> Result<(), anyhow::Error> {
-    const PATH: &str = "data/article.json";
+/// `SIGHUP`を受け取るたびに`data/token.json`を読み直す。
+#[cfg(unix)]
+fn spawn_token_reloader() -> Result<(), anyhow::Error> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangup = signal(SignalKind::hangup()).context("while listening SIGHUP")?;
+    actix_web::rt::spawn(async move {
+        while hangup.recv().await.is_some() {
+            let store = TOKEN_STORE.get().expect("token store is not initialized");
+            match store.reload() {
+                Ok(()) if store.current().is_empty() => warn!("reloaded {TOKEN_FILE_PATH}, but no token is configured"),
+                Ok(()) => info!("reloaded {TOKEN_FILE_PATH}"),
+                Err(e) => error!("failed to reload {TOKEN_FILE_PATH}, keeping current tokens: {e:#}"),
+            }
+        }
+    });
 
+    Ok(())
+}
+
+/// クライアントの実際のアドレス。Cloudflare tunnelを経由している場合は`CF-Connecting-IP`から取得する。
+fn extract_real_ip(req: &HttpRequest, proxied_by_cloudflare: bool) -> Option<IpAddr> {
+    if proxied_by_cloudflare {
+        req.headers().get("CF-Connecting-IP")?.to_str().ok()?.parse::<IpAddr>().ok()
+    } else {
+        req.peer_addr().map(|x| x.ip())
+    }
+}
+
+/// 認証に失敗し続けているクライアントからのリクエストを`429`で拒否し、認証の成否を記録する。
+///
+/// 認証を伴うリクエストだけを数える。認証を伴わない閲覧は締め出さない。
+/// 成否はハンドラーが[`authorize`](auth::authorize)で照合した結果 ([`TokenLookup`]) で決め、応答の状態には依らない。
+fn throttle_authentication<S>(
+    req: ServiceRequest,
+    srv: &S,
+    throttle: &Arc<AuthFailureThrottle>,
+    proxied_by_cloudflare: bool,
+) -> LocalBoxFuture<'static, Result<ServiceResponse, actix_web::Error>>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse, Error = actix_web::Error>,
+    S::Future: 'static,
+{
+    let client = extract_real_ip(req.request(), proxied_by_cloudflare)
+        .filter(|_| req.headers().contains_key(AUTHORIZATION));
+    let Some(client) = client else {
+        return Box::pin(srv.call(req))
+    };
+
+    if let Verdict::TooManyAttempts { retry_after } = throttle.check(client) {
+        let response = HttpResponseBuilder::new(StatusCode::TOO_MANY_REQUESTS)
+            .insert_header((RETRY_AFTER, retry_after.as_secs().max(1)))
+            .body("Too many failed authentication attempts. Try again later.");
+        return Box::pin(async {
+            Ok(ServiceResponse::new(req.into_parts().0, response))
+        })
+    }
+
+    let throttle = throttle.clone();
+    Box::pin(srv.call(req).map(move |res| {
+        if let Ok(res) = &res {
+            // 応答の状態からは判断しない。記事が無い`404`や権限の無い`403`で記録が消えると、トークンを推測し放題になる
+            match res.request().extensions().get::<TokenLookup>() {
+                Some(TokenLookup::Matched { .. }) => throttle.record_success(client),
+                Some(TokenLookup::Unmatched) => throttle.record_failure(client),
+                // ハンドラーに届く前に、形式の正しくない`Authorization`で拒否された
+                None if res.status() == StatusCode::UNAUTHORIZED => throttle.record_failure(client),
+                None => {}
+            }
+        }
+
+        res
+    }))
+}
+
+/// はてなブックマークのクローラーからのリクエストを`403`で拒否する。
+fn reject_hatena_bookmark_crawler<S>(
+    req: ServiceRequest,
+    srv: &S,
+    proxied_by_cloudflare: bool,
+) -> LocalBoxFuture<'static, Result<ServiceResponse, actix_web::Error>>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse, Error = actix_web::Error>,
+    S::Future: 'static,
+{
+    const HATENA_BOOKMARK_CRAWLER: Ipv4Addr = Ipv4Addr::new(133, 242, 243, 6);
+
+    if extract_real_ip(req.request(), proxied_by_cloudflare).is_some_and(|x| x == HATENA_BOOKMARK_CRAWLER) {
+        Box::pin(async {
+            Ok(ServiceResponse::new(req.into_parts().0, HttpResponseBuilder::new(StatusCode::FORBIDDEN).body("Forbidden")))
+        })
+    } else {
+        Box::pin(srv.call(req))
+    }
+}
+
+/// `require_precondition`が真の場合、`If-Match`も`If-Unmodified-Since`も無い記事の更新・削除・公開範囲とIDの変更を`428`で拒否する。
+pub async fn boot_http_server(port: u16, host: &str, proxied_by_cloudflare: bool, storage: StorageBackend, trash_retention_days: Option<u32>, keep_backups: usize, require_precondition: bool) -> Result<(), anyhow::Error> {
     let bearer_token = {
         let mut buf = String::new();
         stdin().read_line(&mut buf).expect("failed to read from stdin");
@@ -75,16 +259,31 @@ pub async fn boot_http_server(port: u16, host: &str, proxied_by_cloudflare: bool
     };
     // migration
 
-    let repo = migrate_and_load(PATH).await;
-    WRITE_TOKEN.set(bearer_token).unwrap();
+    let search_index = Arc::new(
+        SearchIndexedStore::new(open_article_store(storage, keep_backups).await?).context("while building search index")?
+    );
+    let repo: Arc<dyn ArticleStore> = search_index.clone();
+    TOKEN_STORE.set(load_token_store(&bearer_token)?).expect("token store is already initialized");
+    #[cfg(unix)]
+    spawn_token_reloader()?;
+
+    if let Some(days) = trash_retention_days {
+        spawn_trash_purger(repo.clone(), TimeDelta::days(days.into()));
+    }
+
+    let throttle = Arc::new(AuthFailureThrottle::new());
+    let audit_log = Data::new(
+        AuditLog::open(AUDIT_LOG_PATH, proxied_by_cloudflare).with_context(|| format!("while opening {AUDIT_LOG_PATH}"))?
+    );
+    spawn_publication_scheduler(repo.clone(), audit_log.clone().into_inner());
 
     // TODO: AppやHttpServerの型変数が記述できないため関数にくくり出せない
-    GLOBAL_ARTICLE_REPOSITORY.set(repo).expect("unreachable!");
-    let http_server_closure = |proxied_by_cloudflare| {
+    let http_server_closure = move |proxied_by_cloudflare| {
+        let throttle = throttle.clone();
         let logger_format = if proxied_by_cloudflare {
-            r#"%a (CF '%{CF-Connecting-IP}i') %t "%r" %s "%{Referer}i" "%{User-Agent}i" "#
+            r#"%a (CF '%{CF-Connecting-IP}i') %t "%r" %s "%{Referer}i" "%{User-Agent}i" token=%{token}xo "#
         } else {
-            r#"%a %t "%r" %s "%{Referer}i" "%{User-Agent}i" "#
+            r#"%a %t "%r" %s "%{Referer}i" "%{User-Agent}i" token=%{token}xo "#
         };
 
         App::new()
@@ -99,10 +298,35 @@ pub async fn boot_http_server(port: u16, host: &str, proxied_by_cloudflare: bool
                                     article::update,
                                     article::remove,
                                     article::update_visibility,
+                                    revision::list,
+                                    revision::fetch,
+                                    revision::diff,
+                                    revision::revert,
+                                    share::create,
+                                    share::list,
+                                    share::revoke,
                                 )
-                            ),
+                            )
+                            .service((tag::update, article::update_metadata, schedule::update, schedule::cancel))
+                            .service((draft::fetch, draft::update, draft::discard, draft::publish)),
                         prefixed_service("/meta")
                             .service(meta::change_id),
+                        prefixed_service("/trash")
+                            .service(
+                                (
+                                    trash::list,
+                                    trash::restore,
+                                    trash::purge,
+                                )
+                            ),
+                        prefixed_service("/schedule")
+                            .service(schedule::list),
+                        prefixed_service("/tags")
+                            .service(tag::list),
+                        prefixed_service("/search")
+                            .service(search::search),
+                        prefixed_service("/audit")
+                            .service(audit_api::list),
                         prefixed_service("/list")
                             .service(article_id_list)
                             .service(article_id_list_by_year)
@@ -110,35 +334,18 @@ pub async fn boot_http_server(port: u16, host: &str, proxied_by_cloudflare: bool
                     )
                 )
             )
+            .app_data(Data::from(repo.clone()))
+            .app_data(Data::from(search_index.clone()))
+            .app_data(audit_log.clone())
+            .app_data(Data::new(PreconditionPolicy { required: require_precondition }))
             .app_data(
                 BearerAuthConfig::default()
                     .realm("Perform write operation")
                     .scope("article:write"),
             )
-            .wrap_fn(move |req, srv| {
-                let cloudflare_support = proxied_by_cloudflare;
-
-                const HATENA_BOOKMARK_CRAWLER: Ipv4Addr = Ipv4Addr::new(133, 242, 243, 6);
-                let extract_real_ip = |req: &ServiceRequest, cloudflare_support: bool| {
-                    if cloudflare_support {
-                        req.headers().get("CF-Connecting-IP")?.to_str().ok()?.parse::<IpAddr>().ok()
-                    } else {
-                        req.peer_addr().map(|x| x.ip())
-                    }
-                };
-
-                if extract_real_ip(&req, cloudflare_support).is_some_and(|x| x == HATENA_BOOKMARK_CRAWLER) {
-                    Box::pin(async {
-                        Ok(ServiceResponse::new(req.into_parts().0, HttpResponseBuilder::new(StatusCode::FORBIDDEN).body("Forbidden")))
-                    }) as LocalBoxFuture<Result<ServiceResponse, actix_web::Error>>
-                } else {
-                    use actix_web::dev::Service;
-
-                    Box::pin(srv.call(req).map(|x| x.map(actix_web::dev::ServiceResponse::map_into_boxed_body)))  
-                        as LocalBoxFuture<Result<ServiceResponse, actix_web::Error>>
-                }
-            })
-            .wrap(Logger::new(logger_format))
+            .wrap_fn(move |req, srv| throttle_authentication(req, srv, &throttle, proxied_by_cloudflare))
+            .wrap_fn(move |req, srv| reject_hatena_bookmark_crawler(req, srv, proxied_by_cloudflare))
+            .wrap(Logger::new(logger_format).custom_response_replace("token", token_name_for_log))
             .wrap(crate::service::rest::cors::middleware_factory())
     };
     
@@ -153,3 +360,50 @@ pub async fn boot_http_server(port: u16, host: &str, proxied_by_cloudflare: bool
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+    use std::sync::Arc;
+    use actix_web::{App, HttpResponse};
+    use actix_web::http::header::AUTHORIZATION;
+    use actix_web::http::StatusCode;
+    use actix_web::test::{call_service, init_service, TestRequest};
+    use actix_web::web::{self, Data};
+    use toy_blog_endpoint_model::{ArticleId, Visibility};
+    use crate::service::persistence::{ArticleStore, InMemoryArticleStore};
+    use crate::service::rest::api::{article, trash};
+    use crate::service::rest::auth::install_test_tokens;
+    use crate::service::rest::throttle::AuthFailureThrottle;
+    use super::throttle_authentication;
+
+    #[actix_web::test]
+    async fn forbidden_or_not_found_does_not_reset_failures() {
+        install_test_tokens();
+        let repo: Arc<dyn ArticleStore> = Arc::new(InMemoryArticleStore::new());
+        repo.create_entry(&ArticleId::new("private".to_string()), "secret".to_string(), Visibility::Private, None).unwrap();
+        let throttle = Arc::new(AuthFailureThrottle::new());
+        let app = init_service(
+            App::new()
+                .service(web::scope("/api/article").service(article::fetch))
+                .service(web::scope("/api/trash").service(trash::list))
+                .route("/forbidden", web::get().to(|| async { HttpResponse::Forbidden().finish() }))
+                .app_data(Data::from(repo))
+                .wrap_fn(move |req, srv| throttle_authentication(req, srv, &throttle, false))
+        ).await;
+        let client = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1)), 12345);
+
+        let mut statuses = vec![];
+        for uri in ["/api/trash", "/api/article/private", "/forbidden"].into_iter().cycle().take(12) {
+            let request = TestRequest::get().uri(uri)
+                .peer_addr(client)
+                .insert_header((AUTHORIZATION, "Bearer wrong"))
+                .to_request();
+            statuses.push(call_service(&app, request).await.status());
+        }
+
+        assert!(statuses.contains(&StatusCode::NOT_FOUND), "{statuses:?}");
+        assert!(statuses.contains(&StatusCode::FORBIDDEN), "{statuses:?}");
+        assert!(statuses.contains(&StatusCode::TOO_MANY_REQUESTS), "{statuses:?}");
+    }
+}
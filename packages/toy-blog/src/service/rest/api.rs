@@ -1,3 +1,11 @@
+pub mod audit;
 pub mod article;
+pub mod draft;
 pub mod meta;
 pub mod list;
+pub mod revision;
+pub mod schedule;
+pub mod search;
+pub mod share;
+pub mod tag;
+pub mod trash;
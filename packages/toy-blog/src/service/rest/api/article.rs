@@ -2,35 +2,37 @@
 use actix_web::{HttpRequest, HttpResponse, Responder};
 use actix_web::{delete, get, post, put};
 
-use actix_web::http::header::USER_AGENT;
+use actix_web::http::header::{EntityTag, IfNoneMatch, USER_AGENT};
 use actix_web::http::StatusCode;
-use actix_web::web::{Bytes, Json, Path};
+use actix_web::web::{Bytes, Data, Header, Json, Path, Query};
 use actix_web_httpauth::extractors::bearer::BearerAuth;
+use chrono::{DateTime, FixedOffset, SubsecRound};
 use log::{error, info};
 use once_cell::unsync::Lazy;
-use toy_blog_endpoint_model::{ArticleContent, ArticleCreatedNotice, ArticleCreateWarning, ArticleId, ArticleSnapshot, ArticleSnapshotMetadata, CreateArticleError, DeleteArticleError, GetArticleError, OwnedMetadata, UpdateArticleError, UpdateVisibilityPayload, Visibility};
-use crate::service::rest::auth::is_wrong_token;
-use crate::service::rest::inner_no_leak::{UnhandledError};
-use crate::service::rest::repository::GLOBAL_ARTICLE_REPOSITORY;
-use crate::service::persistence::ArticleRepository;
+use toy_blog_endpoint_model::{Article, ArticleContent, ArticleMetadata, AuditAction, ArticleCreatedNotice, ArticleCreateWarning, ArticleId, ArticleSnapshot, ArticleSnapshotMetadata, CreateArticleError, DeleteArticleError, GetArticleDocumentResult, GetArticleError, GetArticleQuery, GetArticleResult, OwnedMetadata, UpdateArticleError, UpdateArticleMetadataError, UpdateArticleMetadataResult, UpdateVisibilityPayload, Visibility};
+use crate::service::rest::api::share::is_shared_with;
+use crate::service::rest::audit::{AuditEvent, AuditLog};
+use crate::service::rest::auth::{authorize, AuthorizationError, Principal, TokenScope};
+use crate::service::rest::exposed_representation_format::{add_vary_accept, article_document, content_etag, document_etag, prefers_json, MaybeNotModified};
+use crate::service::rest::header::IfModifiedSince;
+use crate::service::rest::precondition::WritePreconditions;
+use crate::service::rest::inner_no_leak::{ComposeInternalError, UnhandledError};
+use crate::service::persistence::{ArticleStore, PersistenceError};
 use super::super::exposed_representation_format::EndpointRepresentationCompiler;
 
-fn x_get<'a>() -> &'a ArticleRepository {
-    GLOBAL_ARTICLE_REPOSITORY.get().expect("must be fully-initialized")
-}
-
 #[post("/{article_id}")]
 #[allow(clippy::future_not_send)]
-pub async fn create(path: Path<String>, data: Bytes, bearer: BearerAuth, request: HttpRequest) -> impl Responder {
+pub async fn create(path: Path<String>, data: Bytes, bearer: BearerAuth, request: HttpRequest, repo: Data<dyn ArticleStore>, audit: Data<AuditLog>) -> impl Responder {
     let token = bearer.token();
     let res = || async {
-        if is_wrong_token(token) {
-            return Ok(Err(CreateArticleError::Unauthorized))
-        }
+        let principal = match authorize(&request, token, TokenScope::ArticleWrite) {
+            Ok(principal) => principal,
+            Err(e) => return Ok(Err(e.into())),
+        };
 
         let path = ArticleId::new(path.into_inner());
         info!("create");
-        if x_get().exists(&path) {
+        if repo.exists(&path).map_err(UnhandledError::new)? {
             return Ok(Err(CreateArticleError::DuplicatedArticleId))
         }
 
@@ -38,15 +40,19 @@ pub async fn create(path: Path<String>, data: Bytes, bearer: BearerAuth, request
         let Ok(text) = plain_text else { return Ok(Err(CreateArticleError::InvalidUtf8)) };
 
         info!("valid utf8");
-        let res = x_get().create_entry(&path, text.clone(), Visibility::Private);
+        let res = repo.create_entry(&path, text.clone(), Visibility::Private, Some(&principal.name));
         match res {
             Ok(()) => {}
             Err(err) => return Err(UnhandledError::new(err))
         }
+        audit.record(&request, &principal, AuditEvent {
+            new_content: Some(&text),
+            ..AuditEvent::new(AuditAction::Create, &path)
+        });
 
         let curl_like = request.headers().get(USER_AGENT)
             .and_then(|ua| ua.to_str().ok())
-            .map_or(false, |ua| ua.starts_with("curl/"));
+            .is_some_and(|ua| ua.starts_with("curl/"));
 
         let no_newline = Lazy::new(|| text.contains('\n'));
 
@@ -64,19 +70,48 @@ pub async fn create(path: Path<String>, data: Bytes, bearer: BearerAuth, request
         }))
     };
 
-    EndpointRepresentationCompiler::from_value(res().await).into_plain_text()
+    EndpointRepresentationCompiler::from_value(res().await).negotiate(&request)
 }
 
 enum Res {
     Internal(UnhandledError),
     General(GetArticleError),
-    Ok(OwnedMetadata<ArticleSnapshotMetadata, ArticleSnapshot>),
+    Ok(Box<Article>),
 }
 
+/// 記事が`If-None-Match`または`If-Modified-Since`の時点から変わっていないかどうか。
+///
+/// RFC 7232 § 6に従い、`If-None-Match`があれば`If-Modified-Since`は見ない。`If-None-Match`は弱い比較で評価する。
+fn is_not_modified(
+    updated_at: DateTime<FixedOffset>,
+    etag: &EntityTag,
+    if_none_match: Option<&IfNoneMatch>,
+    if_modified_since: Option<&IfModifiedSince>,
+) -> bool {
+    match (if_none_match, if_modified_since) {
+        (Some(IfNoneMatch::Any), _) => true,
+        // ヘッダーが無い場合も空の`Items`になる
+        (Some(IfNoneMatch::Items(tags)), _) if !tags.is_empty() => tags.iter().any(|x| x.weak_eq(etag)),
+        // HTTP-dateは秒単位なので、秒未満を切り捨ててから比べる
+        (_, Some(if_modified_since)) => updated_at.trunc_subsecs(0) <= if_modified_since.0.0,
+        (_, None) => false,
+    }
+}
+
+/// `Accept`でJSONが求められていれば[`ArticleDocument`]を、そうでなければ本文だけを返す。
 #[get("/{article_id}")]
-pub async fn fetch(path: Path<String>, auth: Option<BearerAuth>) -> impl Responder {
+#[allow(clippy::too_many_arguments)]
+pub async fn fetch(
+    path: Path<String>,
+    query: Query<GetArticleQuery>,
+    auth: Option<BearerAuth>,
+    if_none_match: Option<Header<IfNoneMatch>>,
+    if_modified_since: Option<IfModifiedSince>,
+    request: HttpRequest,
+    repo: Data<dyn ArticleStore>,
+) -> impl Responder {
     let article_id = ArticleId::new(path.into_inner());
-    let res = fetch_business_logic(&article_id, auth);
+    let res = fetch_business_logic(&request, &**repo, &article_id, auth.as_ref(), query.share.as_deref());
 
     let x = match res {
         Res::Internal(sre) => {
@@ -84,137 +119,468 @@ pub async fn fetch(path: Path<String>, auth: Option<BearerAuth>) -> impl Respond
             return HttpResponse::new(StatusCode::INTERNAL_SERVER_ERROR)
         }
         Res::General(e) => Err(e),
-        Res::Ok(v) => Ok(v)
+        Res::Ok(v) => Ok(*v)
+    };
+
+    // 表現ごとにETagが違うので、条件はそれぞれの表現に対して評価する。
+    // 読めない記事は存在しないものとして扱うので、条件を評価するのは読める場合だけ
+    let mut response = if prefers_json(&request) {
+        let x: GetArticleDocumentResult = x.map(|article| article_document(article_id, article));
+        let not_modified = x.as_ref().is_ok_and(|document| {
+            is_not_modified(document.updated_at.fixed_offset(), &document_etag(document), if_none_match.as_deref(), if_modified_since.as_ref())
+        });
+
+        EndpointRepresentationCompiler::from_value(MaybeNotModified { inner: x, not_modified })
+            .into_json_value()
+    } else {
+        let x: GetArticleResult = x.map(|article| OwnedMetadata {
+            metadata: ArticleSnapshotMetadata {
+                updated_at: article.updated_at.fixed_offset(),
+            },
+            data: ArticleSnapshot {
+                content: ArticleContent::new(article.content),
+            },
+        });
+        let not_modified = x.as_ref().is_ok_and(|snapshot| {
+            let etag = content_etag(snapshot.data.content.as_str());
+            is_not_modified(snapshot.metadata.updated_at, &etag, if_none_match.as_deref(), if_modified_since.as_ref())
+        });
+
+        EndpointRepresentationCompiler::from_value(MaybeNotModified { inner: x, not_modified })
+            .into_plain_text()
     };
+    add_vary_accept(&mut response);
 
-    EndpointRepresentationCompiler::from_value(x).into_plain_text().map_into_boxed_body()
+    response.map_into_boxed_body()
 }
 
-// TODO: テスト書く
-fn fetch_business_logic(article_id: &ArticleId, auth: Option<BearerAuth>) -> Res {
-    let exists = x_get().exists(article_id);
+fn fetch_business_logic(request: &HttpRequest, repo: &dyn ArticleStore, article_id: &ArticleId, auth: Option<&BearerAuth>, share: Option<&str>) -> Res {
+    let exists = match repo.exists(article_id) {
+        Ok(exists) => exists,
+        Err(e) => return Res::Internal(UnhandledError::new(e))
+    };
 
     if !exists {
         return Res::General(GetArticleError::NoSuchArticleFoundById)
     }
 
-    let content = match x_get().read_snapshot(article_id) {
+    let content = match repo.read_snapshot(article_id) {
         Ok(content) => content,
         Err(e) => return Res::Internal(UnhandledError::new(e))
     };
 
-    // Visibility::Restricted, Visibility::Publicは検証不要
-    if content.visibility == Visibility::Private && auth.map_or(true, |auth| is_wrong_token(auth.token())) {
-        return Res::General(GetArticleError::NoSuchArticleFoundById)
-        // now, private article can see from permitted user!
+    if !is_readable(request, &content, auth) {
+        // 限定公開の記事は共有リンクからも読める
+        let shared = match share {
+            Some(share) if content.visibility == Visibility::Restricted => is_shared_with(repo, article_id, share),
+            _ => Ok(false),
+        };
+
+        match shared {
+            Ok(true) => {}
+            Ok(false) => return Res::General(GetArticleError::NoSuchArticleFoundById),
+            Err(e) => return Res::Internal(UnhandledError::new(e)),
+        }
     }
 
-    let u = content.updated_at;
-    let uo = u.offset();
-    let uu = u.with_timezone(uo);
+    Res::Ok(Box::new(content))
+}
+
+/// `token`に`scope`が許可されていて、かつ`article_id`の記事を変更できるかどうか。変更できる場合は変更前の記事も返す。
+///
+/// 記事が存在しなければ`not_found`を返す。
+pub(super) fn authorize_modification<E: From<AuthorizationError>>(
+    request: &HttpRequest,
+    repo: &dyn ArticleStore,
+    article_id: &ArticleId,
+    token: &str,
+    scope: TokenScope,
+    not_found: E,
+) -> ComposeInternalError<Result<(Principal, Article), E>> {
+    let principal = match authorize(request, token, scope) {
+        Ok(principal) => principal,
+        Err(e) => return Ok(Err(e.into())),
+    };
+
+    let article = match repo.read_snapshot(article_id) {
+        Ok(article) => article,
+        Err(PersistenceError::AbsentValue) => return Ok(Err(not_found)),
+        Err(e) => return Err(UnhandledError::new(e)),
+    };
+
+    Ok(principal.ensure_can_modify(&article).map(|()| (principal, article)).map_err(E::from))
+}
 
-    Res::Ok(OwnedMetadata {
-        metadata: ArticleSnapshotMetadata {
-            updated_at: uu
-        },
-        data: ArticleSnapshot {
-            content: ArticleContent::new(content.content)
-        },
-    })
+/// 記事の読み取りを許可するかどうか。許可しないときは記事が存在しないものとして扱うこと。
+///
+/// 限定公開の記事は共有リンクからも読めるが、それはこの関数では考慮しない。
+pub(super) fn is_readable(request: &HttpRequest, article: &Article, auth: Option<&BearerAuth>) -> bool {
+    // now, private article can see from permitted user!
+    article.visibility == Visibility::Public || auth.is_some_and(|auth| authorize(request, auth.token(), TokenScope::ArticleReadPrivate).is_ok())
 }
 
 #[put("/{article_id}")]
 #[allow(clippy::future_not_send)]
-pub async fn update(path: Path<String>, data: Bytes, bearer: BearerAuth) -> impl Responder {
+pub async fn update(path: Path<String>, data: Bytes, bearer: BearerAuth, preconditions: WritePreconditions, request: HttpRequest, repo: Data<dyn ArticleStore>, audit: Data<AuditLog>) -> impl Responder {
     let res = || async {
         let token = bearer.token();
-
-        if is_wrong_token(token) {
-            return Ok(Err(UpdateArticleError::InvalidBearerToken))
-        }
-
         let article_id = ArticleId::new(path.into_inner());
 
-        let exists = x_get().exists(&article_id);
+        let (principal, old) = match authorize_modification(&request, &**repo, &article_id, token, TokenScope::ArticleWrite, UpdateArticleError::ArticleNotFoundById)? {
+            Ok(x) => x,
+            Err(e) => return Ok(Err(e)),
+        };
 
-        if !exists {
-            return Ok(Err(UpdateArticleError::ArticleNotFoundById))
+        // 読んでから書くまでに変わっているかもしれないので、書き込む直前にもストアの中で評価する
+        if let Err(e) = preconditions.evaluate(&article_id, &old) {
+            return Ok(Err(e.into()))
         }
+        let expected = |current: &Article| preconditions.evaluate(&article_id, current).is_ok();
 
         let data = match String::from_utf8(data.to_vec()) {
             Ok(data) => data,
             Err(e) => return Ok(Err(UpdateArticleError::InvalidByteSequenceForUtf8(e)))
         };
 
-        match x_get().update_entry(&article_id, data) {
+        match repo.update_entry_if(&article_id, &expected, data.clone(), Some(&principal.name)) {
             Ok(()) => {
+                audit.record(&request, &principal, AuditEvent {
+                    old_content: Some(&old.content),
+                    new_content: Some(&data),
+                    ..AuditEvent::new(AuditAction::Update, &article_id)
+                });
                 Ok(Ok(()))
             }
+            Err(PersistenceError::PreconditionFailed) => Ok(Err(UpdateArticleError::PreconditionFailed)),
             Err(err) => {
                 Err(UnhandledError::new(err))
             }
         }
     };
 
-    EndpointRepresentationCompiler::from_value(res().await).into_plain_text()
+    EndpointRepresentationCompiler::from_value(res().await).negotiate(&request)
 }
 
 #[delete("/{article_id}")]
 #[allow(clippy::future_not_send)]
-pub async fn remove(path: Path<String>, bearer: BearerAuth) -> impl Responder {
+pub async fn remove(path: Path<String>, bearer: BearerAuth, preconditions: WritePreconditions, request: HttpRequest, repo: Data<dyn ArticleStore>, audit: Data<AuditLog>) -> impl Responder {
     let res = || async {
         let article_id = ArticleId::new(path.into_inner());
         let token = bearer.token();
 
-        if is_wrong_token(token) {
-            return Ok(Err(DeleteArticleError::InvalidBearerToken))
-        }
-
-        let exists = x_get().exists(&article_id);
+        let (principal, old) = match authorize_modification(&request, &**repo, &article_id, token, TokenScope::ArticleDelete, DeleteArticleError::NoSuchArticleFoundById)? {
+            Ok(x) => x,
+            Err(e) => return Ok(Err(e)),
+        };
 
-        if !exists {
-            return Ok(Err(DeleteArticleError::NoSuchArticleFoundById))
+        if let Err(e) = preconditions.evaluate(&article_id, &old) {
+            return Ok(Err(e.into()))
         }
+        let expected = |current: &Article| preconditions.evaluate(&article_id, current).is_ok();
 
-        match x_get().remove(&article_id) {
+        match repo.remove_if(&article_id, &expected) {
             Ok(()) => {
+                audit.record(&request, &principal, AuditEvent {
+                    old_content: Some(&old.content),
+                    ..AuditEvent::new(AuditAction::Delete, &article_id)
+                });
                 Ok(Ok(()))
             }
+            Err(PersistenceError::PreconditionFailed) => Ok(Err(DeleteArticleError::PreconditionFailed)),
             Err(err) => {
                 Err(UnhandledError::new(err))
             }
         }
     };
 
-    EndpointRepresentationCompiler::from_value(res().await).into_plain_text()
+    EndpointRepresentationCompiler::from_value(res().await).negotiate(&request)
 }
 
 #[put("/{article_id}/visibility")]
-pub async fn update_visibility(path: Path<String>, payload: Json<UpdateVisibilityPayload>, bearer: BearerAuth) -> impl Responder {
+pub async fn update_visibility(path: Path<String>, payload: Json<UpdateVisibilityPayload>, bearer: BearerAuth, preconditions: WritePreconditions, request: HttpRequest, repo: Data<dyn ArticleStore>, audit: Data<AuditLog>) -> impl Responder {
     let res = || async {
         let article_id = ArticleId::new(path.into_inner());
         let token = bearer.token();
 
-        if is_wrong_token(token) {
-            return Ok(Err(DeleteArticleError::InvalidBearerToken))
-        }
-
-        let exists = x_get().exists(&article_id);
+        let (principal, article) = match authorize_modification(&request, &**repo, &article_id, token, TokenScope::ArticleWrite, DeleteArticleError::NoSuchArticleFoundById)? {
+            Ok(x) => x,
+            Err(e) => return Ok(Err(e)),
+        };
 
-        if !exists {
-            return Ok(Err(DeleteArticleError::NoSuchArticleFoundById))
+        if let Err(e) = preconditions.evaluate(&article_id, &article) {
+            return Ok(Err(e.into()))
         }
+        let expected = |current: &Article| preconditions.evaluate(&article_id, current).is_ok();
 
         let new_visibility = payload.visibility;
-        match x_get().change_visibility(&article_id, new_visibility) {
+        match repo.change_visibility_if(&article_id, &expected, new_visibility) {
             Ok(()) => {
+                audit.record(&request, &principal, AuditEvent {
+                    old_content: Some(&article.content),
+                    new_content: Some(&article.content),
+                    visibility: Some(new_visibility),
+                    ..AuditEvent::new(AuditAction::ChangeVisibility, &article_id)
+                });
                 Ok(Ok(()))
             }
+            Err(PersistenceError::PreconditionFailed) => Ok(Err(DeleteArticleError::PreconditionFailed)),
             Err(err) => {
                 Err(UnhandledError::new(err))
             }
         }
     };
 
-    EndpointRepresentationCompiler::from_value(res().await).into_plain_text()
+    EndpointRepresentationCompiler::from_value(res().await).negotiate(&request)
+}
+
+/// 題名の長さの上限 (文字数)
+const MAX_TITLE_CHARS: usize = 200;
+/// 要約の長さの上限 (文字数)
+const MAX_SUMMARY_CHARS: usize = 1000;
+
+/// BCP 47の言語タグの構文だけを確かめる。登録されている言語かどうかは確かめない。
+fn is_language_tag(s: &str) -> bool {
+    let mut subtags = s.split('-');
+    let primary = subtags.next().unwrap_or_default();
+
+    (2..=8).contains(&primary.len())
+        && primary.bytes().all(|b| b.is_ascii_alphabetic())
+        && subtags.all(|x| (1..=8).contains(&x.len()) && x.bytes().all(|b| b.is_ascii_alphanumeric()))
+}
+
+fn validate_metadata(metadata: &ArticleMetadata) -> Result<(), UpdateArticleMetadataError> {
+    if metadata.title.as_deref().is_some_and(|x| x.trim().is_empty() || x.chars().count() > MAX_TITLE_CHARS || x.chars().any(char::is_control)) {
+        return Err(UpdateArticleMetadataError::InvalidTitle)
+    }
+
+    if metadata.summary.as_deref().is_some_and(|x| x.trim().is_empty() || x.chars().count() > MAX_SUMMARY_CHARS) {
+        return Err(UpdateArticleMetadataError::InvalidSummary)
+    }
+
+    match &metadata.lang {
+        Some(lang) if !is_language_tag(lang) => Err(UpdateArticleMetadataError::InvalidLang(lang.clone())),
+        _ => Ok(()),
+    }
+}
+
+#[put("/{article_id}/metadata")]
+#[allow(clippy::future_not_send)]
+pub async fn update_metadata(path: Path<String>, payload: Json<ArticleMetadata>, bearer: BearerAuth, request: HttpRequest, repo: Data<dyn ArticleStore>, audit: Data<AuditLog>) -> impl Responder {
+    let res = || async {
+        let article_id = ArticleId::new(path.into_inner());
+
+        let (principal, article) = match authorize_modification(&request, &**repo, &article_id, bearer.token(), TokenScope::ArticleWrite, UpdateArticleMetadataError::NoSuchArticleFoundById)? {
+            Ok(x) => x,
+            Err(e) => return Ok(Err(e)),
+        };
+
+        if let Err(e) = validate_metadata(&payload) {
+            return Ok(Err(e))
+        }
+
+        let metadata = payload.into_inner();
+        repo.change_metadata(&article_id, metadata.clone()).map_err(UnhandledError::new)?;
+        audit.record(&request, &principal, AuditEvent {
+            old_content: Some(&article.content),
+            new_content: Some(&article.content),
+            metadata: Some(&metadata),
+            ..AuditEvent::new(AuditAction::ChangeMetadata, &article_id)
+        });
+
+        Ok(Ok(()))
+    };
+
+    let res: ComposeInternalError<UpdateArticleMetadataResult> = res().await;
+    EndpointRepresentationCompiler::from_value(res).negotiate(&request)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use actix_web::App;
+    use actix_web::test::{call_service, init_service, read_body, TestRequest};
+    use actix_web::http::header::{ACCEPT, CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED, VARY};
+    use actix_web::http::StatusCode;
+    use actix_web::web::{Bytes, Data};
+    use chrono::{Local, TimeDelta};
+    use toy_blog_endpoint_model::{ArticleId, ArticleMetadata, ArticleShareLink, ShareLinkId, UpdateArticleMetadataError, Visibility};
+    use crate::service::persistence::{ArticleStore, InMemoryArticleStore};
+    use crate::service::rest::exposed_representation_format::content_etag;
+    use crate::service::rest::header::HttpDate;
+    use super::{fetch, fetch_business_logic, is_language_tag, validate_metadata, Res};
+
+    #[test]
+    fn language_tags_are_checked_syntactically() {
+        for valid in ["ja", "en-US", "zh-Hant-TW", "sgn-JP"] {
+            assert!(is_language_tag(valid), "{valid}");
+        }
+        for invalid in ["", "j", "ja_JP", "en-", "123", "ja-toolongsubtag"] {
+            assert!(!is_language_tag(invalid), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn malformed_metadata_is_rejected() {
+        let metadata = |title: Option<&str>, summary: Option<&str>| ArticleMetadata {
+            title: title.map(ToString::to_string),
+            summary: summary.map(ToString::to_string),
+            lang: None,
+        };
+
+        assert!(validate_metadata(&ArticleMetadata::default()).is_ok());
+        assert!(validate_metadata(&metadata(Some("題名"), Some("一行目\n二行目"))).is_ok());
+        assert!(matches!(validate_metadata(&metadata(Some(" "), None)), Err(UpdateArticleMetadataError::InvalidTitle)));
+        assert!(matches!(validate_metadata(&metadata(Some("two\nlines"), None)), Err(UpdateArticleMetadataError::InvalidTitle)));
+        assert!(matches!(validate_metadata(&metadata(None, Some(""))), Err(UpdateArticleMetadataError::InvalidSummary)));
+        assert!(matches!(
+            validate_metadata(&ArticleMetadata { lang: Some("日本語".to_string()), ..ArticleMetadata::default() }),
+            Err(UpdateArticleMetadataError::InvalidLang(_))
+        ));
+    }
+
+    #[test]
+    fn plain_text_update_keeps_metadata() {
+        let store = InMemoryArticleStore::new();
+        let id = ArticleId::new("a".to_string());
+        store.create_entry(&id, "first".to_string(), Visibility::Public, None).unwrap();
+        let metadata = ArticleMetadata { title: Some("題名".to_string()), summary: None, lang: Some("ja".to_string()) };
+        store.change_metadata(&id, metadata.clone()).unwrap();
+        store.update_entry(&id, "second".to_string(), None).unwrap();
+
+        let article = store.read_snapshot(&id).unwrap();
+        assert_eq!(article.content, "second");
+        assert_eq!(article.metadata(), metadata);
+        assert_eq!(store.revisions(&id).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn restricted_article_is_readable_only_with_share_link() {
+        let store = InMemoryArticleStore::new();
+        let id = ArticleId::new("a".to_string());
+        store.create_entry(&id, "content".to_string(), Visibility::Restricted, None).unwrap();
+        store.add_share_link(&id, ArticleShareLink {
+            id: ShareLinkId("link".to_string()),
+            // "secret"のSHA-256
+            digest: "2bb80d537b1da3e38bd30361aa855686bde0eacd7162fef6a25fe97bf527a25b".to_string(),
+            created_at: Local::now(),
+            expires_at: None,
+        }).unwrap();
+        let request = TestRequest::default().to_http_request();
+
+        assert!(matches!(fetch_business_logic(&request, &store, &id, None, None), Res::General(_)));
+        assert!(matches!(fetch_business_logic(&request, &store, &id, None, Some("wrong")), Res::General(_)));
+        assert!(matches!(fetch_business_logic(&request, &store, &id, None, Some("secret")), Res::Ok(_)));
+
+        store.change_visibility(&id, Visibility::Private).unwrap();
+        assert!(matches!(fetch_business_logic(&request, &store, &id, None, Some("secret")), Res::General(_)));
+    }
+
+    fn store_with(visibility: Visibility) -> Arc<dyn ArticleStore> {
+        let store = InMemoryArticleStore::new();
+        store.create_entry(&ArticleId::new("a".to_string()), "hello".to_string(), visibility, None).unwrap();
+
+        Arc::new(store)
+    }
+
+    /// `GET /a`を送り、ステータス・`ETag`・本文を返す。
+    async fn conditional_get(repo: Arc<dyn ArticleStore>, headers: &[(&str, &str)]) -> (StatusCode, Option<String>, Bytes) {
+        let app = init_service(App::new().app_data(Data::from(repo)).service(fetch)).await;
+        let request = headers.iter()
+            .fold(TestRequest::get().uri("/a"), |request, header| request.insert_header(*header))
+            .to_request();
+        let res = call_service(&app, request).await;
+        let status = res.status();
+        let etag = res.headers().get(ETAG).map(|x| x.to_str().unwrap().to_string());
+
+        (status, etag, read_body(res).await)
+    }
+
+    #[actix_web::test]
+    async fn fetch_reports_validators() {
+        let app = init_service(App::new().app_data(Data::from(store_with(Visibility::Public))).service(fetch)).await;
+        let res = call_service(&app, TestRequest::get().uri("/a").to_request()).await;
+
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers().get(ETAG).unwrap().to_str().unwrap(), content_etag("hello").to_string());
+        assert!(res.headers().contains_key(LAST_MODIFIED));
+    }
+
+    #[actix_web::test]
+    async fn if_none_match_is_honoured() {
+        let repo = store_with(Visibility::Public);
+        let etag = content_etag("hello").to_string();
+        let weak = format!("W/{etag}");
+
+        let (status, returned, body) = conditional_get(repo.clone(), &[(IF_NONE_MATCH.as_str(), &etag)]).await;
+        assert_eq!(status, StatusCode::NOT_MODIFIED);
+        assert_eq!(returned.as_deref(), Some(etag.as_str()));
+        assert!(body.is_empty());
+
+        // If-None-Matchは弱い比較なので、W/が付いていても一致する
+        assert_eq!(conditional_get(repo.clone(), &[(IF_NONE_MATCH.as_str(), &weak)]).await.0, StatusCode::NOT_MODIFIED);
+        assert_eq!(conditional_get(repo.clone(), &[(IF_NONE_MATCH.as_str(), "*")]).await.0, StatusCode::NOT_MODIFIED);
+        assert_eq!(conditional_get(repo.clone(), &[(IF_NONE_MATCH.as_str(), "\"other\", ")]).await.0, StatusCode::OK);
+
+        repo.update_entry(&ArticleId::new("a".to_string()), "bye".to_string(), None).unwrap();
+        let (status, _, body) = conditional_get(repo, &[(IF_NONE_MATCH.as_str(), &etag)]).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, "bye");
+    }
+
+    #[actix_web::test]
+    async fn if_modified_since_is_honoured() {
+        let repo = store_with(Visibility::Public);
+        let hour_ago = HttpDate::try_from(Local::now() - TimeDelta::hours(1)).unwrap().to_string();
+        let hour_later = HttpDate::try_from(Local::now() + TimeDelta::hours(1)).unwrap().to_string();
+
+        let (status, _, body) = conditional_get(repo.clone(), &[(IF_MODIFIED_SINCE.as_str(), &hour_later)]).await;
+        assert_eq!(status, StatusCode::NOT_MODIFIED);
+        assert!(body.is_empty());
+        assert_eq!(conditional_get(repo.clone(), &[(IF_MODIFIED_SINCE.as_str(), &hour_ago)]).await.0, StatusCode::OK);
+        assert_eq!(conditional_get(repo.clone(), &[(IF_MODIFIED_SINCE.as_str(), "not a date")]).await.0, StatusCode::OK);
+
+        // If-None-Matchがあれば、If-Modified-Sinceは無視する
+        let (status, _, _) = conditional_get(repo, &[
+            (IF_NONE_MATCH.as_str(), "\"other\""),
+            (IF_MODIFIED_SINCE.as_str(), &hour_later),
+        ]).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn fetch_returns_json_document_when_asked() {
+        let repo = store_with(Visibility::Public);
+        let app = init_service(App::new().app_data(Data::from(repo.clone())).service(fetch)).await;
+        let res = call_service(&app, TestRequest::get().uri("/a").insert_header((ACCEPT, "application/json")).to_request()).await;
+
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers().get(CONTENT_TYPE).unwrap(), "application/json");
+        assert_eq!(res.headers().get(VARY).unwrap(), "Accept");
+        let etag = res.headers().get(ETAG).unwrap().to_str().unwrap().to_string();
+        assert_ne!(etag, content_etag("hello").to_string());
+
+        let document: serde_json::Value = serde_json::from_slice(&read_body(res).await).unwrap();
+        let article = repo.read_snapshot(&ArticleId::new("a".to_string())).unwrap();
+        assert_eq!(document, serde_json::json!({
+            "id": "a",
+            "content": "hello",
+            "visibility": "public",
+            "created_at": article.created_at,
+            "updated_at": article.updated_at,
+        }));
+
+        // ETagは表現ごとに違うので、JSONのETagは平文の条件付きGETには一致しない
+        let (status, _, body) = conditional_get(repo.clone(), &[(ACCEPT.as_str(), "application/json"), (IF_NONE_MATCH.as_str(), &etag)]).await;
+        assert_eq!(status, StatusCode::NOT_MODIFIED);
+        assert!(body.is_empty());
+        assert_eq!(conditional_get(repo, &[(IF_NONE_MATCH.as_str(), &etag)]).await.0, StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn conditions_do_not_reveal_unreadable_article() {
+        let (status, etag, _) = conditional_get(store_with(Visibility::Private), &[(IF_NONE_MATCH.as_str(), "*")]).await;
+
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(etag, None);
+    }
 }
@@ -0,0 +1,28 @@
+use std::num::NonZeroU32;
+use actix_web::{get, HttpRequest, Responder};
+use actix_web::web::{Data, Query};
+use actix_web_httpauth::extractors::bearer::BearerAuth;
+use toy_blog_endpoint_model::{GetAuditLogQuery, GetAuditLogResult};
+use crate::service::rest::audit::AuditLog;
+use crate::service::rest::auth::{authorize, TokenScope};
+use crate::service::rest::ComposeInternalError;
+use crate::service::rest::exposed_representation_format::EndpointRepresentationCompiler;
+use crate::service::rest::inner_no_leak::UnhandledError;
+
+/// `limit`が省略された場合に返す操作の数
+const DEFAULT_AUDIT_LOG_LIMIT: u32 = 100;
+
+#[get("")]
+#[allow(clippy::unused_async, clippy::needless_pass_by_value)]
+pub async fn list(query: Query<GetAuditLogQuery>, bearer: BearerAuth, request: HttpRequest, audit: Data<AuditLog>) -> impl Responder {
+    let res: ComposeInternalError<GetAuditLogResult> = (|| {
+        if let Err(e) = authorize(&request, bearer.token(), TokenScope::AuditRead) {
+            return Ok(Err(e.into()))
+        }
+
+        let limit = query.limit.map_or(DEFAULT_AUDIT_LOG_LIMIT, NonZeroU32::get);
+        audit.read(query.since, query.until, limit as usize).map(Ok).map_err(UnhandledError::new)
+    })();
+
+    EndpointRepresentationCompiler::from_value(res).negotiate(&request)
+}
@@ -0,0 +1,192 @@
+use actix_web::{delete, get, post, put, HttpRequest, Responder};
+use actix_web::web::{Bytes, Data, Path};
+use actix_web_httpauth::extractors::bearer::BearerAuth;
+use chrono::Local;
+use toy_blog_endpoint_model::{ArticleContent, ArticleDraft, ArticleId, ArticleSnapshot, ArticleSnapshotMetadata, AuditAction, DraftError, GetDraftResult, OwnedMetadata, UpdateDraftResult};
+use crate::service::persistence::{ArticleStore, PersistenceError};
+use crate::service::rest::api::article::authorize_modification;
+use crate::service::rest::audit::{AuditEvent, AuditLog};
+use crate::service::rest::auth::TokenScope;
+use crate::service::rest::exposed_representation_format::EndpointRepresentationCompiler;
+use crate::service::rest::inner_no_leak::{ComposeInternalError, UnhandledError};
+
+/// 下書きは公開前の本文なので、記事を変更できるトークンでなければ読めない。
+#[get("/{article_id}/draft")]
+#[allow(clippy::unused_async, clippy::needless_pass_by_value)]
+pub async fn fetch(path: Path<String>, bearer: BearerAuth, request: HttpRequest, repo: Data<dyn ArticleStore>) -> impl Responder {
+    let article_id = ArticleId::new(path.into_inner());
+
+    let res: ComposeInternalError<GetDraftResult> = (|| {
+        let (_, article) = match authorize_modification(&request, &**repo, &article_id, bearer.token(), TokenScope::ArticleWrite, DraftError::NoSuchArticleFoundById)? {
+            Ok(x) => x,
+            Err(e) => return Ok(Err(e)),
+        };
+
+        let Some(draft) = article.draft else {
+            return Ok(Err(DraftError::NoDraft))
+        };
+
+        Ok(Ok(OwnedMetadata {
+            metadata: ArticleSnapshotMetadata {
+                updated_at: draft.updated_at.fixed_offset(),
+            },
+            data: ArticleSnapshot {
+                content: ArticleContent::new(draft.content),
+            },
+        }))
+    })();
+
+    EndpointRepresentationCompiler::from_value(res).negotiate(&request)
+}
+
+#[put("/{article_id}/draft")]
+#[allow(clippy::unused_async, clippy::needless_pass_by_value)]
+pub async fn update(path: Path<String>, data: Bytes, bearer: BearerAuth, request: HttpRequest, repo: Data<dyn ArticleStore>, audit: Data<AuditLog>) -> impl Responder {
+    let article_id = ArticleId::new(path.into_inner());
+
+    let res: ComposeInternalError<UpdateDraftResult> = (|| {
+        let (principal, article) = match authorize_modification(&request, &**repo, &article_id, bearer.token(), TokenScope::ArticleWrite, DraftError::NoSuchArticleFoundById)? {
+            Ok(x) => x,
+            Err(e) => return Ok(Err(e)),
+        };
+
+        let content = match String::from_utf8(data.to_vec()) {
+            Ok(content) => content,
+            Err(e) => return Ok(Err(DraftError::InvalidByteSequenceForUtf8(e))),
+        };
+
+        let draft = ArticleDraft {
+            content,
+            updated_at: Local::now(),
+            updated_by: Some(principal.name.clone()),
+        };
+        repo.change_draft(&article_id, Some(draft.clone())).map_err(UnhandledError::new)?;
+        audit.record(&request, &principal, AuditEvent {
+            old_content: article.draft.as_ref().map(|x| x.content.as_str()),
+            new_content: Some(&draft.content),
+            ..AuditEvent::new(AuditAction::SaveDraft, &article_id)
+        });
+
+        Ok(Ok(()))
+    })();
+
+    EndpointRepresentationCompiler::from_value(res).negotiate(&request)
+}
+
+#[delete("/{article_id}/draft")]
+#[allow(clippy::unused_async, clippy::needless_pass_by_value)]
+pub async fn discard(path: Path<String>, bearer: BearerAuth, request: HttpRequest, repo: Data<dyn ArticleStore>, audit: Data<AuditLog>) -> impl Responder {
+    let article_id = ArticleId::new(path.into_inner());
+
+    let res: ComposeInternalError<UpdateDraftResult> = (|| {
+        let (principal, article) = match authorize_modification(&request, &**repo, &article_id, bearer.token(), TokenScope::ArticleWrite, DraftError::NoSuchArticleFoundById)? {
+            Ok(x) => x,
+            Err(e) => return Ok(Err(e)),
+        };
+
+        let Some(draft) = article.draft else {
+            return Ok(Err(DraftError::NoDraft))
+        };
+
+        repo.change_draft(&article_id, None).map_err(UnhandledError::new)?;
+        audit.record(&request, &principal, AuditEvent {
+            old_content: Some(&draft.content),
+            ..AuditEvent::new(AuditAction::DiscardDraft, &article_id)
+        });
+
+        Ok(Ok(()))
+    })();
+
+    EndpointRepresentationCompiler::from_value(res).negotiate(&request)
+}
+
+/// 下書きを本文にする。本文の更新と同じく新しい版が作られ、更新日時が進む。
+#[post("/{article_id}/draft/publish")]
+#[allow(clippy::unused_async, clippy::needless_pass_by_value)]
+pub async fn publish(path: Path<String>, bearer: BearerAuth, request: HttpRequest, repo: Data<dyn ArticleStore>, audit: Data<AuditLog>) -> impl Responder {
+    let article_id = ArticleId::new(path.into_inner());
+
+    let res: ComposeInternalError<UpdateDraftResult> = (|| {
+        let (principal, article) = match authorize_modification(&request, &**repo, &article_id, bearer.token(), TokenScope::ArticleWrite, DraftError::NoSuchArticleFoundById)? {
+            Ok(x) => x,
+            Err(e) => return Ok(Err(e)),
+        };
+
+        if article.draft.is_none() {
+            return Ok(Err(DraftError::NoDraft))
+        }
+
+        // 確かめた後で下書きが捨てられていることもあるので、記録には実際に公開した内容を使う
+        let previous = match repo.publish_draft(&article_id, Some(&principal.name)) {
+            Ok(previous) => previous,
+            Err(PersistenceError::AbsentValue) => return Ok(Err(DraftError::NoDraft)),
+            Err(e) => return Err(UnhandledError::new(e)),
+        };
+        audit.record(&request, &principal, AuditEvent {
+            old_content: Some(&previous.content),
+            new_content: previous.draft.as_ref().map(|x| x.content.as_str()),
+            ..AuditEvent::new(AuditAction::PublishDraft, &article_id)
+        });
+
+        Ok(Ok(()))
+    })();
+
+    EndpointRepresentationCompiler::from_value(res).negotiate(&request)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use actix_web::App;
+    use actix_web::http::header::AUTHORIZATION;
+    use actix_web::http::StatusCode;
+    use actix_web::test::{call_service, init_service, TestRequest};
+    use actix_web::web::{self, Data};
+    use toy_blog_endpoint_model::{ArticleId, Visibility};
+    use crate::service::persistence::{ArticleStore, InMemoryArticleStore};
+    use crate::service::rest::audit::AuditLog;
+    use crate::service::rest::auth::install_test_tokens;
+    use super::{fetch, publish, update};
+
+    #[actix_web::test]
+    async fn draft_is_not_readable_without_token() {
+        let repo: Arc<dyn ArticleStore> = Arc::new(InMemoryArticleStore::new());
+        repo.create_entry(&ArticleId::new("a".to_string()), "published".to_string(), Visibility::Public, None).unwrap();
+        let app = init_service(App::new().service(web::scope("/api/article").service(fetch)).app_data(Data::from(repo))).await;
+
+        let res = call_service(&app, TestRequest::get().uri("/api/article/a/draft").to_request()).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_web::test]
+    async fn publish_replaces_content_and_clears_draft() {
+        install_test_tokens();
+        let repo: Arc<dyn ArticleStore> = Arc::new(InMemoryArticleStore::new());
+        let id = ArticleId::new("a".to_string());
+        repo.create_entry(&id, "published".to_string(), Visibility::Public, None).unwrap();
+        let before = repo.read_snapshot(&id).unwrap();
+        let audit_log = tempfile::NamedTempFile::new().unwrap();
+        let app = init_service(
+            App::new()
+                .service(web::scope("/api/article").service((update, publish)))
+                .app_data(Data::from(repo.clone()))
+                .app_data(Data::new(AuditLog::open(audit_log.path(), false).unwrap()))
+        ).await;
+        let request = |request: TestRequest| request.insert_header((AUTHORIZATION, "Bearer admin")).to_request();
+
+        let res = call_service(&app, request(TestRequest::put().uri("/api/article/a/draft").set_payload("drafted"))).await;
+        assert_eq!(res.status(), StatusCode::NO_CONTENT);
+        assert_eq!(repo.read_snapshot(&id).unwrap().updated_at, before.updated_at, "saving a draft must not touch the article");
+
+        let res = call_service(&app, request(TestRequest::post().uri("/api/article/a/draft/publish"))).await;
+        assert_eq!(res.status(), StatusCode::NO_CONTENT);
+        let after = repo.read_snapshot(&id).unwrap();
+        assert_eq!(after.content, "drafted");
+        assert!(after.draft.is_none());
+        assert!(after.updated_at > before.updated_at);
+        assert_eq!(repo.revisions(&id).unwrap().len(), 2);
+
+        let res = call_service(&app, request(TestRequest::post().uri("/api/article/a/draft/publish"))).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -1,47 +1,168 @@
+use std::cmp::Ordering;
+use std::fmt::Write;
 use std::future::{Future, ready};
+use std::num::NonZeroU32;
 
-use actix_web::{get, Responder};
-use actix_web::web::Path;
-use chrono::Datelike;
+use actix_web::{get, HttpRequest, HttpResponse, Responder};
+use actix_web::http::header::{EntityTag, IfNoneMatch};
+use actix_web::web::{Data, Header, Path, Query};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use chrono::{DateTime, Local, NaiveDate};
+use serde::{Deserialize, Serialize};
 
-use toy_blog_endpoint_model::{AnnoDominiYear, Article, ArticleId, ArticleListingResponseRepresentation, ArticleListingResponseMetadata, ArticleListResponseEntry, OneOriginTwoDigitsMonth, OwnedMetadata, Visibility};
+use toy_blog_endpoint_model::{AnnoDominiYear, Article, ArticleId, ArticleListingResponseRepresentation, ArticleListingResponseMetadata, ArticleListResponseEntry, ListArticleRequestQuery, ListArticleSortPolicy, OneOriginTwoDigitsMonth, OwnedMetadata, Visibility};
 
-use crate::service::persistence::ArticleRepository;
-use crate::service::rest::exposed_representation_format::{ArticleIdCollectionResponseRepr, EndpointRepresentationCompiler, MaybeNotModified, ReportLastModofied};
-use crate::service::rest::header::IfModifiedSince;
-use crate::service::rest::repository::GLOBAL_ARTICLE_REPOSITORY;
+use crate::service::persistence::{ArticleStore, PersistenceError};
+use crate::service::rest::exposed_representation_format::{listing_etag, ArticleIdCollectionResponseRepr, EndpointRepresentationCompiler, MaybeNotModified, ReportLastModofied};
+use crate::service::rest::inner_no_leak::UnhandledError;
 
-fn compute_and_filter_out(
-    article_repository: &ArticleRepository,
-    if_modified_since: Option<IfModifiedSince>,
-    additional_filter: impl Clone + Fn(&&(ArticleId, Article)) -> bool
-) -> ArticleIdCollectionResponseRepr {
-    let x = article_repository.entries();
-    let only_public = |x: &&(ArticleId, Article)| x.1.visibility == Visibility::Public;
-    let ret_304;
-    let latest_updated = x.iter()
-        .filter(only_public)
-        .filter(additional_filter.clone())
-        .max_by_key(|r| r.1.updated_at)
-        .map(|x| &x.1).map(|x| x.updated_at);
-
-    if let Some(if_modified_since) = if_modified_since {
-        let if_unmodified_since = if_modified_since.0.0;
-        ret_304 = latest_updated.is_some_and(|d| d >= if_unmodified_since);
-    } else {
-        ret_304 = false;
+/// ページの最後の記事の位置。次のページはこの位置より後の記事から始まる。
+///
+/// 位置は記事そのものではなく並べ替えのキーで表すので、ページを取得する間に記事が作成・削除されてもずれない。
+#[derive(Deserialize, Serialize, Clone, Eq, PartialEq, Debug)]
+struct Cursor {
+    sort: ListArticleSortPolicy,
+    at: DateTime<Local>,
+    id: ArticleId,
+}
+
+impl Cursor {
+    fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(self).expect("cursor must be serializable"))
+    }
+
+    fn decode(s: &str) -> Option<Self> {
+        serde_json::from_slice(&URL_SAFE_NO_PAD.decode(s).ok()?).ok()
+    }
+}
+
+/// 一覧のどの部分を返すか
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+struct PageRequest {
+    sort: ListArticleSortPolicy,
+    after: Option<Cursor>,
+    limit: Option<NonZeroU32>,
+    /// このタグが付いた記事だけを返す
+    tag: Option<String>,
+}
+
+impl PageRequest {
+    /// カーソルが不正な場合、または並び順がカーソルを作った時と違う場合は`None`を返す。
+    fn from_query(query: &ListArticleRequestQuery) -> Option<Self> {
+        let sort = query.policy.unwrap_or_default();
+        let after = match &query.cursor {
+            Some(cursor) => Some(Cursor::decode(cursor).filter(|x| x.sort == sort)?),
+            None => None,
+        };
+
+        Some(Self { sort, after, limit: query.limit, tag: query.tag.clone() })
+    }
+
+    /// `next`から始まるページを指す`Link`ヘッダーの値
+    fn next_page_link(&self, path: &str, next: &Cursor) -> String {
+        let limit = self.limit.map(|x| format!("&limit={x}")).unwrap_or_default();
+        let tag = self.tag.as_deref().map(|x| format!("&tag={}", percent_encode(x))).unwrap_or_default();
+        format!("<{path}?sort={}{limit}{tag}&cursor={}>; rel=\"next\"", self.sort.as_ref(), next.encode())
+    }
+}
+
+/// クエリの値として使えるように、RFC 3986のunreservedでない文字をUTF-8のバイトごとにパーセントエンコードする。
+fn percent_encode(s: &str) -> String {
+    s.bytes().fold(String::with_capacity(s.len()), |mut encoded, b| {
+        if b.is_ascii_alphanumeric() || b"-._~".contains(&b) {
+            encoded.push(char::from(b));
+        } else {
+            write!(encoded, "%{b:02X}").expect("writing to String never fails");
+        }
+        encoded
+    })
+}
+
+const fn sort_key(entry: &ArticleListResponseEntry, sort: ListArticleSortPolicy) -> DateTime<Local> {
+    match sort {
+        ListArticleSortPolicy::Newest | ListArticleSortPolicy::Oldest => entry.created_at,
+        ListArticleSortPolicy::RecentUpdated | ListArticleSortPolicy::LeastRecentlyUpdated => entry.updated_at,
+    }
+}
+
+/// `sort`の順で`a`が`b`より前か後か。同じ日時の記事はIDの昇順に並べる。
+fn compare_position(sort: ListArticleSortPolicy, a: (DateTime<Local>, &ArticleId), b: (DateTime<Local>, &ArticleId)) -> Ordering {
+    let by_time = match sort {
+        ListArticleSortPolicy::Newest | ListArticleSortPolicy::RecentUpdated => b.0.cmp(&a.0),
+        ListArticleSortPolicy::Oldest | ListArticleSortPolicy::LeastRecentlyUpdated => a.0.cmp(&b.0),
+    };
+
+    by_time.then_with(|| a.1.0.cmp(&b.1.0))
+}
+
+/// `policy`の順に並べる。同じ日時の記事はIDの昇順に並べる。
+fn sort_entries(entries: &mut [ArticleListResponseEntry], policy: ListArticleSortPolicy) {
+    entries.sort_by(|a, b| compare_position(policy, (sort_key(a, policy), &a.id), (sort_key(b, policy), &b.id)));
+}
+
+/// `page`が指すページの記事と、次のページがあればその位置を返す。
+fn paginate(mut entries: Vec<ArticleListResponseEntry>, page: &PageRequest) -> (Vec<ArticleListResponseEntry>, Option<Cursor>) {
+    sort_entries(&mut entries, page.sort);
+    if let Some(after) = &page.after {
+        entries.retain(|x| compare_position(page.sort, (sort_key(x, page.sort), &x.id), (after.at, &after.id)) == Ordering::Greater);
+    }
+
+    let Some(limit) = page.limit.and_then(|x| usize::try_from(x.get()).ok()) else {
+        return (entries, None)
+    };
+    if entries.len() <= limit {
+        return (entries, None)
+    }
+
+    entries.truncate(limit);
+    let next = entries.last().map(|last| Cursor {
+        sort: page.sort,
+        at: sort_key(last, page.sort),
+        id: last.id.clone(),
+    });
+
+    (entries, next)
+}
+
+/// ページの`ETag`が`If-None-Match`と一致するかどうか。比較は弱い比較で、`*`は常に一致する。
+fn matches_if_none_match(etag: &EntityTag, if_none_match: Option<&IfNoneMatch>) -> bool {
+    match if_none_match {
+        Some(IfNoneMatch::Any) => true,
+        Some(IfNoneMatch::Items(tags)) => tags.iter().any(|x| x.weak_eq(etag)),
+        None => false,
     }
+}
 
-    let entries = ArticleListingResponseRepresentation(x.iter().filter(only_public).filter(additional_filter.clone())
+/// 公開されている記事のうち`page`が指すページを返す。タグが指定されていれば、そのタグが付いた記事に絞り込む。`path`は次のページへのリンクに使う。
+///
+/// `304`はページの`ETag`と`If-None-Match`だけから判断する。ページに含まれる記事の更新日時では、
+/// 記事の削除や公開範囲の変更でページから記事が消えたことを検知できないので、`If-Modified-Since`は見ない。
+fn compute_and_filter_out(
+    x: &[(ArticleId, Article)],
+    page: &PageRequest,
+    path: &str,
+    if_none_match: Option<&IfNoneMatch>,
+) -> ArticleIdCollectionResponseRepr {
+    let entries = x.iter()
+        .filter(|x| x.1.visibility == Visibility::Public)
+        .filter(|x| page.tag.as_ref().is_none_or(|tag| x.1.tags.contains(tag)))
         .map(|(id, a)| ArticleListResponseEntry {
             id: id.clone(),
             created_at: a.created_at,
             updated_at: a.updated_at,
-        }).collect());
-    let old_cre = x.iter().filter(only_public).filter(additional_filter.clone())
-        .min_by_key(|x| x.1.created_at).map(|x| x.1.created_at);
-    let new_upd = x.iter().filter(only_public).filter(additional_filter)
-        .max_by_key(|x| x.1.updated_at).map(|x| x.1.updated_at);
+            author: a.created_by.clone(),
+            title: a.title.clone(),
+            summary: a.summary.clone(),
+            lang: a.lang.clone(),
+        }).collect::<Vec<_>>();
+    let (entries, next) = paginate(entries, page);
+    let next_page_link = next.map(|next| page.next_page_link(path, &next));
+
+    let latest_updated = entries.iter().map(|x| x.updated_at).max();
+    let old_cre = entries.iter().map(|x| x.created_at).min();
+    let data = ArticleListingResponseRepresentation(entries);
+    let not_modified = matches_if_none_match(&listing_etag(&data, next_page_link.as_deref()), if_none_match);
 
     ArticleIdCollectionResponseRepr(
         MaybeNotModified {
@@ -49,83 +170,276 @@ fn compute_and_filter_out(
                 inner: OwnedMetadata {
                     metadata: ArticleListingResponseMetadata {
                         oldest_created_at: old_cre,
-                        newest_updated_at: new_upd,
+                        newest_updated_at: latest_updated,
                     },
-                    data: entries
+                    data
                 },
                 latest_updated: latest_updated.map(|x| x.try_into().unwrap())
             },
-            is_modified: ret_304,
-        }
+            not_modified,
+        },
+        next_page_link,
     )
 }
 
-const ONCE_CELL_INITIALIZATION_ERROR: &str = "must be fully initialized";
+fn invalid_cursor() -> HttpResponse {
+    HttpResponse::BadRequest().body("invalid cursor")
+}
+
+/// 記事を読み出せなかった場合は`500`にする。
+fn into_listing_response(res: Result<ArticleIdCollectionResponseRepr, PersistenceError>) -> HttpResponse {
+    match res {
+        Ok(repr) => EndpointRepresentationCompiler::from_value(repr)
+            .into_json()
+            .map_body(|_, y| serde_json::to_string(&y).expect(""))
+            .map_into_boxed_body(),
+        Err(e) => EndpointRepresentationCompiler::from_value(UnhandledError::new(e))
+            .into_plain_text()
+            .map_into_boxed_body(),
+    }
+}
 
 #[get("/article")]
-#[allow(clippy::unused_async)]
-pub fn article_id_list(if_modified_since: Option<IfModifiedSince>) -> impl Future<Output = impl Responder> {
-    let v = EndpointRepresentationCompiler::from_value(
-        article_id_list0(GLOBAL_ARTICLE_REPOSITORY.get().expect(ONCE_CELL_INITIALIZATION_ERROR), if_modified_since)
-    ).into_json()
-        .map_body(|_, y| serde_json::to_string(&y).expect(""))
-        .map_into_boxed_body();
+#[allow(clippy::unused_async, clippy::needless_pass_by_value)]
+pub fn article_id_list(query: Query<ListArticleRequestQuery>, if_none_match: Option<Header<IfNoneMatch>>, request: HttpRequest, repo: Data<dyn ArticleStore>) -> impl Future<Output = impl Responder> {
+    let Some(page) = PageRequest::from_query(&query) else {
+        return ready(invalid_cursor())
+    };
+    ready(into_listing_response(
+        article_id_list0(&**repo, &page, request.path(), if_none_match.as_deref())
+    ))
+}
 
-    ready(v)
+fn article_id_list0(repo: &dyn ArticleStore, page: &PageRequest, path: &str, if_none_match: Option<&IfNoneMatch>) -> Result<ArticleIdCollectionResponseRepr, PersistenceError> {
+    Ok(compute_and_filter_out(&repo.entries()?, page, path, if_none_match))
 }
 
-fn article_id_list0(repo: &ArticleRepository, if_modified_since: Option<IfModifiedSince>) -> ArticleIdCollectionResponseRepr {
-    compute_and_filter_out(repo, if_modified_since, |_| true)
+/// `year`年`month`月1日の0時0分0秒 (ローカル時刻)
+fn beginning_of_month(year: u32, month: u32) -> Option<DateTime<Local>> {
+    NaiveDate::from_ymd_opt(i32::try_from(year).ok()?, month, 1)?
+        .and_hms_opt(0, 0, 0)?
+        .and_local_timezone(Local)
+        .earliest()
 }
 
-#[get("/article/{year}")]
-#[allow(clippy::unused_async)]
-pub fn article_id_list_by_year(path: Path<AnnoDominiYear>, if_modified_since: Option<IfModifiedSince>) -> impl Future<Output = impl Responder> {
-    let v = EndpointRepresentationCompiler::from_value(
-        article_id_list_by_year0(GLOBAL_ARTICLE_REPOSITORY.get().expect(ONCE_CELL_INITIALIZATION_ERROR), path.into_inner(), if_modified_since)
-    ).into_json()
-        .map_body(|_, y| serde_json::to_string(&y).expect(""))
-        .map_into_boxed_body();
+fn entries_created_between(repo: &dyn ArticleStore, from: Option<DateTime<Local>>, until: Option<DateTime<Local>>) -> Result<Vec<(ArticleId, Article)>, PersistenceError> {
+    match (from, until) {
+        (Some(from), Some(until)) => repo.entries_created_between(from, until),
+        _ => Ok(vec![]),
+    }
+}
 
-    ready(v)
+#[get("/article/{year}")]
+#[allow(clippy::unused_async, clippy::needless_pass_by_value)]
+pub fn article_id_list_by_year(path: Path<AnnoDominiYear>, query: Query<ListArticleRequestQuery>, if_none_match: Option<Header<IfNoneMatch>>, request: HttpRequest, repo: Data<dyn ArticleStore>) -> impl Future<Output = impl Responder> {
+    let Some(page) = PageRequest::from_query(&query) else {
+        return ready(invalid_cursor())
+    };
+    ready(into_listing_response(
+        article_id_list_by_year0(&**repo, path.into_inner(), &page, request.path(), if_none_match.as_deref())
+    ))
 }
 
-#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
-fn article_id_list_by_year0(repo: &ArticleRepository, path: AnnoDominiYear, if_modified_since: Option<IfModifiedSince>) -> ArticleIdCollectionResponseRepr {
-    let year = path.into_inner();
-    compute_and_filter_out(repo, if_modified_since, |x| x.1.created_at.year() as u32 == year)
+fn article_id_list_by_year0(repo: &dyn ArticleStore, year: AnnoDominiYear, page: &PageRequest, path: &str, if_none_match: Option<&IfNoneMatch>) -> Result<ArticleIdCollectionResponseRepr, PersistenceError> {
+    let year = year.into_inner();
+    let entries = entries_created_between(
+        repo,
+        beginning_of_month(year, 1),
+        year.checked_add(1).and_then(|next_year| beginning_of_month(next_year, 1)),
+    )?;
+
+    Ok(compute_and_filter_out(&entries, page, path, if_none_match))
 }
 
 #[get("/article/{year}/{month}")]
+#[allow(clippy::needless_pass_by_value)]
 pub fn article_id_list_by_year_and_month(
-    path: Path<(AnnoDominiYear, OneOriginTwoDigitsMonth)>, if_modified_since: Option<IfModifiedSince>
+    path: Path<(AnnoDominiYear, OneOriginTwoDigitsMonth)>, query: Query<ListArticleRequestQuery>, if_none_match: Option<Header<IfNoneMatch>>, request: HttpRequest, repo: Data<dyn ArticleStore>
 ) -> impl Future<Output = impl Responder> {
-    let v = EndpointRepresentationCompiler::from_value(
-        article_id_list_by_year_and_month0(GLOBAL_ARTICLE_REPOSITORY.get().expect(ONCE_CELL_INITIALIZATION_ERROR), path.into_inner(), if_modified_since)
-    ).into_json()
-        .map_body(|_, y| serde_json::to_string(&y).expect(""))
-        .map_into_boxed_body();
-
-    ready(v)
+    let Some(page) = PageRequest::from_query(&query) else {
+        return ready(invalid_cursor())
+    };
+    ready(into_listing_response(
+        article_id_list_by_year_and_month0(&**repo, path.into_inner(), &page, request.path(), if_none_match.as_deref())
+    ))
 }
 
-#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
-fn article_id_list_by_year_and_month0(repo: &ArticleRepository, path: (AnnoDominiYear, OneOriginTwoDigitsMonth), if_modified_since: Option<IfModifiedSince>) 
-    -> ArticleIdCollectionResponseRepr {
-    let (year, month) = path;
+fn article_id_list_by_year_and_month0(repo: &dyn ArticleStore, year_and_month: (AnnoDominiYear, OneOriginTwoDigitsMonth), page: &PageRequest, path: &str, if_none_match: Option<&IfNoneMatch>) 
+    -> Result<ArticleIdCollectionResponseRepr, PersistenceError> {
+    let (year, month) = year_and_month;
     let year = year.into_inner();
-    let month = month.into_inner();
-    compute_and_filter_out(repo, if_modified_since, |x| x.1.created_at.year() as u32 == year && x.1.created_at.month() as u8 == month)
+    let month = u32::from(month.into_inner());
+    let next_month = if month == 12 {
+        year.checked_add(1).and_then(|next_year| beginning_of_month(next_year, 1))
+    } else {
+        beginning_of_month(year, month + 1)
+    };
+    let entries = entries_created_between(repo, beginning_of_month(year, month), next_month)?;
+
+    Ok(compute_and_filter_out(&entries, page, path, if_none_match))
 }
 
 #[cfg(test)]
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
 mod tests {
-    use chrono::{Datelike, Local};
+    use chrono::{Datelike, Local, TimeDelta};
+
+    use std::num::NonZeroU32;
+    use std::sync::Arc;
+    use actix_web::App;
+    use actix_web::http::header::{HeaderName, IfNoneMatch, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LINK};
+    use actix_web::http::StatusCode;
+    use actix_web::test::{call_service, init_service, TestRequest};
+    use actix_web::web::{self, Data};
+
+    use toy_blog_endpoint_model::{AnnoDominiYear, ArticleId, ArticleListResponseEntry, ListArticleRequestQuery, ListArticleSortPolicy, OneOriginTwoDigitsMonth, Visibility};
+
+    use crate::service::persistence::{ArticleRepository, ArticleStore, InMemoryArticleStore};
+    use crate::service::rest::api::list::{article_id_list, article_id_list0, article_id_list_by_year0, article_id_list_by_year_and_month0, compute_and_filter_out, Cursor, PageRequest, paginate, sort_entries};
+    use crate::service::rest::exposed_representation_format::listing_etag;
+    use crate::service::rest::header::HttpDate;
+
+    fn entry_created_hours_ago(id: &str, hours: i64) -> ArticleListResponseEntry {
+        let at = Local::now() - TimeDelta::hours(hours);
+        ArticleListResponseEntry { id: ArticleId::new(id.to_string()), created_at: at, updated_at: at, author: None, title: None, summary: None, lang: None }
+    }
+
+    fn ids(entries: &[ArticleListResponseEntry]) -> String {
+        entries.iter().map(|x| x.id.0.as_str()).collect::<Vec<_>>().join(",")
+    }
+
+    #[test]
+    fn cursor_stays_stable_while_articles_are_created_or_deleted() {
+        let page = |after| PageRequest { sort: ListArticleSortPolicy::Newest, after, limit: NonZeroU32::new(2), tag: None };
+        let mut entries = vec![
+            entry_created_hours_ago("a", 1),
+            entry_created_hours_ago("b", 2),
+            entry_created_hours_ago("c", 3),
+            entry_created_hours_ago("d", 4),
+            entry_created_hours_ago("e", 5),
+        ];
+
+        let (first, next) = paginate(entries.clone(), &page(None));
+        assert_eq!(ids(&first), "a,b");
+
+        // 1ページ目を取得した後に、先頭に記事が作られ、1ページ目の記事が削除された
+        entries.push(entry_created_hours_ago("new", 0));
+        entries.retain(|x| x.id.0 != "b");
+
+        let (second, next) = paginate(entries.clone(), &page(next));
+        assert_eq!(ids(&second), "c,d");
+        let (third, next) = paginate(entries, &page(next));
+        assert_eq!(ids(&third), "e");
+        assert_eq!(next, None);
+    }
+
+    #[test]
+    fn cursor_is_bound_to_sort_order() {
+        let cursor = Cursor { sort: ListArticleSortPolicy::Oldest, at: Local::now(), id: ArticleId::new("a".to_string()) };
+        let query = |sort, cursor: &str| ListArticleRequestQuery { policy: sort, limit: None, cursor: Some(cursor.to_string()), tag: None };
+
+        assert_eq!(Cursor::decode(&cursor.encode()), Some(cursor.clone()));
+        assert!(PageRequest::from_query(&query(Some(ListArticleSortPolicy::Oldest), &cursor.encode())).is_some());
+        assert!(PageRequest::from_query(&query(None, &cursor.encode())).is_none());
+        assert!(PageRequest::from_query(&query(Some(ListArticleSortPolicy::Oldest), "broken")).is_none());
+    }
+
+    #[test]
+    fn etag_is_computed_per_page() {
+        let store = InMemoryArticleStore::new();
+        for id in ["a", "b", "c"] {
+            store.create_entry(&ArticleId::new(id.to_string()), id.to_string(), Visibility::Public, None).unwrap();
+        }
+        let page = PageRequest { sort: ListArticleSortPolicy::Oldest, after: None, limit: NonZeroU32::new(1), tag: None };
+        let first = compute_and_filter_out(&store.entries().unwrap(), &page, "/api/list/article", None);
+        assert!(!first.0.not_modified);
+        assert!(first.1.as_deref().is_some_and(|link| link.starts_with("</api/list/article?sort=oldest&limit=1&cursor=")));
+        let if_none_match = IfNoneMatch::Items(vec![listing_etag(&first.0.inner.inner.data, first.1.as_deref())]);
+        let is_not_modified = || compute_and_filter_out(&store.entries().unwrap(), &page, "/api/list/article", Some(&if_none_match)).0.not_modified;
+
+        assert!(is_not_modified(), "first page should be reported as not modified");
+        // 他のページの記事の変更は、このページに影響しない
+        store.update_entry(&ArticleId::new("b".to_string()), "changed".to_string(), None).unwrap();
+        assert!(is_not_modified());
+        // 次のページが無くなれば、記事の更新日時が変わらなくても変わったものとして扱う
+        for id in ["b", "c"] {
+            store.change_visibility(&ArticleId::new(id.to_string()), Visibility::Private).unwrap();
+        }
+        assert!(!is_not_modified());
+    }
+
+    #[actix_web::test]
+    async fn paginated_listing_answers_not_modified() {
+        let store: Arc<dyn ArticleStore> = Arc::new(InMemoryArticleStore::new());
+        for id in ["a", "b"] {
+            store.create_entry(&ArticleId::new(id.to_string()), id.to_string(), Visibility::Public, None).unwrap();
+        }
+        let app = init_service(App::new().service(web::scope("/api/list").service(article_id_list)).app_data(Data::from(store.clone()))).await;
+        let request = |header: (HeaderName, String)| TestRequest::get()
+            .uri("/api/list/article?sort=oldest&limit=1")
+            .insert_header(header)
+            .to_request();
+
+        let response = call_service(&app, TestRequest::get().uri("/api/list/article?sort=oldest&limit=1").to_request()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let etag = response.headers().get(ETAG).unwrap().to_str().unwrap().to_string();
 
-    use toy_blog_endpoint_model::{AnnoDominiYear, ArticleId, OneOriginTwoDigitsMonth, Visibility};
+        let response = call_service(&app, request((IF_NONE_MATCH, etag.clone()))).await;
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert!(response.headers().contains_key(LINK), "next page should still be reported");
 
-    use crate::service::persistence::ArticleRepository;
-    use crate::service::rest::api::list::{article_id_list0, article_id_list_by_year0, article_id_list_by_year_and_month0};
+        // ページの記事の更新日時だけでは、記事がページから消えたことを検知できない
+        let future = HttpDate::try_from(Local::now() + TimeDelta::days(1)).unwrap().to_string();
+        assert_eq!(call_service(&app, request((IF_MODIFIED_SINCE, future))).await.status(), StatusCode::OK);
+
+        store.remove_if(&ArticleId::new("b".to_string()), &|_| true).unwrap();
+        let response = call_service(&app, request((IF_NONE_MATCH, etag))).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(!response.headers().contains_key(LINK));
+    }
+
+    #[test]
+    fn filtered_by_tag_and_tag_is_kept_in_next_page_link() {
+        let store = InMemoryArticleStore::new();
+        for (id, tags) in [("a", vec!["日記"]), ("b", vec![]), ("c", vec!["日記", "rust"])] {
+            let id = ArticleId::new(id.to_string());
+            store.create_entry(&id, String::new(), Visibility::Public, None).unwrap();
+            store.change_tags(&id, tags.into_iter().map(ToString::to_string).collect()).unwrap();
+        }
+        let page = PageRequest { sort: ListArticleSortPolicy::Oldest, after: None, limit: NonZeroU32::new(1), tag: Some("日記".to_string()) };
+
+        let first = compute_and_filter_out(&store.entries().unwrap(), &page, "/api/list/article", None);
+        assert_eq!(ids(&first.0.inner.inner.data.0), "a");
+        assert!(first.1.as_deref().is_some_and(|link| link.contains("&tag=%E6%97%A5%E8%A8%98&cursor=")));
+
+        let all = PageRequest { limit: None, ..page };
+        assert_eq!(ids(&compute_and_filter_out(&store.entries().unwrap(), &all, "/", None).0.inner.inner.data.0), "a,c");
+    }
+
+    #[test]
+    fn entries_are_sorted_by_policy_and_then_by_id() {
+        let now = Local::now();
+        let entry = |id: &str, created_hours_ago, updated_hours_ago| ArticleListResponseEntry {
+            id: ArticleId::new(id.to_string()),
+            created_at: now - TimeDelta::hours(created_hours_ago),
+            updated_at: now - TimeDelta::hours(updated_hours_ago),
+            author: None,
+            title: None,
+            summary: None,
+            lang: None,
+        };
+        let mut entries = vec![entry("c", 2, 0), entry("b", 1, 1), entry("a", 2, 2)];
+        let ids = |entries: &[ArticleListResponseEntry]| entries.iter().map(|x| x.id.0.as_str()).collect::<Vec<_>>().join(",");
+
+        sort_entries(&mut entries, ListArticleSortPolicy::Newest);
+        assert_eq!(ids(&entries), "b,a,c");
+        sort_entries(&mut entries, ListArticleSortPolicy::Oldest);
+        assert_eq!(ids(&entries), "a,c,b");
+        sort_entries(&mut entries, ListArticleSortPolicy::RecentUpdated);
+        assert_eq!(ids(&entries), "c,b,a");
+        sort_entries(&mut entries, ListArticleSortPolicy::LeastRecentlyUpdated);
+        assert_eq!(ids(&entries), "a,b,c");
+    }
 
     #[test]
     fn do_not_include_non_public_article() {
@@ -139,15 +453,15 @@ mod tests {
                 let a = ArticleRepository::new(m.path()).await;
                 {
                     let aa = ArticleId::new("12345".to_string());
-                    a.create_entry(&aa, "12345".to_string(), Visibility::Private).unwrap();
-                    let ac = article_id_list0(&a, None);
+                    a.create_entry(&aa, "12345".to_string(), Visibility::Private, None).unwrap();
+                    let ac = article_id_list0(&a, &PageRequest::default(), "/", None).unwrap();
                     let m = ac.0.inner.inner.data.0.iter().find(|x| x.id == aa);
                     assert!(m.is_none());
                 }
                 {
                     let aa = ArticleId::new("123456".to_string());
-                    a.create_entry(&aa, "123456".to_string(), Visibility::Restricted).unwrap();
-                    let ac = article_id_list0(&a, None);
+                    a.create_entry(&aa, "123456".to_string(), Visibility::Restricted, None).unwrap();
+                    let ac = article_id_list0(&a, &PageRequest::default(), "/", None).unwrap();
                     let m = ac.0.inner.inner.data.0.iter().find(|x| x.id == aa);
                     assert!(m.is_none());
                 }
@@ -166,15 +480,15 @@ mod tests {
                 let a = ArticleRepository::new(m.path()).await;
                 {
                     let aa = ArticleId::new("12345".to_string());
-                    a.create_entry(&aa, "12345".to_string(), Visibility::Private).unwrap();
-                    let ac = article_id_list_by_year0(&a, AnnoDominiYear::try_from(Local::now().year() as u32).unwrap(), None);
+                    a.create_entry(&aa, "12345".to_string(), Visibility::Private, None).unwrap();
+                    let ac = article_id_list_by_year0(&a, AnnoDominiYear::try_from(Local::now().year() as u32).unwrap(), &PageRequest::default(), "/", None).unwrap();
                     let m = ac.0.inner.inner.data.0.iter().find(|x| x.id == aa);
                     assert!(m.is_none());
                 }
                 {
                     let aa = ArticleId::new("123456".to_string());
-                    a.create_entry(&aa, "123456".to_string(), Visibility::Restricted).unwrap();
-                    let ac = article_id_list_by_year0(&a, AnnoDominiYear::try_from(Local::now().year() as u32).unwrap(), None);
+                    a.create_entry(&aa, "123456".to_string(), Visibility::Restricted, None).unwrap();
+                    let ac = article_id_list_by_year0(&a, AnnoDominiYear::try_from(Local::now().year() as u32).unwrap(), &PageRequest::default(), "/", None).unwrap();
                     let m = ac.0.inner.inner.data.0.iter().find(|x| x.id == aa);
                     assert!(m.is_none());
                 }
@@ -193,27 +507,27 @@ mod tests {
                 let a = ArticleRepository::new(m.path()).await;
                 {
                     let aa = ArticleId::new("12345".to_string());
-                    a.create_entry(&aa, "12345".to_string(), Visibility::Private).unwrap();
+                    a.create_entry(&aa, "12345".to_string(), Visibility::Private, None).unwrap();
                     let now = Local::now();
                     let ac = article_id_list_by_year_and_month0(
                         &a, (
                             AnnoDominiYear::try_from(now.year() as u32).unwrap(),
                             OneOriginTwoDigitsMonth::try_from(now.month() as u8).unwrap()
-                        ), None
-                    );
+                        ), &PageRequest::default(), "/", None
+                    ).unwrap();
                     let a = ac.0.inner.inner.data.0.iter().find(|x| x.id == aa);
                     assert!(a.is_none());
                 }
                 {
                     let aa = ArticleId::new("123456".to_string());
-                    a.create_entry(&aa, "123456".to_string(), Visibility::Restricted).unwrap();
+                    a.create_entry(&aa, "123456".to_string(), Visibility::Restricted, None).unwrap();
                     let now = Local::now();
                     let ac = article_id_list_by_year_and_month0(
                         &a, (
                             AnnoDominiYear::try_from(now.year() as u32).unwrap(),
                             OneOriginTwoDigitsMonth::try_from(now.month() as u8).unwrap()
-                        ), None
-                    );
+                        ), &PageRequest::default(), "/", None
+                    ).unwrap();
                     let a = ac.0.inner.inner.data.0.iter().find(|x| x.id == aa);
                     assert!(a.is_none());
                 }
@@ -1,28 +1,41 @@
-use actix_web::Responder;
-use actix_web::web::Query;
+use actix_web::{HttpRequest, Responder};
+use actix_web::web::{Data, Query};
 use actix_web::post;
 use actix_web_httpauth::extractors::bearer::BearerAuth;
-use toy_blog_endpoint_model::{ChangeArticleIdError, ChangeArticleIdRequestQuery, ChangeArticleIdRequestResult};
-use crate::service::rest::auth::{is_wrong_token};
+use toy_blog_endpoint_model::{Article, AuditAction, ChangeArticleIdError, ChangeArticleIdRequestQuery, ChangeArticleIdRequestResult};
+use crate::service::rest::api::article::authorize_modification;
+use crate::service::rest::audit::{AuditEvent, AuditLog};
+use crate::service::rest::auth::TokenScope;
 use crate::service::rest::exposed_representation_format::EndpointRepresentationCompiler;
+use crate::service::rest::precondition::WritePreconditions;
 use crate::service::rest::ComposeInternalError;
 use crate::service::rest::inner_no_leak::UnhandledError;
-use crate::service::persistence::PersistenceError;
-use crate::service::rest::repository::GLOBAL_ARTICLE_REPOSITORY;
+use crate::service::persistence::{ArticleStore, PersistenceError};
 
 #[post("/change-id")]
-pub async fn change_id(query: Query<ChangeArticleIdRequestQuery>, bearer: BearerAuth) -> impl Responder {
+pub async fn change_id(query: Query<ChangeArticleIdRequestQuery>, bearer: BearerAuth, preconditions: WritePreconditions, request: HttpRequest, repo: Data<dyn ArticleStore>, audit: Data<AuditLog>) -> impl Responder {
     let token = bearer.token();
 
     let ChangeArticleIdRequestQuery { from, to } = query.into_inner();
 
     let res: ComposeInternalError<ChangeArticleIdRequestResult> = (|| {
-        if is_wrong_token(token) {
-            return Ok(Err(ChangeArticleIdError::Unauthorized))
+        let (principal, article) = match authorize_modification(&request, &**repo, &from, token, TokenScope::MetaRename, ChangeArticleIdError::ArticleNotFoundById)? {
+            Ok(x) => x,
+            Err(e) => return Ok(Err(e)),
+        };
+
+        // 読んでから書くまでに変わっているかもしれないので、書き込む直前にもストアの中で評価する
+        if let Err(e) = preconditions.evaluate(&from, &article) {
+            return Ok(Err(e.into()))
         }
+        let expected = |current: &Article| preconditions.evaluate(&from, current).is_ok();
 
-        match GLOBAL_ARTICLE_REPOSITORY.get().expect("must be fully-initialized").rename(&from, to) {
+        match repo.rename_if(&from, &expected, to.clone()) {
             Ok(()) => {
+                audit.record(&request, &principal, AuditEvent {
+                    renamed_to: Some(&to),
+                    ..AuditEvent::new(AuditAction::Rename, &from)
+                });
                 Ok(Ok(()))
             }
             Err(e) => {
@@ -30,11 +43,65 @@ pub async fn change_id(query: Query<ChangeArticleIdRequestQuery>, bearer: Bearer
                     PersistenceError::AbsentValue => {
                         Ok(Err(ChangeArticleIdError::ArticleNotFoundById))
                     }
+                    PersistenceError::DuplicatedId => {
+                        Ok(Err(ChangeArticleIdError::DuplicatedArticleId))
+                    }
+                    PersistenceError::PreconditionFailed => {
+                        Ok(Err(ChangeArticleIdError::PreconditionFailed))
+                    }
                     other => Err(UnhandledError::new(other)),
                 }
             }
         }
     })();
 
-    EndpointRepresentationCompiler::from_value(res).into_plain_text()
+    EndpointRepresentationCompiler::from_value(res).negotiate(&request)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use actix_web::App;
+    use actix_web::http::header::AUTHORIZATION;
+    use actix_web::http::StatusCode;
+    use actix_web::test::{call_service, init_service, TestRequest};
+    use actix_web::web::{self, Data};
+    use toy_blog_endpoint_model::{ArticleId, AuditAction, Visibility};
+    use crate::service::persistence::{ArticleStore, InMemoryArticleStore};
+    use crate::service::rest::audit::AuditLog;
+    use crate::service::rest::auth::install_test_tokens;
+    use super::change_id;
+
+    #[actix_web::test]
+    async fn rename_to_existing_id_is_conflict() {
+        let repo: Arc<dyn ArticleStore> = Arc::new(InMemoryArticleStore::new());
+        repo.create_entry(&ArticleId::new("a".to_string()), "a".to_string(), Visibility::Public, None).unwrap();
+        repo.create_entry(&ArticleId::new("b".to_string()), "b".to_string(), Visibility::Public, None).unwrap();
+        let audit_log = tempfile::NamedTempFile::new().unwrap();
+        let audit = Data::new(AuditLog::open(audit_log.path(), false).unwrap());
+        install_test_tokens();
+        let app = init_service(
+            App::new()
+                .service(web::scope("/api/meta").service(change_id))
+                .app_data(Data::from(repo.clone()))
+                .app_data(audit.clone())
+        ).await;
+        let rename = |to: &str| TestRequest::post()
+            .uri(&format!("/api/meta/change-id?from=a&to={to}"))
+            .insert_header((AUTHORIZATION, "Bearer admin"))
+            .to_request();
+
+        let res = call_service(&app, rename("b")).await;
+        assert_eq!(res.status(), StatusCode::CONFLICT);
+        assert_eq!(repo.read_snapshot(&ArticleId::new("a".to_string())).unwrap().content, "a");
+        assert!(audit.read(None, None, usize::MAX).unwrap().is_empty());
+
+        let res = call_service(&app, rename("c")).await;
+        assert!(res.status().is_success());
+        let recorded = audit.read(None, None, usize::MAX).unwrap();
+        assert_eq!(recorded.len(), 1);
+        assert_eq!(recorded[0].action, AuditAction::Rename);
+        assert_eq!(recorded[0].old_content_hash, None);
+        assert_eq!(recorded[0].new_content_hash, None);
+    }
 }
@@ -0,0 +1,148 @@
+use actix_web::{get, post, HttpRequest, Responder};
+use actix_web::web::{Data, Path, Query};
+use actix_web_httpauth::extractors::bearer::BearerAuth;
+use similar::TextDiff;
+use toy_blog_endpoint_model::{ArticleContent, ArticleId, ArticleRevision, ArticleRevisionDiffQuery, ArticleRevisionNumber, ArticleRevisionSummary, ArticleSnapshot, ArticleSnapshotMetadata, AuditAction, DiffArticleRevisionResult, GetArticleError, GetArticleRevisionError, GetArticleRevisionResult, ListArticleRevisionsResult, OwnedMetadata, RevertArticleError, RevertArticleResult, UnifiedDiff};
+use crate::service::persistence::{ArticleStore, PersistenceError};
+use crate::service::rest::api::article::{authorize_modification, is_readable};
+use crate::service::rest::audit::{AuditEvent, AuditLog};
+use crate::service::rest::auth::TokenScope;
+use crate::service::rest::ComposeInternalError;
+use crate::service::rest::exposed_representation_format::EndpointRepresentationCompiler;
+use crate::service::rest::inner_no_leak::UnhandledError;
+
+/// 読み取りが許可されている記事の全ての版を返す。記事が無いか、読み取りが許可されていないときは`None`を返す。
+fn readable_revisions(request: &HttpRequest, repo: &dyn ArticleStore, article_id: &ArticleId, auth: Option<&BearerAuth>) -> Result<Option<Vec<ArticleRevision>>, UnhandledError> {
+    let article = match repo.read_snapshot(article_id) {
+        Ok(article) => article,
+        Err(PersistenceError::AbsentValue) => return Ok(None),
+        Err(e) => return Err(UnhandledError::new(e)),
+    };
+
+    if !is_readable(request, &article, auth) {
+        return Ok(None)
+    }
+
+    match repo.revisions(article_id) {
+        Ok(revisions) => Ok(Some(revisions)),
+        Err(PersistenceError::AbsentValue) => Ok(None),
+        Err(e) => Err(UnhandledError::new(e)),
+    }
+}
+
+fn find_revision(revisions: &[ArticleRevision], revision: u32) -> Option<&ArticleRevision> {
+    let revision = ArticleRevisionNumber::try_from(revision).ok()?;
+
+    revisions.iter().find(|x| x.revision == revision)
+}
+
+#[get("/{article_id}/revisions")]
+pub async fn list(path: Path<String>, auth: Option<BearerAuth>, request: HttpRequest, repo: Data<dyn ArticleStore>) -> impl Responder {
+    let article_id = ArticleId::new(path.into_inner());
+
+    let res: ComposeInternalError<ListArticleRevisionsResult> = (|| {
+        let Some(revisions) = readable_revisions(&request, &**repo, &article_id, auth.as_ref())? else {
+            return Ok(Err(GetArticleError::NoSuchArticleFoundById))
+        };
+
+        Ok(Ok(revisions.into_iter().map(|x| ArticleRevisionSummary {
+            revision: x.revision,
+            created_at: x.created_at,
+        }).collect()))
+    })();
+
+    EndpointRepresentationCompiler::from_value(res).negotiate(&request)
+}
+
+#[get("/{article_id}/revisions/{revision}")]
+pub async fn fetch(path: Path<(String, u32)>, auth: Option<BearerAuth>, request: HttpRequest, repo: Data<dyn ArticleStore>) -> impl Responder {
+    let (article_id, revision) = path.into_inner();
+    let article_id = ArticleId::new(article_id);
+
+    let res: ComposeInternalError<GetArticleRevisionResult> = (|| {
+        let Some(revisions) = readable_revisions(&request, &**repo, &article_id, auth.as_ref())? else {
+            return Ok(Err(GetArticleRevisionError::NoSuchArticleFoundById))
+        };
+
+        let Some(revision) = find_revision(&revisions, revision) else {
+            return Ok(Err(GetArticleRevisionError::NoSuchRevisionFound))
+        };
+
+        Ok(Ok(OwnedMetadata {
+            metadata: ArticleSnapshotMetadata {
+                updated_at: revision.created_at.fixed_offset(),
+            },
+            data: ArticleSnapshot {
+                content: ArticleContent::new(revision.content.clone()),
+            },
+        }))
+    })();
+
+    EndpointRepresentationCompiler::from_value(res).negotiate(&request)
+}
+
+#[get("/{article_id}/diff")]
+pub async fn diff(path: Path<String>, query: Query<ArticleRevisionDiffQuery>, auth: Option<BearerAuth>, request: HttpRequest, repo: Data<dyn ArticleStore>) -> impl Responder {
+    let article_id = ArticleId::new(path.into_inner());
+    let ArticleRevisionDiffQuery { from, to } = query.into_inner();
+
+    let res: ComposeInternalError<DiffArticleRevisionResult> = (|| {
+        let Some(revisions) = readable_revisions(&request, &**repo, &article_id, auth.as_ref())? else {
+            return Ok(Err(GetArticleRevisionError::NoSuchArticleFoundById))
+        };
+
+        let (Some(old), Some(new)) = (
+            find_revision(&revisions, from.into_inner()),
+            find_revision(&revisions, to.into_inner()),
+        ) else {
+            return Ok(Err(GetArticleRevisionError::NoSuchRevisionFound))
+        };
+
+        let diff = TextDiff::from_lines(&old.content, &new.content)
+            .unified_diff()
+            .header(&format!("{article_id}@{from}"), &format!("{article_id}@{to}"))
+            .to_string();
+
+        Ok(Ok(UnifiedDiff(diff)))
+    })();
+
+    EndpointRepresentationCompiler::from_value(res).negotiate(&request)
+}
+
+#[post("/{article_id}/revisions/{revision}/revert")]
+pub async fn revert(path: Path<(String, u32)>, bearer: BearerAuth, request: HttpRequest, repo: Data<dyn ArticleStore>, audit: Data<AuditLog>) -> impl Responder {
+    let (article_id, revision) = path.into_inner();
+    let article_id = ArticleId::new(article_id);
+
+    let res: ComposeInternalError<RevertArticleResult> = (|| {
+        let (principal, old) = match authorize_modification(&request, &**repo, &article_id, bearer.token(), TokenScope::ArticleWrite, RevertArticleError::ArticleNotFoundById)? {
+            Ok(x) => x,
+            Err(e) => return Ok(Err(e)),
+        };
+
+        let revisions = match repo.revisions(&article_id) {
+            Ok(revisions) => revisions,
+            Err(PersistenceError::AbsentValue) => return Ok(Err(RevertArticleError::ArticleNotFoundById)),
+            Err(e) => return Err(UnhandledError::new(e)),
+        };
+
+        let Some(revision) = find_revision(&revisions, revision) else {
+            return Ok(Err(RevertArticleError::NoSuchRevisionFound))
+        };
+
+        // 古い版を書き戻すのではなく、その内容で新しい版を作る
+        match repo.update_entry(&article_id, revision.content.clone(), Some(&principal.name)) {
+            Ok(()) => {
+                audit.record(&request, &principal, AuditEvent {
+                    old_content: Some(&old.content),
+                    new_content: Some(&revision.content),
+                    ..AuditEvent::new(AuditAction::Revert, &article_id)
+                });
+                Ok(Ok(()))
+            }
+            Err(e) => Err(UnhandledError::new(e)),
+        }
+    })();
+
+    EndpointRepresentationCompiler::from_value(res).negotiate(&request)
+}
@@ -0,0 +1,185 @@
+use actix_web::{delete, get, put, HttpRequest, Responder};
+use actix_web::web::{Data, Json, Path};
+use actix_web_httpauth::extractors::bearer::BearerAuth;
+use chrono::Local;
+use toy_blog_endpoint_model::{ArticleId, AuditAction, ListScheduledPublicationsResult, ScheduledPublication, SchedulePublicationError, SchedulePublicationPayload, SchedulePublicationResult, Visibility};
+use crate::service::persistence::ArticleStore;
+use crate::service::rest::api::article::authorize_modification;
+use crate::service::rest::audit::{AuditEvent, AuditLog};
+use crate::service::rest::auth::{authorize, TokenScope};
+use crate::service::rest::exposed_representation_format::EndpointRepresentationCompiler;
+use crate::service::rest::inner_no_leak::{ComposeInternalError, UnhandledError};
+
+#[put("/{article_id}/schedule")]
+#[allow(clippy::unused_async, clippy::needless_pass_by_value)]
+pub async fn update(path: Path<String>, payload: Json<SchedulePublicationPayload>, bearer: BearerAuth, request: HttpRequest, repo: Data<dyn ArticleStore>, audit: Data<AuditLog>) -> impl Responder {
+    let article_id = ArticleId::new(path.into_inner());
+
+    let res: ComposeInternalError<SchedulePublicationResult> = (|| {
+        let (principal, article) = match authorize_modification(&request, &**repo, &article_id, bearer.token(), TokenScope::ArticleWrite, SchedulePublicationError::NoSuchArticleFoundById)? {
+            Ok(x) => x,
+            Err(e) => return Ok(Err(e)),
+        };
+
+        if article.visibility != Visibility::Private {
+            return Ok(Err(SchedulePublicationError::NotPrivate))
+        }
+
+        let publish_at = payload.publish_at.with_timezone(&Local);
+        if publish_at <= Local::now() {
+            return Ok(Err(SchedulePublicationError::NotInFuture))
+        }
+
+        repo.schedule_publication(&article_id, Some(publish_at)).map_err(UnhandledError::new)?;
+        audit.record(&request, &principal, AuditEvent {
+            old_content: Some(&article.content),
+            new_content: Some(&article.content),
+            publish_at: Some(publish_at),
+            ..AuditEvent::new(AuditAction::SchedulePublication, &article_id)
+        });
+
+        Ok(Ok(()))
+    })();
+
+    EndpointRepresentationCompiler::from_value(res).negotiate(&request)
+}
+
+#[delete("/{article_id}/schedule")]
+#[allow(clippy::unused_async, clippy::needless_pass_by_value)]
+pub async fn cancel(path: Path<String>, bearer: BearerAuth, request: HttpRequest, repo: Data<dyn ArticleStore>, audit: Data<AuditLog>) -> impl Responder {
+    let article_id = ArticleId::new(path.into_inner());
+
+    let res: ComposeInternalError<SchedulePublicationResult> = (|| {
+        let (principal, article) = match authorize_modification(&request, &**repo, &article_id, bearer.token(), TokenScope::ArticleWrite, SchedulePublicationError::NoSuchArticleFoundById)? {
+            Ok(x) => x,
+            Err(e) => return Ok(Err(e)),
+        };
+
+        if article.publish_at.is_none() {
+            return Ok(Err(SchedulePublicationError::NotScheduled))
+        }
+
+        repo.schedule_publication(&article_id, None).map_err(UnhandledError::new)?;
+        audit.record(&request, &principal, AuditEvent {
+            old_content: Some(&article.content),
+            new_content: Some(&article.content),
+            ..AuditEvent::new(AuditAction::SchedulePublication, &article_id)
+        });
+
+        Ok(Ok(()))
+    })();
+
+    EndpointRepresentationCompiler::from_value(res).negotiate(&request)
+}
+
+#[get("")]
+#[allow(clippy::unused_async, clippy::needless_pass_by_value)]
+pub async fn list(bearer: BearerAuth, request: HttpRequest, repo: Data<dyn ArticleStore>) -> impl Responder {
+    let res: ComposeInternalError<ListScheduledPublicationsResult> = (|| {
+        let principal = match authorize(&request, bearer.token(), TokenScope::ArticleWrite) {
+            Ok(principal) => principal,
+            Err(e) => return Ok(Err(e.into())),
+        };
+
+        // 著者には自分の記事だけを見せる
+        let mut entries = repo.entries().map_err(UnhandledError::new)?
+            .into_iter()
+            .filter(|(_, article)| principal.can_modify(article))
+            .filter_map(|(id, article)| article.publish_at.map(|publish_at| ScheduledPublication { id, publish_at }))
+            .collect::<Vec<_>>();
+        entries.sort_by(|a, b| a.publish_at.cmp(&b.publish_at).then_with(|| a.id.0.cmp(&b.id.0)));
+
+        Ok(Ok(entries))
+    })();
+
+    EndpointRepresentationCompiler::from_value(res).negotiate(&request)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use actix_web::App;
+    use actix_web::http::header::{HeaderName, ACCEPT, AUTHORIZATION, VARY};
+    use actix_web::http::StatusCode;
+    use actix_web::test::{call_service, init_service, read_body, TestRequest};
+    use actix_web::web::{self, Data};
+    use chrono::{DateTime, Local, TimeDelta};
+    use serde_json::json;
+    use toy_blog_endpoint_model::{ArticleId, Visibility};
+    use crate::service::persistence::{ArticleStore, InMemoryArticleStore};
+    use crate::service::rest::audit::AuditLog;
+    use crate::service::rest::auth::install_test_tokens;
+    use super::{list, update};
+
+    fn bearer(token: &str) -> (HeaderName, String) {
+        (AUTHORIZATION, format!("Bearer {token}"))
+    }
+
+    fn schedule(id: &str, publish_at: DateTime<Local>) -> TestRequest {
+        TestRequest::put()
+            .uri(&format!("/api/article/{id}/schedule"))
+            .insert_header(bearer("admin"))
+            .set_json(json!({ "publish_at": publish_at.to_rfc3339() }))
+    }
+
+    #[actix_web::test]
+    async fn only_private_article_can_be_scheduled_in_future() {
+        let repo: Arc<dyn ArticleStore> = Arc::new(InMemoryArticleStore::new());
+        repo.create_entry(&ArticleId::new("public".to_string()), "public".to_string(), Visibility::Public, None).unwrap();
+        repo.create_entry(&ArticleId::new("private".to_string()), "private".to_string(), Visibility::Private, None).unwrap();
+        let audit_log = tempfile::NamedTempFile::new().unwrap();
+        install_test_tokens();
+        let app = init_service(
+            App::new()
+                .service(web::scope("/api/article").service(update))
+                .service(web::scope("/api/schedule").service(list))
+                .app_data(Data::from(repo.clone()))
+                .app_data(Data::new(AuditLog::open(audit_log.path(), false).unwrap()))
+        ).await;
+        let tomorrow = Local::now() + TimeDelta::days(1);
+
+        let res = call_service(&app, schedule("public", tomorrow).to_request()).await;
+        assert_eq!(res.status(), StatusCode::CONFLICT);
+
+        let res = call_service(&app, schedule("private", Local::now() - TimeDelta::minutes(1)).to_request()).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        assert!(repo.read_snapshot(&ArticleId::new("private".to_string())).unwrap().publish_at.is_none());
+
+        let res = call_service(&app, schedule("private", tomorrow).to_request()).await;
+        assert_eq!(res.status(), StatusCode::NO_CONTENT);
+        assert!(repo.read_snapshot(&ArticleId::new("private".to_string())).unwrap().publish_at.is_some());
+    }
+
+    #[actix_web::test]
+    async fn author_sees_only_own_schedules() {
+        let repo: Arc<dyn ArticleStore> = Arc::new(InMemoryArticleStore::new());
+        let tomorrow = Local::now() + TimeDelta::days(1);
+        for author in ["alice", "bob"] {
+            let id = ArticleId::new(author.to_string());
+            repo.create_entry(&id, author.to_string(), Visibility::Private, Some(author)).unwrap();
+            repo.schedule_publication(&id, Some(tomorrow)).unwrap();
+        }
+        let audit_log = tempfile::NamedTempFile::new().unwrap();
+        install_test_tokens();
+        let app = init_service(
+            App::new()
+                .service(web::scope("/api/article").service(update))
+                .service(web::scope("/api/schedule").service(list))
+                .app_data(Data::from(repo.clone()))
+                .app_data(Data::new(AuditLog::open(audit_log.path(), false).unwrap()))
+        ).await;
+
+        let listed = |token| TestRequest::get().uri("/api/schedule").insert_header(bearer(token));
+
+        let res = call_service(&app, listed("alice").insert_header((ACCEPT, "application/json")).to_request()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers().get(VARY).unwrap(), "Accept");
+        let body: serde_json::Value = serde_json::from_slice(&read_body(res).await).unwrap();
+        assert_eq!(body.as_array().unwrap().iter().map(|x| x["id"].as_str().unwrap()).collect::<Vec<_>>(), ["alice"]);
+
+        let res = call_service(&app, listed("admin").to_request()).await;
+        let body = read_body(res).await;
+        let ids = std::str::from_utf8(&body).unwrap().lines().map(|x| x.split(' ').next().unwrap()).collect::<Vec<_>>();
+        assert_eq!(ids, ["alice", "bob"]);
+    }
+}
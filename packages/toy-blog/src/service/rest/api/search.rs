@@ -0,0 +1,30 @@
+use std::future::{Future, ready};
+use actix_web::{get, HttpResponse, Responder};
+use actix_web::web::{Data, Query};
+use toy_blog_endpoint_model::SearchArticlesQuery;
+use crate::service::persistence::SearchIndexedStore;
+use crate::service::rest::exposed_representation_format::{EndpointRepresentationCompiler, SearchHitsRepr};
+use crate::service::rest::inner_no_leak::UnhandledError;
+
+const DEFAULT_SEARCH_LIMIT: usize = 20;
+
+#[get("")]
+#[allow(clippy::needless_pass_by_value)]
+pub fn search(query: Query<SearchArticlesQuery>, index: Data<SearchIndexedStore>) -> impl Future<Output = impl Responder> {
+    if query.q.trim().is_empty() {
+        return ready(HttpResponse::BadRequest().body("query must not be empty"))
+    }
+
+    let limit = query.limit.and_then(|x| usize::try_from(x.get()).ok()).unwrap_or(DEFAULT_SEARCH_LIMIT);
+    let v = match index.search(&query.q, limit) {
+        Ok(hits) => EndpointRepresentationCompiler::from_value(SearchHitsRepr(hits))
+            .into_json()
+            .map_body(|_, y| serde_json::to_string(&y).expect(""))
+            .map_into_boxed_body(),
+        Err(e) => EndpointRepresentationCompiler::from_value(UnhandledError::new(e))
+            .into_plain_text()
+            .map_into_boxed_body(),
+    };
+
+    ready(v)
+}
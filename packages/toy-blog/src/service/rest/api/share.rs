@@ -0,0 +1,135 @@
+use actix_web::{delete, get, post, HttpRequest, Responder};
+use actix_web::web::{Data, Path, Query};
+use actix_web_httpauth::extractors::bearer::BearerAuth;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use chrono::{DateTime, Local, TimeDelta};
+use rand::RngCore;
+use rand::rngs::OsRng;
+use subtle::ConstantTimeEq;
+use toy_blog_endpoint_model::{ArticleId, ArticleShareLink, CreatedShareLink, CreateShareLinkQuery, CreateShareLinkResult, ListShareLinksResult, RevokeShareLinkResult, ShareLinkError, ShareLinkId};
+use crate::service::hash::digest_of;
+use crate::service::hash_token::generate_token;
+use crate::service::persistence::{ArticleStore, PersistenceError};
+use crate::service::rest::api::article::authorize_modification;
+use crate::service::rest::auth::TokenScope;
+use crate::service::rest::ComposeInternalError;
+use crate::service::rest::exposed_representation_format::EndpointRepresentationCompiler;
+use crate::service::rest::inner_no_leak::UnhandledError;
+
+/// 共有リンクのIDのバイト数。IDは秘密ではないので短くてよい
+const SHARE_LINK_ID_BYTES: usize = 6;
+
+/// `token`が`links`のいずれかと一致し、`at`の時点で期限が切れていないかどうか。
+fn matches_any(links: &[ArticleShareLink], token: &str, at: DateTime<Local>) -> bool {
+    let digest = digest_of(token);
+
+    links.iter()
+        .filter(|link| link.expires_at.is_none_or(|expires_at| at < expires_at))
+        .any(|link| bool::from(link.digest.as_bytes().ct_eq(digest.as_bytes())))
+}
+
+/// `token`が記事の有効な共有リンクかどうか。
+pub(super) fn is_shared_with(repo: &dyn ArticleStore, article_id: &ArticleId, token: &str) -> Result<bool, PersistenceError> {
+    Ok(matches_any(&repo.share_links(article_id)?, token, Local::now()))
+}
+
+#[post("/{article_id}/share-links")]
+#[allow(clippy::unused_async, clippy::needless_pass_by_value)]
+pub async fn create(path: Path<String>, query: Query<CreateShareLinkQuery>, bearer: BearerAuth, request: HttpRequest, repo: Data<dyn ArticleStore>) -> impl Responder {
+    let article_id = ArticleId::new(path.into_inner());
+
+    let res: ComposeInternalError<CreateShareLinkResult> = (|| {
+        if let Err(e) = authorize_modification(&request, &**repo, &article_id, bearer.token(), TokenScope::ArticleShare, ShareLinkError::NoSuchArticleFoundById)? {
+            return Ok(Err(e))
+        }
+
+        let mut id = [0; SHARE_LINK_ID_BYTES];
+        OsRng.fill_bytes(&mut id);
+        let id = ShareLinkId(URL_SAFE_NO_PAD.encode(id));
+        let token = generate_token();
+        let created_at = Local::now();
+        let expires_at = query.expires_in_hours.map(|hours| created_at + TimeDelta::hours(hours.into()));
+
+        let link = ArticleShareLink {
+            id: id.clone(),
+            digest: digest_of(&token),
+            created_at,
+            expires_at,
+        };
+        match repo.add_share_link(&article_id, link) {
+            Ok(()) => Ok(Ok(CreatedShareLink { id, token, expires_at })),
+            Err(PersistenceError::AbsentValue) => Ok(Err(ShareLinkError::NoSuchArticleFoundById)),
+            Err(e) => Err(UnhandledError::new(e)),
+        }
+    })();
+
+    EndpointRepresentationCompiler::from_value(res).negotiate(&request)
+}
+
+#[get("/{article_id}/share-links")]
+#[allow(clippy::unused_async, clippy::needless_pass_by_value)]
+pub async fn list(path: Path<String>, bearer: BearerAuth, request: HttpRequest, repo: Data<dyn ArticleStore>) -> impl Responder {
+    let article_id = ArticleId::new(path.into_inner());
+
+    let res: ComposeInternalError<ListShareLinksResult> = (|| {
+        if let Err(e) = authorize_modification(&request, &**repo, &article_id, bearer.token(), TokenScope::ArticleShare, ShareLinkError::NoSuchArticleFoundById)? {
+            return Ok(Err(e))
+        }
+
+        match repo.share_links(&article_id) {
+            Ok(links) => Ok(Ok(links)),
+            Err(PersistenceError::AbsentValue) => Ok(Err(ShareLinkError::NoSuchArticleFoundById)),
+            Err(e) => Err(UnhandledError::new(e)),
+        }
+    })();
+
+    EndpointRepresentationCompiler::from_value(res).negotiate(&request)
+}
+
+#[delete("/{article_id}/share-links/{link_id}")]
+#[allow(clippy::unused_async, clippy::needless_pass_by_value)]
+pub async fn revoke(path: Path<(String, String)>, bearer: BearerAuth, request: HttpRequest, repo: Data<dyn ArticleStore>) -> impl Responder {
+    let (article_id, link_id) = path.into_inner();
+    let article_id = ArticleId::new(article_id);
+    let link_id = ShareLinkId(link_id);
+
+    let res: ComposeInternalError<RevokeShareLinkResult> = (|| {
+        if let Err(e) = authorize_modification(&request, &**repo, &article_id, bearer.token(), TokenScope::ArticleShare, ShareLinkError::NoSuchArticleFoundById)? {
+            return Ok(Err(e))
+        }
+
+        match repo.revoke_share_link(&article_id, &link_id) {
+            Ok(()) => Ok(Ok(())),
+            Err(PersistenceError::AbsentValue) => Ok(Err(ShareLinkError::NoSuchShareLinkFound)),
+            Err(e) => Err(UnhandledError::new(e)),
+        }
+    })();
+
+    EndpointRepresentationCompiler::from_value(res).negotiate(&request)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Local, TimeDelta};
+    use toy_blog_endpoint_model::{ArticleShareLink, ShareLinkId};
+    use crate::service::hash::digest_of;
+    use super::matches_any;
+
+    #[test]
+    fn expired_or_unknown_token_does_not_match() {
+        let now = Local::now();
+        let link = |token: &str, expires_at| ArticleShareLink {
+            id: ShareLinkId(token.to_string()),
+            digest: digest_of(token),
+            created_at: now,
+            expires_at,
+        };
+        let links = [link("forever", None), link("expired", Some(now))];
+
+        assert!(matches_any(&links, "forever", now));
+        assert!(!matches_any(&links, "expired", now));
+        assert!(matches_any(&links, "expired", now - TimeDelta::seconds(1)));
+        assert!(!matches_any(&links, "unknown", now));
+    }
+}
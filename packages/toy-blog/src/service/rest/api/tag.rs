@@ -0,0 +1,144 @@
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::future::{Future, ready};
+use actix_web::{get, put, HttpRequest, Responder};
+use actix_web::web::{Data, Json, Path};
+use actix_web_httpauth::extractors::bearer::BearerAuth;
+use toy_blog_endpoint_model::{Article, ArticleId, AuditAction, TagCount, UpdateTagsError, UpdateTagsPayload, UpdateTagsResult, Visibility};
+use crate::service::persistence::ArticleStore;
+use crate::service::rest::api::article::authorize_modification;
+use crate::service::rest::audit::{AuditEvent, AuditLog};
+use crate::service::rest::auth::TokenScope;
+use crate::service::rest::exposed_representation_format::{EndpointRepresentationCompiler, TagCountsRepr};
+use crate::service::rest::inner_no_leak::{ComposeInternalError, UnhandledError};
+
+/// 1つの記事に付けられるタグの数
+const MAX_TAGS_PER_ARTICLE: usize = 32;
+/// タグの長さの上限 (文字数)
+const MAX_TAG_CHARS: usize = 64;
+
+/// 空白や制御文字を含むタグは、一覧のクエリやURLで扱いにくいので受け付けない。
+fn is_valid_tag(tag: &str) -> bool {
+    !tag.is_empty()
+        && tag.chars().count() <= MAX_TAG_CHARS
+        && !tag.chars().any(|c| c.is_whitespace() || c.is_control())
+}
+
+fn validate_tags(payload: &UpdateTagsPayload) -> Result<(), UpdateTagsError> {
+    if payload.tags.len() > MAX_TAGS_PER_ARTICLE {
+        return Err(UpdateTagsError::TooManyTags { max: MAX_TAGS_PER_ARTICLE })
+    }
+
+    payload.tags.iter()
+        .find(|tag| !is_valid_tag(tag))
+        .map_or(Ok(()), |invalid| Err(UpdateTagsError::InvalidTag(invalid.clone())))
+}
+
+#[put("/{article_id}/tags")]
+#[allow(clippy::future_not_send)]
+pub async fn update(path: Path<String>, payload: Json<UpdateTagsPayload>, bearer: BearerAuth, request: HttpRequest, repo: Data<dyn ArticleStore>, audit: Data<AuditLog>) -> impl Responder {
+    let res = || async {
+        let article_id = ArticleId::new(path.into_inner());
+
+        let (principal, article) = match authorize_modification(&request, &**repo, &article_id, bearer.token(), TokenScope::ArticleWrite, UpdateTagsError::NoSuchArticleFoundById)? {
+            Ok(x) => x,
+            Err(e) => return Ok(Err(e)),
+        };
+
+        if let Err(e) = validate_tags(&payload) {
+            return Ok(Err(e))
+        }
+
+        let UpdateTagsPayload { tags } = payload.into_inner();
+        repo.change_tags(&article_id, tags.clone()).map_err(UnhandledError::new)?;
+        audit.record(&request, &principal, AuditEvent {
+            old_content: Some(&article.content),
+            new_content: Some(&article.content),
+            tags: Some(&tags),
+            ..AuditEvent::new(AuditAction::ChangeTags, &article_id)
+        });
+
+        Ok(Ok(()))
+    };
+
+    let res: ComposeInternalError<UpdateTagsResult> = res().await;
+    EndpointRepresentationCompiler::from_value(res).negotiate(&request)
+}
+
+/// 公開されている記事に付けられたタグを、記事の多い順に数える。同じ数のタグは名前の順に並べる。
+fn count_public_tags(entries: &[(ArticleId, Article)]) -> Vec<TagCount> {
+    let mut counts = BTreeMap::<&str, usize>::new();
+    for tag in entries.iter().filter(|(_, a)| a.visibility == Visibility::Public).flat_map(|(_, a)| &a.tags) {
+        *counts.entry(tag).or_default() += 1;
+    }
+
+    let mut counts = counts.into_iter()
+        .map(|(tag, count)| TagCount { tag: tag.to_string(), count })
+        .collect::<Vec<_>>();
+    // 安定ソートなので、同じ数のタグは名前の順のまま残る
+    counts.sort_by_key(|x| Reverse(x.count));
+
+    counts
+}
+
+#[get("")]
+#[allow(clippy::needless_pass_by_value)]
+pub fn list(repo: Data<dyn ArticleStore>) -> impl Future<Output = impl Responder> {
+    let v = match repo.entries() {
+        Ok(entries) => EndpointRepresentationCompiler::from_value(TagCountsRepr(count_public_tags(&entries)))
+            .into_json()
+            .map_body(|_, y| serde_json::to_string(&y).expect(""))
+            .map_into_boxed_body(),
+        Err(e) => EndpointRepresentationCompiler::from_value(UnhandledError::new(e))
+            .into_plain_text()
+            .map_into_boxed_body(),
+    };
+
+    ready(v)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+    use toy_blog_endpoint_model::{ArticleId, TagCount, UpdateTagsError, UpdateTagsPayload, Visibility};
+    use crate::service::persistence::{ArticleStore, InMemoryArticleStore};
+    use super::{count_public_tags, validate_tags, MAX_TAGS_PER_ARTICLE};
+
+    fn tags(tags: &[&str]) -> BTreeSet<String> {
+        tags.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn only_public_articles_are_counted() {
+        let store = InMemoryArticleStore::new();
+        for (id, visibility, tag_set) in [
+            ("a", Visibility::Public, tags(&["rust", "日記"])),
+            ("b", Visibility::Public, tags(&["rust"])),
+            ("c", Visibility::Private, tags(&["rust", "secret"])),
+            ("d", Visibility::Restricted, tags(&["secret"])),
+        ] {
+            let id = ArticleId::new(id.to_string());
+            store.create_entry(&id, String::new(), visibility, None).unwrap();
+            store.change_tags(&id, tag_set).unwrap();
+        }
+
+        assert_eq!(count_public_tags(&store.entries().unwrap()), [
+            TagCount { tag: "rust".to_string(), count: 2 },
+            TagCount { tag: "日記".to_string(), count: 1 },
+        ]);
+    }
+
+    #[test]
+    fn malformed_tags_are_rejected() {
+        let payload = |t: &[&str]| UpdateTagsPayload { tags: tags(t) };
+
+        assert!(validate_tags(&payload(&["rust", "日記"])).is_ok());
+        assert!(validate_tags(&payload(&[])).is_ok());
+        assert!(matches!(validate_tags(&payload(&[""])), Err(UpdateTagsError::InvalidTag(_))));
+        assert!(matches!(validate_tags(&payload(&["two words"])), Err(UpdateTagsError::InvalidTag(_))));
+        assert!(matches!(validate_tags(&payload(&["a".repeat(65).as_str()])), Err(UpdateTagsError::InvalidTag(_))));
+
+        let many = (0..=MAX_TAGS_PER_ARTICLE).map(|x| x.to_string()).collect();
+        assert!(matches!(validate_tags(&UpdateTagsPayload { tags: many }), Err(UpdateTagsError::TooManyTags { .. })));
+    }
+}
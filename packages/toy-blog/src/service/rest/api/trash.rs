@@ -0,0 +1,155 @@
+use actix_web::{delete, get, post, HttpRequest, Responder};
+use actix_web::web::{Data, Path};
+use actix_web_httpauth::extractors::bearer::BearerAuth;
+use toy_blog_endpoint_model::{ArticleId, AuditAction, ListTrashResult, PurgeArticleError, PurgeArticleResult, RestoreArticleError, RestoreArticleResult, TrashedArticle, TrashedArticleSummary};
+use crate::service::persistence::{ArticleStore, PersistenceError};
+use crate::service::rest::audit::{AuditEvent, AuditLog};
+use crate::service::rest::auth::{authorize, AuthorizationError, Principal, TokenScope};
+use crate::service::rest::ComposeInternalError;
+use crate::service::rest::exposed_representation_format::EndpointRepresentationCompiler;
+use crate::service::rest::inner_no_leak::UnhandledError;
+
+fn find_trashed(repo: &dyn ArticleStore, article_id: &ArticleId) -> Result<Option<TrashedArticle>, PersistenceError> {
+    Ok(repo.trashed_entries()?
+        .into_iter()
+        .find(|(id, _)| id == article_id)
+        .map(|(_, trashed)| trashed))
+}
+
+/// ゴミ箱にある記事を変更できるかどうか。ゴミ箱に無い場合は、後の処理で見つからないものとして扱うために`true`を返す。
+fn can_modify_trashed(principal: &Principal, trashed: Option<&TrashedArticle>) -> bool {
+    trashed.is_none_or(|trashed| principal.can_modify(&trashed.article))
+}
+
+#[get("")]
+#[allow(clippy::unused_async, clippy::needless_pass_by_value)]
+pub async fn list(bearer: BearerAuth, request: HttpRequest, repo: Data<dyn ArticleStore>) -> impl Responder {
+    let res: ComposeInternalError<ListTrashResult> = (|| {
+        let principal = match authorize(&request, bearer.token(), TokenScope::ArticleDelete) {
+            Ok(principal) => principal,
+            Err(e) => return Ok(Err(e.into())),
+        };
+
+        // 著者には自分の記事だけを見せる
+        let mut entries = repo.trashed_entries()
+            .map_err(UnhandledError::new)?
+            .into_iter()
+            .filter(|(_, trashed)| principal.can_modify(&trashed.article))
+            .map(|(id, trashed)| TrashedArticleSummary {
+                id,
+                deleted_at: trashed.deleted_at,
+            })
+            .collect::<Vec<_>>();
+        entries.sort_by(|a, b| b.deleted_at.cmp(&a.deleted_at).then_with(|| a.id.0.cmp(&b.id.0)));
+
+        Ok(Ok(entries))
+    })();
+
+    EndpointRepresentationCompiler::from_value(res).negotiate(&request)
+}
+
+#[post("/{article_id}/restore")]
+#[allow(clippy::unused_async, clippy::needless_pass_by_value)]
+pub async fn restore(path: Path<String>, bearer: BearerAuth, request: HttpRequest, repo: Data<dyn ArticleStore>, audit: Data<AuditLog>) -> impl Responder {
+    let article_id = ArticleId::new(path.into_inner());
+
+    let res: ComposeInternalError<RestoreArticleResult> = (|| {
+        let principal = match authorize(&request, bearer.token(), TokenScope::ArticleDelete) {
+            Ok(principal) => principal,
+            Err(e) => return Ok(Err(e.into())),
+        };
+        let trashed = find_trashed(&**repo, &article_id).map_err(UnhandledError::new)?;
+        if !can_modify_trashed(&principal, trashed.as_ref()) {
+            return Ok(Err(AuthorizationError::NotOwner.into()))
+        }
+
+        match repo.restore(&article_id) {
+            Ok(()) => {
+                audit.record(&request, &principal, AuditEvent {
+                    new_content: trashed.as_ref().map(|x| x.article.content.as_str()),
+                    ..AuditEvent::new(AuditAction::Restore, &article_id)
+                });
+                Ok(Ok(()))
+            }
+            Err(PersistenceError::AbsentValue) => Ok(Err(RestoreArticleError::NoSuchArticleFoundInTrash)),
+            Err(PersistenceError::DuplicatedId) => Ok(Err(RestoreArticleError::DuplicatedArticleId)),
+            Err(e) => Err(UnhandledError::new(e)),
+        }
+    })();
+
+    EndpointRepresentationCompiler::from_value(res).negotiate(&request)
+}
+
+#[delete("/{article_id}")]
+#[allow(clippy::unused_async, clippy::needless_pass_by_value)]
+pub async fn purge(path: Path<String>, bearer: BearerAuth, request: HttpRequest, repo: Data<dyn ArticleStore>, audit: Data<AuditLog>) -> impl Responder {
+    let article_id = ArticleId::new(path.into_inner());
+
+    let res: ComposeInternalError<PurgeArticleResult> = (|| {
+        let principal = match authorize(&request, bearer.token(), TokenScope::ArticleDelete) {
+            Ok(principal) => principal,
+            Err(e) => return Ok(Err(e.into())),
+        };
+        let trashed = find_trashed(&**repo, &article_id).map_err(UnhandledError::new)?;
+        if !can_modify_trashed(&principal, trashed.as_ref()) {
+            return Ok(Err(AuthorizationError::NotOwner.into()))
+        }
+
+        match repo.purge(&article_id) {
+            Ok(()) => {
+                audit.record(&request, &principal, AuditEvent {
+                    old_content: trashed.as_ref().map(|x| x.article.content.as_str()),
+                    ..AuditEvent::new(AuditAction::Purge, &article_id)
+                });
+                Ok(Ok(()))
+            }
+            Err(PersistenceError::AbsentValue) => Ok(Err(PurgeArticleError::NoSuchArticleFoundInTrash)),
+            Err(e) => Err(UnhandledError::new(e)),
+        }
+    })();
+
+    EndpointRepresentationCompiler::from_value(res).negotiate(&request)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use actix_web::App;
+    use actix_web::http::header::AUTHORIZATION;
+    use actix_web::http::StatusCode;
+    use actix_web::test::{call_service, init_service, TestRequest};
+    use actix_web::web::{self, Data};
+    use toy_blog_endpoint_model::{ArticleId, Visibility};
+    use crate::service::persistence::{ArticleStore, SqliteArticleStore};
+    use crate::service::rest::audit::AuditLog;
+    use crate::service::rest::auth::install_test_tokens;
+    use super::{list, purge, restore};
+
+    #[actix_web::test]
+    async fn unreadable_trash_is_internal_error() {
+        let db = tempfile::NamedTempFile::new().unwrap();
+        let repo: Arc<dyn ArticleStore> = Arc::new(SqliteArticleStore::open(db.path()).unwrap());
+        let id = ArticleId::new("owned".to_string());
+        repo.create_entry(&id, "content".to_string(), Visibility::Private, Some("bob")).unwrap();
+        repo.remove_if(&id, &|_| true).unwrap();
+        rusqlite::Connection::open(db.path()).unwrap().execute_batch("DROP TABLE article_trash_revision; DROP TABLE article_trash;").unwrap();
+
+        let audit_log = tempfile::NamedTempFile::new().unwrap();
+        install_test_tokens();
+        let app = init_service(
+            App::new()
+                .service(web::scope("/api/trash").service(list).service(restore).service(purge))
+                .app_data(Data::from(repo))
+                .app_data(Data::new(AuditLog::open(audit_log.path(), false).unwrap()))
+        ).await;
+
+        let res = call_service(&app, TestRequest::get().uri("/api/trash").insert_header((AUTHORIZATION, "Bearer admin")).to_request()).await;
+        assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        // 読めなかったことを所有権の確認を通ったものとして扱ってはいけない
+        let res = call_service(&app, TestRequest::post().uri("/api/trash/owned/restore").insert_header((AUTHORIZATION, "Bearer alice")).to_request()).await;
+        assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        let res = call_service(&app, TestRequest::delete().uri("/api/trash/owned").insert_header((AUTHORIZATION, "Bearer alice")).to_request()).await;
+        assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+}
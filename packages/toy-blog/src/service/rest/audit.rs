@@ -0,0 +1,197 @@
+use std::collections::{BTreeSet, VecDeque};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use actix_web::HttpRequest;
+use chrono::{DateTime, FixedOffset, Local};
+use log::{error, warn};
+use toy_blog_endpoint_model::{ArticleId, ArticleMetadata, AuditAction, AuditEntry, Visibility};
+use crate::service::hash::digest_of;
+use crate::service::rest::auth::Principal;
+use crate::service::rest::extract_real_ip;
+
+/// 監査ログに記録する操作の内容。本文はダイジェストだけが記録される。
+#[derive(Copy, Clone)]
+pub struct AuditEvent<'a> {
+    pub action: AuditAction,
+    pub article_id: &'a ArticleId,
+    pub renamed_to: Option<&'a ArticleId>,
+    pub old_content: Option<&'a str>,
+    pub new_content: Option<&'a str>,
+    pub visibility: Option<Visibility>,
+    pub tags: Option<&'a BTreeSet<String>>,
+    pub metadata: Option<&'a ArticleMetadata>,
+    pub publish_at: Option<DateTime<Local>>,
+}
+
+impl<'a> AuditEvent<'a> {
+    pub const fn new(action: AuditAction, article_id: &'a ArticleId) -> Self {
+        Self {
+            action,
+            article_id,
+            renamed_to: None,
+            old_content: None,
+            new_content: None,
+            visibility: None,
+            tags: None,
+            metadata: None,
+            publish_at: None,
+        }
+    }
+}
+
+/// 記事への書き込みを1行ずつJSONで追記していくログ。書き込んだ行は変更しない。
+pub struct AuditLog {
+    path: PathBuf,
+    file: Mutex<File>,
+    proxied_by_cloudflare: bool,
+}
+
+impl AuditLog {
+    pub fn open(path: impl AsRef<Path>, proxied_by_cloudflare: bool) -> Result<Self, std::io::Error> {
+        let path = path.as_ref().to_path_buf();
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+
+        Ok(Self {
+            path,
+            file: Mutex::new(file),
+            proxied_by_cloudflare,
+        })
+    }
+
+    /// `principal`が`request`で行った操作を記録する。
+    ///
+    /// 操作は既に済んでいるので、記録に失敗してもリクエストは失敗させずにログへ出力するだけにする。
+    pub fn record(&self, request: &HttpRequest, principal: &Principal, event: AuditEvent<'_>) {
+        self.write_entry(&principal.name, extract_real_ip(request, self.proxied_by_cloudflare), event);
+    }
+
+    /// リクエストを伴わずにサーバー自身が行った操作を記録する。`actor`はトークンの名前の代わりに記録される。
+    pub fn record_unattended(&self, actor: &str, event: AuditEvent<'_>) {
+        self.write_entry(actor, None, event);
+    }
+
+    fn write_entry(&self, token: &str, client_ip: Option<IpAddr>, event: AuditEvent<'_>) {
+        let entry = AuditEntry {
+            at: Local::now(),
+            action: event.action,
+            article_id: event.article_id.clone(),
+            renamed_to: event.renamed_to.cloned(),
+            old_content_hash: event.old_content.map(digest_of),
+            new_content_hash: event.new_content.map(digest_of),
+            visibility: event.visibility,
+            tags: event.tags.cloned(),
+            metadata: event.metadata.cloned(),
+            publish_at: event.publish_at,
+            token: token.to_string(),
+            client_ip,
+        };
+
+        if let Err(e) = self.append(&entry) {
+            error!("failed to write audit log: {e}: {entry:?}");
+        }
+    }
+
+    fn append(&self, entry: &AuditEntry) -> Result<(), std::io::Error> {
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+
+        // 1行を1回で書き込み、他の書き込みと混ざらないようにする
+        let mut file = self.file.lock().expect("audit log is poisoned");
+        file.write_all(&line)?;
+        file.flush()
+    }
+
+    /// `since`以降、`until`より前の操作のうち新しいものを`limit`件まで、古い順に返す。読めない行は読み飛ばす。
+    ///
+    /// ファイルは先頭から1行ずつ読み、直近の`limit`件だけを手元に残す。
+    pub fn read(&self, since: Option<DateTime<FixedOffset>>, until: Option<DateTime<FixedOffset>>, limit: usize) -> Result<Vec<AuditEntry>, std::io::Error> {
+        if limit == 0 {
+            return Ok(vec![])
+        }
+
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e),
+        };
+
+        let mut entries = VecDeque::new();
+        for (index, line) in BufReader::new(file).lines().enumerate() {
+            let line = line?;
+            if line.is_empty() {
+                continue
+            }
+
+            match serde_json::from_str::<AuditEntry>(&line) {
+                Ok(entry) if is_within(&entry, since, until) => {
+                    if entries.len() >= limit {
+                        entries.pop_front();
+                    }
+                    entries.push_back(entry);
+                }
+                Ok(_) => {}
+                Err(e) => warn!("skipping malformed line {} of {}: {e}", index + 1, self.path.display()),
+            }
+        }
+
+        Ok(entries.into())
+    }
+}
+
+fn is_within(entry: &AuditEntry, since: Option<DateTime<FixedOffset>>, until: Option<DateTime<FixedOffset>>) -> bool {
+    since.is_none_or(|since| since <= entry.at) && until.is_none_or(|until| entry.at < until)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::OpenOptions;
+    use std::io::Write;
+    use actix_web::test::TestRequest;
+    use chrono::{Local, TimeDelta};
+    use toy_blog_endpoint_model::{ArticleId, AuditAction};
+    use crate::service::rest::auth::{Principal, Role};
+    use super::{AuditEvent, AuditLog};
+
+    #[test]
+    fn recorded_entries_are_filtered_by_time() {
+        let file = tempfile::NamedTempFile::new().expect("failed to initialize temporary file");
+        let log = AuditLog::open(file.path(), false).unwrap();
+        let request = TestRequest::default().peer_addr("192.0.2.1:1234".parse().unwrap()).to_http_request();
+        let principal = Principal { name: "alice".to_string(), role: Role::Author };
+        let id = ArticleId::new("a".to_string());
+
+        let before = Local::now();
+        log.record(&request, &principal, AuditEvent {
+            new_content: Some("secret"),
+            ..AuditEvent::new(AuditAction::Create, &id)
+        });
+        OpenOptions::new().append(true).open(file.path()).unwrap().write_all(b"not json\n").unwrap();
+        let between = Local::now();
+        log.record(&request, &principal, AuditEvent::new(AuditAction::Delete, &id));
+
+        let all = log.read(None, None, usize::MAX).unwrap();
+        assert_eq!(all.len(), 2);
+        assert_eq!(all[0].action, AuditAction::Create);
+        assert_eq!(all[0].old_content_hash, None);
+        assert_eq!(all[0].new_content_hash.as_deref(), Some("2bb80d537b1da3e38bd30361aa855686bde0eacd7162fef6a25fe97bf527a25b"));
+        assert_eq!(all[0].token, "alice");
+        assert_eq!(all[0].client_ip, Some("192.0.2.1".parse().unwrap()));
+
+        let later = log.read(Some(between.fixed_offset()), None, usize::MAX).unwrap();
+        assert_eq!(later.len(), 1);
+        assert_eq!(later[0].action, AuditAction::Delete);
+
+        let earlier = log.read(Some(before.fixed_offset()), Some(between.fixed_offset()), usize::MAX).unwrap();
+        assert_eq!(earlier.len(), 1);
+        assert_eq!(earlier[0].action, AuditAction::Create);
+
+        let last = log.read(None, None, 1).unwrap();
+        assert_eq!(last.len(), 1);
+        assert_eq!(last[0].action, AuditAction::Delete);
+
+        assert!(log.read(Some((Local::now() + TimeDelta::minutes(1)).fixed_offset()), None, usize::MAX).unwrap().is_empty());
+    }
+}
@@ -1,8 +1,558 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use actix_web::{HttpMessage, HttpRequest};
+use actix_web::dev::ServiceResponse;
+use anyhow::{anyhow, Context};
+use chrono::{DateTime, Local};
+use argon2::{Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
+use argon2::password_hash::SaltString;
+use rand::rngs::OsRng;
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
 use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumIter, EnumString, IntoEnumIterator};
+use toy_blog_endpoint_model::{Article, ChangeArticleIdError, CreateArticleError, DeleteArticleError, DraftError, GetAuditLogError, ListScheduledPublicationsError, ListTrashError, PurgeArticleError, RestoreArticleError, RevertArticleError, SchedulePublicationError, ShareLinkError, UpdateArticleError, UpdateArticleMetadataError, UpdateTagsError};
 
-pub(in super) fn is_wrong_token(token: &str) -> bool {
-    let correct_token = WRITE_TOKEN.get().unwrap().as_str();
-    correct_token != token
+/// トークンに許可される操作
+#[derive(Deserialize, Serialize, Display, EnumString, EnumIter, Copy, Clone, Eq, PartialEq, Debug)]
+pub enum TokenScope {
+    /// 非公開の記事とその版を読む
+    #[serde(rename = "article:read-private")]
+    #[strum(serialize = "article:read-private")]
+    ArticleReadPrivate,
+    /// 記事を作成・更新し、公開範囲を変える
+    #[serde(rename = "article:write")]
+    #[strum(serialize = "article:write")]
+    ArticleWrite,
+    /// 限定公開の記事の共有リンクを作成・一覧・取り消す
+    #[serde(rename = "article:share")]
+    #[strum(serialize = "article:share")]
+    ArticleShare,
+    /// 記事をゴミ箱へ移し、ゴミ箱を操作する
+    #[serde(rename = "article:delete")]
+    #[strum(serialize = "article:delete")]
+    ArticleDelete,
+    /// 記事のIDを変える
+    #[serde(rename = "meta:rename")]
+    #[strum(serialize = "meta:rename")]
+    MetaRename,
+    /// 監査ログを読む
+    #[serde(rename = "audit:read")]
+    #[strum(serialize = "audit:read")]
+    AuditRead,
 }
 
-pub static WRITE_TOKEN: OnceCell<String> = OnceCell::new();
+/// アカウントの役割
+#[derive(Deserialize, Serialize, Display, EnumString, Copy, Clone, Eq, PartialEq, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum Role {
+    /// 自分が作成した記事だけを変更できる
+    Author,
+    /// 全ての記事を変更できる。役割が書かれていないトークンは、互換性のために管理者として扱う
+    #[default]
+    Admin,
+}
+
+/// `data/token.json`の要素
+#[derive(Deserialize, Serialize, Debug)]
+pub(in crate::service) struct TokenEntry {
+    /// アカウントの名前。入れ替え中のトークンは同じ名前を持つ
+    pub name: String,
+    /// `hash-token`サブコマンドで作ったArgon2のハッシュ (PHC文字列形式)
+    pub hash: String,
+    pub scopes: Vec<TokenScope>,
+    #[serde(default)]
+    pub role: Role,
+    /// この日時以降は受け付けない。トークンを入れ替える際、古いトークンに設定する
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Local>>,
+}
+
+impl TokenEntry {
+    pub fn is_expired_at(&self, at: DateTime<Local>) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= at)
+    }
+}
+
+/// `path`からトークンの一覧を読み込む。ファイルが無い場合は空になる。
+pub(in crate::service) fn read_token_file(path: &Path) -> Result<Vec<TokenEntry>, anyhow::Error> {
+    if !path.exists() {
+        return Ok(vec![])
+    }
+
+    let file = File::open(path).with_context(|| format!("while opening {path}", path = path.display()))?;
+    serde_json::from_reader(file).with_context(|| format!("while parsing {path}", path = path.display()))
+}
+
+/// トークンの照合に使う値。平文のトークンはメモリ上にも残さない。
+#[derive(Clone, Debug)]
+enum Credential {
+    /// Argon2のハッシュ (PHC文字列形式)
+    Hashed(String),
+    /// 標準入力から与えられたトークンのSHA-256ダイジェスト
+    Digest([u8; 32]),
+}
+
+impl Credential {
+    fn verify(&self, token: &str) -> bool {
+        match self {
+            Self::Hashed(hash) => {
+                // ハッシュは読み込む時に検証済み。比較はargon2が定数時間で行う
+                PasswordHash::new(hash).is_ok_and(|hash| Argon2::default().verify_password(token.as_bytes(), &hash).is_ok())
+            }
+            Self::Digest(digest) => Sha256::digest(token.as_bytes()).as_slice().ct_eq(digest.as_slice()).into(),
+        }
+    }
+}
+
+/// 名前の付いたトークン。名前はリクエストのログに出る。
+#[derive(Clone, Debug)]
+pub struct NamedToken {
+    pub name: String,
+    credential: Credential,
+    pub scopes: Vec<TokenScope>,
+    pub role: Role,
+    pub expires_at: Option<DateTime<Local>>,
+}
+
+impl NamedToken {
+    /// 全ての操作が許可された管理者のトークン
+    pub fn with_all_scopes(name: String, token: &str) -> Self {
+        Self {
+            name,
+            credential: Credential::Digest(Sha256::digest(token.as_bytes()).into()),
+            scopes: TokenScope::iter().collect(),
+            role: Role::Admin,
+            expires_at: None,
+        }
+    }
+}
+
+impl TryFrom<TokenEntry> for NamedToken {
+    type Error = anyhow::Error;
+
+    fn try_from(value: TokenEntry) -> Result<Self, Self::Error> {
+        let TokenEntry { name, hash, scopes, role, expires_at } = value;
+        PasswordHash::new(&hash).map_err(|e| anyhow!("the hash of {name} is malformed: {e}"))?;
+
+        Ok(Self {
+            name,
+            credential: Credential::Hashed(hash),
+            scopes,
+            role,
+            expires_at,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct TokenStore {
+    tokens: Vec<NamedToken>,
+    /// 照合に成功したトークンのSHA-256ダイジェストから、[`Self::tokens`]の添字への対応。
+    ///
+    /// Argon2による照合はリクエストごとに行うには遅いので、一度照合できたトークンは覚えておく。
+    /// 比較されるのはダイジェストなので、照合にかかる時間から元のトークンが漏れることはない。
+    verified: RwLock<HashMap<[u8; 32], usize>>,
+    /// どのトークンとも一致しなかったトークンのSHA-256ダイジェスト。
+    ///
+    /// 一致しないトークンは全てのハッシュと照合することになるので、一致するものより遅い。
+    /// 数が[`MAX_REJECTED_DIGESTS`]に達したら全て忘れる。
+    rejected: RwLock<HashSet<[u8; 32]>>,
+}
+
+/// 覚えておく一致しなかったトークンの数の上限
+const MAX_REJECTED_DIGESTS: usize = 4096;
+
+impl TokenStore {
+    pub fn new(tokens: Vec<NamedToken>) -> Self {
+        Self {
+            tokens,
+            verified: RwLock::new(HashMap::new()),
+            rejected: RwLock::new(HashSet::new()),
+        }
+    }
+
+    /// `path`からトークンを読み込む。ファイルが無い場合は空になる。
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, anyhow::Error> {
+        let tokens = read_token_file(path.as_ref())?.into_iter().map(NamedToken::try_from).collect::<Result<_, _>>()?;
+
+        Ok(Self::new(tokens))
+    }
+
+    pub fn push(&mut self, token: NamedToken) {
+        self.tokens.push(token);
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    /// `token`と一致し、`at`の時点で期限が切れていないトークンを探す。
+    fn find_at(&self, token: &str, at: DateTime<Local>) -> Option<&NamedToken> {
+        let digest: [u8; 32] = Sha256::digest(token.as_bytes()).into();
+        let cached = self.verified.read().expect("verified tokens are poisoned").get(&digest).copied();
+        let index = if let Some(index) = cached {
+            index
+        } else {
+            if self.rejected.read().expect("rejected tokens are poisoned").contains(&digest) {
+                return None
+            }
+
+            let Some(index) = self.tokens.iter().position(|x| x.credential.verify(token)) else {
+                self.remember_rejected(digest);
+                return None
+            };
+            self.verified.write().expect("verified tokens are poisoned").insert(digest, index);
+            index
+        };
+
+        let found = &self.tokens[index];
+        found.expires_at.is_none_or(|expires_at| at < expires_at).then_some(found)
+    }
+
+    fn find(&self, token: &str) -> Option<&NamedToken> {
+        self.find_at(token, Local::now())
+    }
+
+    fn remember_rejected(&self, digest: [u8; 32]) {
+        let mut rejected = self.rejected.write().expect("rejected tokens are poisoned");
+        if rejected.len() >= MAX_REJECTED_DIGESTS {
+            rejected.clear();
+        }
+        rejected.insert(digest);
+    }
+}
+
+/// サーバーを止めずにトークンを差し替えられる[`TokenStore`]。
+///
+/// 差し替える時は[`TokenStore`]ごと作り直すので、照合済みのトークンや一致しなかったトークンの記録も捨てられる。
+#[derive(Debug)]
+pub struct ReloadableTokenStore {
+    path: PathBuf,
+    /// ファイルに書かれていないトークン。読み直した後も残る
+    fixed: Vec<NamedToken>,
+    current: RwLock<Arc<TokenStore>>,
+}
+
+impl ReloadableTokenStore {
+    /// `path`からトークンを読み込み、`fixed`を加える。
+    pub fn load(path: impl Into<PathBuf>, fixed: Vec<NamedToken>) -> Result<Self, anyhow::Error> {
+        let path = path.into();
+        let current = Self::load_with(&path, &fixed)?;
+
+        Ok(Self {
+            path,
+            fixed,
+            current: RwLock::new(Arc::new(current)),
+        })
+    }
+
+    fn load_with(path: &Path, fixed: &[NamedToken]) -> Result<TokenStore, anyhow::Error> {
+        let mut store = TokenStore::load(path)?;
+        for token in fixed {
+            store.push(token.clone());
+        }
+
+        Ok(store)
+    }
+
+    /// ファイルを読み直す。読み込みに失敗した場合は、それまでのトークンを使い続ける。
+    pub fn reload(&self) -> Result<(), anyhow::Error> {
+        let store = Arc::new(Self::load_with(&self.path, &self.fixed)?);
+        *self.current.write().expect("token store is poisoned") = store;
+
+        Ok(())
+    }
+
+    pub fn current(&self) -> Arc<TokenStore> {
+        self.current.read().expect("token store is poisoned").clone()
+    }
+}
+
+fn hash_token_with(argon2: &Argon2, token: &str) -> Result<String, anyhow::Error> {
+    let salt = SaltString::generate(&mut OsRng);
+    let hash = argon2.hash_password(token.as_bytes(), &salt).map_err(|e| anyhow!("failed to hash token: {e}"))?;
+
+    Ok(hash.to_string())
+}
+
+/// `token`をソルト付きでハッシュし、`data/token.json`の`hash`に書ける形式で返す。
+pub(in crate::service) fn hash_token(token: &str) -> Result<String, anyhow::Error> {
+    hash_token_with(&Argon2::default(), token)
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum AuthorizationError {
+    /// どのトークンとも一致しない
+    InvalidToken,
+    /// トークンは正しいが、操作が許可されていない
+    InsufficientScope,
+    /// 他のアカウントが作成した記事を変更しようとした
+    NotOwner,
+}
+
+/// 認証されたアカウント
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Principal {
+    pub name: String,
+    pub role: Role,
+}
+
+impl Principal {
+    /// `article`を変更できるかどうか。
+    pub fn can_modify(&self, article: &Article) -> bool {
+        self.role == Role::Admin || article.created_by.as_deref() == Some(self.name.as_str())
+    }
+
+    /// `article`を変更できなければ[`AuthorizationError::NotOwner`]を返す。
+    pub fn ensure_can_modify(&self, article: &Article) -> Result<(), AuthorizationError> {
+        if self.can_modify(article) {
+            Ok(())
+        } else {
+            Err(AuthorizationError::NotOwner)
+        }
+    }
+}
+
+/// [`authorize`]がトークンを照合した結果。`request`の拡張に記録され、リクエストのログに使われる。
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub(in super) enum TokenLookup {
+    /// トークンと一致した。`name`はそのトークンの名前
+    Matched { name: String },
+    /// どのトークンとも一致しなかった
+    Unmatched,
+}
+
+/// `token`に`scope`が許可されているかどうか。許可されていれば、トークンのアカウントを返す。
+///
+/// 照合の結果は[`TokenLookup`]として`request`に記録する。
+pub(in super) fn authorize(request: &HttpRequest, token: &str, scope: TokenScope) -> Result<Principal, AuthorizationError> {
+    let store = TOKEN_STORE.get().expect("token store is not initialized").current();
+    let found = store.find(token);
+    request.extensions_mut().insert(found.map_or(TokenLookup::Unmatched, |found| TokenLookup::Matched { name: found.name.clone() }));
+    let found = found.ok_or(AuthorizationError::InvalidToken)?;
+
+    if found.scopes.contains(&scope) {
+        Ok(Principal {
+            name: found.name.clone(),
+            role: found.role,
+        })
+    } else {
+        Err(AuthorizationError::InsufficientScope)
+    }
+}
+
+/// リクエストのログに出すトークンの名前。ハンドラーが[`authorize`]で照合したものを使い、照合していないか一致しなかった場合は`-`を返す。
+pub(in super) fn token_name_for_log(res: &ServiceResponse) -> String {
+    match res.request().extensions().get::<TokenLookup>() {
+        Some(TokenLookup::Matched { name }) => name.clone(),
+        _ => "-".to_string(),
+    }
+}
+
+pub static TOKEN_STORE: OnceCell<ReloadableTokenStore> = OnceCell::new();
+
+/// テストで使うトークンを[`TOKEN_STORE`]に登録する。
+///
+/// `admin`は全ての操作が許可された管理者、`alice`と`bob`は記事を書ける著者で、いずれも名前がそのままトークンになる。
+#[cfg(test)]
+pub(in crate::service) fn install_test_tokens() {
+    let author = |name: &str| NamedToken {
+        scopes: vec![TokenScope::ArticleWrite, TokenScope::ArticleDelete],
+        role: Role::Author,
+        ..NamedToken::with_all_scopes(name.to_string(), name)
+    };
+
+    TOKEN_STORE.get_or_init(|| ReloadableTokenStore {
+        path: PathBuf::new(),
+        fixed: vec![],
+        current: RwLock::new(Arc::new(TokenStore::new(vec![
+            NamedToken::with_all_scopes("admin".to_string(), "admin"),
+            author("alice"),
+            author("bob"),
+        ]))),
+    });
+}
+
+macro_rules! from_authorization_error {
+    ($($error:ident::$invalid_token:ident),* $(,)?) => {
+        $(
+            impl From<AuthorizationError> for $error {
+                fn from(value: AuthorizationError) -> Self {
+                    match value {
+                        AuthorizationError::InvalidToken => Self::$invalid_token,
+                        // 他人の記事かどうかは明かしても問題ないので、権限不足として扱う
+                        AuthorizationError::InsufficientScope | AuthorizationError::NotOwner => Self::InsufficientScope,
+                    }
+                }
+            }
+        )*
+    };
+}
+
+from_authorization_error!(
+    CreateArticleError::Unauthorized,
+    UpdateArticleError::InvalidBearerToken,
+    DeleteArticleError::InvalidBearerToken,
+    ChangeArticleIdError::Unauthorized,
+    RevertArticleError::InvalidBearerToken,
+    ListTrashError::InvalidBearerToken,
+    RestoreArticleError::InvalidBearerToken,
+    PurgeArticleError::InvalidBearerToken,
+    ShareLinkError::InvalidBearerToken,
+    GetAuditLogError::InvalidBearerToken,
+    UpdateTagsError::InvalidBearerToken,
+    UpdateArticleMetadataError::InvalidBearerToken,
+    SchedulePublicationError::InvalidBearerToken,
+    ListScheduledPublicationsError::InvalidBearerToken,
+    DraftError::InvalidBearerToken,
+);
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+    use actix_web::{HttpMessage, HttpResponse};
+    use actix_web::dev::ServiceResponse;
+    use actix_web::test::TestRequest;
+    use argon2::{Algorithm, Argon2, Params, Version};
+    use chrono::{Local, TimeDelta};
+    use toy_blog_endpoint_model::{Article, Visibility};
+    use crate::service::rest::auth::{hash_token_with, NamedToken, Principal, ReloadableTokenStore, Role, token_name_for_log, TokenEntry, TokenLookup, TokenScope, TokenStore};
+
+    /// テストが遅くならないよう、弱いパラメーターでハッシュする
+    fn weak_hash(token: &str) -> String {
+        let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, Params::new(8, 1, 1, None).unwrap());
+        hash_token_with(&argon2, token).unwrap()
+    }
+
+    #[test]
+    fn parse_token_file() {
+        let json = serde_json::json!([{"name": "ci", "hash": weak_hash("secret"), "scopes": ["article:write", "meta:rename"]}]);
+        let entries: Vec<TokenEntry> = serde_json::from_value(json).unwrap();
+        let store = TokenStore::new(entries.into_iter().map(|x| NamedToken::try_from(x).unwrap()).collect());
+
+        let found = store.find("secret").unwrap();
+        assert_eq!(found.name, "ci");
+        assert_eq!(found.scopes, [TokenScope::ArticleWrite, TokenScope::MetaRename]);
+        assert_eq!(found.role, Role::Admin, "role defaults to admin for compatibility");
+        assert!(store.find("Secret").is_none());
+        // 二度目はキャッシュから引かれる
+        assert_eq!(store.find("secret").unwrap().name, "ci");
+    }
+
+    #[test]
+    fn unmatched_token_is_remembered() {
+        let entry = TokenEntry { name: "ci".to_string(), hash: weak_hash("secret"), scopes: vec![], role: Role::Author, expires_at: None };
+        let store = TokenStore::new(vec![NamedToken::try_from(entry).unwrap()]);
+
+        assert!(store.find("wrong").is_none());
+        assert_eq!(store.rejected.read().unwrap().len(), 1);
+        // 二度目は照合せずに拒否する
+        assert!(store.find("wrong").is_none());
+        assert_eq!(store.rejected.read().unwrap().len(), 1);
+        assert_eq!(store.find("secret").unwrap().name, "ci");
+    }
+
+    #[test]
+    fn logged_name_is_the_one_resolved_by_handler() {
+        let response = |lookup: Option<TokenLookup>| {
+            let request = TestRequest::default().to_http_request();
+            if let Some(lookup) = lookup {
+                request.extensions_mut().insert(lookup);
+            }
+            ServiceResponse::new(request, HttpResponse::Ok().finish())
+        };
+
+        assert_eq!(token_name_for_log(&response(Some(TokenLookup::Matched { name: "ci".to_string() }))), "ci");
+        assert_eq!(token_name_for_log(&response(Some(TokenLookup::Unmatched))), "-");
+        assert_eq!(token_name_for_log(&response(None)), "-");
+    }
+
+    #[test]
+    fn malformed_hash_is_rejected() {
+        let entry: TokenEntry = serde_json::from_value(serde_json::json!({"name": "ci", "hash": "secret", "scopes": []})).unwrap();
+        assert!(NamedToken::try_from(entry).is_err());
+    }
+
+    #[test]
+    fn plain_token_is_compared_by_digest() {
+        let store = TokenStore::new(vec![NamedToken::with_all_scopes("stdin".to_string(), "secret")]);
+
+        assert_eq!(store.find("secret").unwrap().name, "stdin");
+        assert!(store.find("secret ").is_none());
+        assert!(store.find("").is_none());
+    }
+
+    #[test]
+    fn expired_token_is_rejected() {
+        let now = Local::now();
+        let entry = TokenEntry { name: "old".to_string(), hash: weak_hash("secret"), scopes: vec![], role: Role::Author, expires_at: Some(now) };
+        let store = TokenStore::new(vec![NamedToken::try_from(entry).unwrap()]);
+
+        assert_eq!(store.find_at("secret", now - TimeDelta::seconds(1)).unwrap().name, "old");
+        assert!(store.find_at("secret", now).is_none());
+    }
+
+    #[test]
+    fn reload_replaces_tokens_from_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("token.json");
+        let write = |name: &str, token: &str| {
+            let entries = [TokenEntry { name: name.to_string(), hash: weak_hash(token), scopes: vec![], role: Role::Author, expires_at: None }];
+            std::fs::write(&path, serde_json::to_vec(&entries).unwrap()).unwrap();
+        };
+
+        write("old", "old-secret");
+        let store = ReloadableTokenStore::load(&path, vec![NamedToken::with_all_scopes("stdin".to_string(), "secret")]).unwrap();
+        assert_eq!(store.current().find("old-secret").unwrap().name, "old");
+
+        write("new", "new-secret");
+        store.reload().unwrap();
+        let current = store.current();
+        assert!(current.find("old-secret").is_none());
+        assert_eq!(current.find("new-secret").unwrap().name, "new");
+        assert_eq!(current.find("secret").unwrap().name, "stdin");
+
+        std::fs::write(&path, "[").unwrap();
+        assert!(store.reload().is_err());
+        assert_eq!(store.current().find("new-secret").unwrap().name, "new");
+    }
+
+    #[test]
+    fn author_can_modify_only_own_articles() {
+        let article = |created_by: Option<&str>| Article {
+            created_at: Local::now(),
+            updated_at: Local::now(),
+            content: String::new(),
+            visibility: Visibility::Public,
+            created_by: created_by.map(ToString::to_string),
+            updated_by: None,
+            tags: BTreeSet::new(),
+            title: None,
+            summary: None,
+            lang: None,
+            publish_at: None,
+            draft: None,
+        };
+        let author = Principal { name: "alice".to_string(), role: Role::Author };
+        let admin = Principal { name: "root".to_string(), role: Role::Admin };
+
+        assert!(author.can_modify(&article(Some("alice"))));
+        assert!(!author.can_modify(&article(Some("bob"))));
+        assert!(!author.can_modify(&article(None)));
+        assert!(admin.can_modify(&article(Some("bob"))));
+        assert!(admin.can_modify(&article(None)));
+    }
+
+    #[test]
+    fn scope_names_round_trip() {
+        for scope in <TokenScope as strum::IntoEnumIterator>::iter() {
+            let parsed: TokenScope = serde_json::from_value(serde_json::Value::from(scope.to_string())).unwrap();
+            assert_eq!(parsed, scope);
+        }
+    }
+}
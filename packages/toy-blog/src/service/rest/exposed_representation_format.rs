@@ -1,14 +1,15 @@
 use std::fmt::{Display, Formatter};
 use std::iter::{Chain, Empty, empty};
 
-use actix_web::http::header::{CONTENT_TYPE, HeaderName, HeaderValue, LAST_MODIFIED, WARNING};
+use actix_web::http::header::{Accept, CONTENT_TYPE, ETAG, EntityTag, Header, HeaderName, HeaderValue, LAST_MODIFIED, LINK, Quality, VARY, WARNING};
 use actix_web::http::StatusCode;
-use actix_web::HttpResponse;
+use actix_web::{HttpRequest, HttpResponse};
 use chrono::{FixedOffset, Utc};
 use serde::{Serialize, Serializer};
 
-use toy_blog_endpoint_model::{ArticleCreatedNotice, ArticleListingResponseRepresentation, ArticleListingResponseMetadata, ChangeArticleIdError, ChangeArticleIdRequestResult, CreateArticleError, CreateArticleResult, DeleteArticleError, DeleteArticleResult, GetArticleError, GetArticleResult, ListArticleResponse, ListArticleResult, OwnedMetadata, UpdateArticleError, UpdateArticleResult};
+use toy_blog_endpoint_model::{Article, ArticleCreatedNotice, ArticleDocument, ArticleId, ArticleListingResponseRepresentation, ArticleListingResponseMetadata, ArticleSnapshot, ArticleSnapshotMetadata, ChangeArticleIdError, ChangeArticleIdRequestResult, CreateArticleError, CreateArticleResult, DeleteArticleError, DeleteArticleResult, DiffArticleRevisionResult, DraftError, GetArticleDocumentResult, GetArticleError, GetAuditLogError, GetAuditLogResult, GetArticleResult, GetArticleRevisionError, GetArticleRevisionResult, GetDraftResult, ListArticleResponse, ListArticleResult, ListArticleRevisionsResult, ListScheduledPublicationsError, ListScheduledPublicationsResult, ListTrashError, ListTrashResult, SchedulePublicationError, SchedulePublicationResult, OwnedMetadata, PurgeArticleError, PurgeArticleResult, CreateShareLinkResult, ListShareLinksResult, RevokeShareLinkResult, ShareLinkError, RestoreArticleError, RestoreArticleResult, RevertArticleError, RevertArticleResult, SearchHit, TagCount, UpdateArticleError, UpdateArticleMetadataError, UpdateArticleMetadataResult, UpdateArticleResult, UpdateDraftResult, UpdateTagsError, UpdateTagsResult};
 
+use crate::service::hash::digest_of;
 use crate::service::rest::header::HttpDate;
 use crate::service::rest::inner_no_leak::{ComposeInternalError, UnhandledError};
 
@@ -37,6 +38,75 @@ impl IntoPlainText for UnhandledError {
     }
 }
 
+/// `Accept: application/json`のときの本文。[`EndpointRepresentationCompiler::into_json`]と違い、エラーも本文にできる。
+pub trait IntoJsonValue {
+    fn into_json_value(self) -> serde_json::Value;
+}
+
+impl<T: IntoJsonValue> IntoJsonValue for ComposeInternalError<T> {
+    fn into_json_value(self) -> serde_json::Value {
+        match self {
+            Ok(t) => t.into_json_value(),
+            Err(e) => e.into_json_value(),
+        }
+    }
+}
+
+impl IntoJsonValue for UnhandledError {
+    fn into_json_value(self) -> serde_json::Value {
+        JsonMessage::Error(self.into_plain_text()).into_json_value()
+    }
+}
+
+/// 平文の本文しか持たない結果をJSONにしたもの。`{"message": ...}`か`{"error": ...}`になる。
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+enum JsonMessage {
+    Message(String),
+    Error(String),
+}
+
+impl IntoJsonValue for JsonMessage {
+    fn into_json_value(self) -> serde_json::Value {
+        serde_json::to_value(self).expect("bug: message must be serializable")
+    }
+}
+
+/// 平文の本文をそのまま[`JsonMessage`]に包んでJSONにできるようにする。成功かどうかはステータスコードで決める。
+macro_rules! json_message {
+    ($($result:ty),* $(,)?) => {
+        $(
+            impl IntoJsonValue for $result {
+                fn into_json_value(self) -> serde_json::Value {
+                    let message = if self.call_status_code().is_success() {
+                        JsonMessage::Message
+                    } else {
+                        JsonMessage::Error
+                    };
+
+                    message(self.into_plain_text()).into_json_value()
+                }
+            }
+        )*
+    };
+}
+
+/// 成功した結果はその値をそのままJSONにし、失敗した結果は平文の本文を[`JsonMessage`]に包む。
+macro_rules! json_document {
+    ($($result:ty),* $(,)?) => {
+        $(
+            impl IntoJsonValue for $result {
+                fn into_json_value(self) -> serde_json::Value {
+                    match self {
+                        Ok(document) => serde_json::to_value(document).expect("bug: document must be serializable"),
+                        error @ Err(_) => JsonMessage::Error(error.into_plain_text()).into_json_value(),
+                    }
+                }
+            }
+        )*
+    };
+}
+
 pub trait ContainsHeaderMap {
     type Iterator: Iterator<Item = Pair>;
 
@@ -123,21 +193,25 @@ impl<T> EndpointRepresentationCompiler<T> {
     }
 }
 
+fn apply_headers<B>(res: &mut HttpResponse<B>, headers: impl Iterator<Item = Pair>) {
+    headers.for_each(|(k, v)| {
+        match v {
+            HeaderValueUpdateMethod::Overwrite(v) => {
+                res.headers_mut().insert(k, v);
+            }
+            HeaderValueUpdateMethod::Append(v) => {
+                res.headers_mut().append(k, v);
+            }
+        }
+    });
+}
+
 impl<T: IntoPlainText + HttpStatusCode + ContainsHeaderMap> EndpointRepresentationCompiler<T> {
     pub fn into_plain_text(self) -> HttpResponse<String> {
         let mut res = HttpResponse::new(self.0.call_status_code());
         res.headers_mut().insert(CONTENT_TYPE, "text/plain; charset=utf-8".try_into().unwrap());
         let x = self.0;
-        x.response_headers().for_each(|(k, v)| {
-            match v {
-                HeaderValueUpdateMethod::Overwrite(v) => {
-                    res.headers_mut().insert(k, v);
-                }
-                HeaderValueUpdateMethod::Append(v) => {
-                    res.headers_mut().append(k, v);
-                }
-            }
-        });
+        apply_headers(&mut res, x.response_headers());
 
         res.set_body(x.into_plain_text())
     }
@@ -147,10 +221,62 @@ impl<T: Serialize + HttpStatusCode + ContainsHeaderMap> EndpointRepresentationCo
     pub fn into_json(self) -> HttpResponse<T> {
         let mut res = HttpResponse::new(self.0.call_status_code());
         res.headers_mut().insert(CONTENT_TYPE, "application/json".try_into().unwrap());
+        apply_headers(&mut res, self.0.response_headers());
         res.set_body(self.0)
     }
 }
 
+impl<T: IntoJsonValue + HttpStatusCode + ContainsHeaderMap> EndpointRepresentationCompiler<T> {
+    pub fn into_json_value(self) -> HttpResponse<String> {
+        let status = self.0.call_status_code();
+        let mut res = HttpResponse::new(status);
+        res.headers_mut().insert(CONTENT_TYPE, "application/json".try_into().unwrap());
+        let x = self.0;
+        apply_headers(&mut res, x.response_headers());
+
+        // 304には本文を含めない (RFC 7232 § 4.1)
+        if status == StatusCode::NOT_MODIFIED {
+            res.set_body(String::new())
+        } else {
+            res.set_body(x.into_json_value().to_string())
+        }
+    }
+}
+
+impl<T: IntoPlainText + IntoJsonValue + HttpStatusCode + ContainsHeaderMap> EndpointRepresentationCompiler<T> {
+    /// `Accept`でJSONが求められていればJSONで、そうでなければ平文で返す。
+    pub fn negotiate(self, request: &HttpRequest) -> HttpResponse<String> {
+        let mut res = if prefers_json(request) {
+            self.into_json_value()
+        } else {
+            self.into_plain_text()
+        };
+        add_vary_accept(&mut res);
+
+        res
+    }
+}
+
+/// `Accept`で平文よりJSONが好まれているかどうか。`Accept`が無い場合や解釈できない場合は平文にする。
+///
+/// `q=0`の型は受け付けられないものとして扱う。
+pub fn prefers_json(request: &HttpRequest) -> bool {
+    let Ok(accept) = Accept::parse(request) else {
+        return false
+    };
+    let acceptable = Accept(accept.0.into_iter().filter(|x| x.quality > Quality::ZERO).collect());
+
+    acceptable.ranked()
+        .into_iter()
+        .find(|x| matches!(x.essence_str(), "application/json" | "text/plain" | "text/*" | "*/*"))
+        .is_some_and(|x| x.essence_str() == "application/json")
+}
+
+/// 本文が`Accept`によって変わることを伝える (RFC 9110 § 12.5.5)
+pub fn add_vary_accept<B>(res: &mut HttpResponse<B>) {
+    res.headers_mut().append(VARY, HeaderValue::from_static("Accept"));
+}
+
 pub struct HttpFormattedDate(chrono::DateTime<FixedOffset>);
 
 impl HttpFormattedDate {
@@ -169,6 +295,44 @@ impl Display for HttpFormattedDate {
 }
 
 type VecIter<T> = <Vec<T> as IntoIterator>::IntoIter;
+
+/// 本文のSHA-256ダイジェストを値とする強いETag。本文が同じなら、記事が更新されても同じ値になる。
+pub fn content_etag(body: &str) -> EntityTag {
+    EntityTag::new_strong(digest_of(body))
+}
+
+/// JSONで返す記事。
+pub fn article_document(id: ArticleId, article: Article) -> ArticleDocument {
+    ArticleDocument {
+        id,
+        content: article.content,
+        visibility: article.visibility,
+        created_at: article.created_at,
+        updated_at: article.updated_at,
+    }
+}
+
+/// JSONで返す記事の強いETag。平文の本文と違う表現なので、[`content_etag`]とは違う値になる。
+///
+/// IDや公開範囲も含めたダイジェストなので、本文以外が変わった場合も変わる。
+pub fn document_etag(document: &ArticleDocument) -> EntityTag {
+    content_etag(&serde_json::to_string(document).expect("bug: article must be serializable"))
+}
+
+/// 記事の一覧のページの`ETag`。本文と同じJSONと次のページへのリンクから作るので、どちらかが変われば変わる。
+pub fn listing_etag(listing: &ArticleListingResponseRepresentation, next_page_link: Option<&str>) -> EntityTag {
+    let mut source = serde_json::to_string(listing).expect("listing must be serializable");
+    if let Some(link) = next_page_link {
+        source.push('\n');
+        source.push_str(link);
+    }
+
+    content_etag(&source)
+}
+
+fn etag_header(etag: &EntityTag) -> Pair {
+    (ETAG, HeaderValueUpdateMethod::Overwrite(HeaderValue::from_str(&etag.to_string()).expect("bug: etag must be visible ASCII")))
+}
 // --------------------------
 
 impl HttpStatusCode for CreateArticleResult {
@@ -179,6 +343,7 @@ impl HttpStatusCode for CreateArticleResult {
                 match f {
                     CreateArticleError::DuplicatedArticleId => StatusCode::CONFLICT,
                     CreateArticleError::Unauthorized => StatusCode::UNAUTHORIZED,
+                    CreateArticleError::InsufficientScope => StatusCode::FORBIDDEN,
                     CreateArticleError::InvalidUtf8 => StatusCode::BAD_REQUEST,
                 }
             }
@@ -211,6 +376,9 @@ impl IntoPlainText for CreateArticleResult {
                     CreateArticleError::Unauthorized => {
                         "You must be authorized to perform this action.".to_string()
                     }
+                    CreateArticleError::InsufficientScope => {
+                        "This token is not allowed to perform this action.".to_string()
+                    }
                     CreateArticleError::DuplicatedArticleId => {
                         "already exist. Please choose another one, or overwrite with PUT request.".to_string()
                     }
@@ -238,27 +406,26 @@ impl HttpStatusCode for GetArticleResult {
 
 impl ContainsHeaderMap for GetArticleResult {
     type Iterator = EitherIter<
-        core::iter::Once<Pair>,
+        core::array::IntoIter<Pair, 2>,
         Empty<Pair>,
         Pair,
     >;
 
     fn response_headers(&self) -> Self::Iterator {
-        use std::iter::once as single_iter;
-
         self.as_ref().map_or(
             EitherIter::Right(empty()),
             |d| EitherIter::Left(
-                single_iter(
-                    // TODO: Having ETag is fun, right?
+                [
                     // compliant with RFC 7232 (HTTP/1.1 Conditional Requests) § 2.1.1
                     (
                         LAST_MODIFIED,
                         HeaderValueUpdateMethod::Overwrite(
                             HttpFormattedDate::new(d.metadata.updated_at).to_string().try_into().unwrap()
                         )
-                    )
-                )
+                    ),
+                    // RFC 7232 § 2.3
+                    etag_header(&content_etag(d.data.content.as_str())),
+                ].into_iter()
             )
         )
     }
@@ -280,6 +447,49 @@ impl IntoPlainText for GetArticleResult {
     }
 }
 
+impl HttpStatusCode for GetArticleDocumentResult {
+    fn call_status_code(&self) -> StatusCode {
+        match self {
+            Ok(_) => StatusCode::OK,
+            Err(GetArticleError::NoSuchArticleFoundById) => StatusCode::NOT_FOUND,
+        }
+    }
+}
+
+impl ContainsHeaderMap for GetArticleDocumentResult {
+    type Iterator = EitherIter<
+        core::array::IntoIter<Pair, 2>,
+        Empty<Pair>,
+        Pair,
+    >;
+
+    fn response_headers(&self) -> Self::Iterator {
+        self.as_ref().map_or(
+            EitherIter::Right(empty()),
+            |d| EitherIter::Left(
+                [
+                    (
+                        LAST_MODIFIED,
+                        HeaderValueUpdateMethod::Overwrite(
+                            HttpFormattedDate::new(d.updated_at.fixed_offset()).to_string().try_into().unwrap()
+                        )
+                    ),
+                    etag_header(&document_etag(d)),
+                ].into_iter()
+            )
+        )
+    }
+}
+
+impl IntoJsonValue for GetArticleDocumentResult {
+    fn into_json_value(self) -> serde_json::Value {
+        match self {
+            Ok(document) => serde_json::to_value(document).expect("bug: article must be serializable"),
+            Err(GetArticleError::NoSuchArticleFoundById) => JsonMessage::Error("Not found".to_string()).into_json_value(),
+        }
+    }
+}
+
 impl HttpStatusCode for UpdateArticleResult {
     fn call_status_code(&self) -> StatusCode {
         match self {
@@ -287,8 +497,11 @@ impl HttpStatusCode for UpdateArticleResult {
             Err(e) => {
                 match e {
                     UpdateArticleError::InvalidBearerToken => StatusCode::UNAUTHORIZED,
+                    UpdateArticleError::InsufficientScope => StatusCode::FORBIDDEN,
                     UpdateArticleError::InvalidByteSequenceForUtf8(_) => StatusCode::BAD_REQUEST,
                     UpdateArticleError::ArticleNotFoundById => StatusCode::NOT_FOUND,
+                    UpdateArticleError::PreconditionFailed => StatusCode::PRECONDITION_FAILED,
+                    UpdateArticleError::PreconditionRequired => StatusCode::PRECONDITION_REQUIRED,
                 }
             }
         }
@@ -312,7 +525,10 @@ impl IntoPlainText for UpdateArticleResult {
             Err(e) => {
                 match e {
                     UpdateArticleError::InvalidBearerToken => "You must be authorized to perform this action.".to_string(),
+                    UpdateArticleError::InsufficientScope => "This token is not allowed to perform this action.".to_string(),
                     UpdateArticleError::ArticleNotFoundById => "Not found".to_string(),
+                    UpdateArticleError::PreconditionFailed => "The article has been changed since the given precondition.".to_string(),
+                    UpdateArticleError::PreconditionRequired => "This server requires If-Match or If-Unmodified-Since for this action.".to_string(),
                     UpdateArticleError::InvalidByteSequenceForUtf8(e) => format!("You must provide valid UTF-8 sequence: {e}")
                 }
             }
@@ -327,7 +543,10 @@ impl HttpStatusCode for DeleteArticleResult {
             Err(e) => {
                 match e {
                     DeleteArticleError::InvalidBearerToken => StatusCode::UNAUTHORIZED,
+                    DeleteArticleError::InsufficientScope => StatusCode::FORBIDDEN,
                     DeleteArticleError::NoSuchArticleFoundById => StatusCode::NOT_FOUND,
+                    DeleteArticleError::PreconditionFailed => StatusCode::PRECONDITION_FAILED,
+                    DeleteArticleError::PreconditionRequired => StatusCode::PRECONDITION_REQUIRED,
                 }
             }
         }
@@ -345,11 +564,99 @@ impl ContainsHeaderMap for DeleteArticleResult {
 impl IntoPlainText for DeleteArticleResult {
     fn into_plain_text(self) -> String {
         match self {
-            Ok(()) => "deleted".to_string(),
+            Ok(()) => "moved to trash".to_string(),
             Err(e) => {
                 match e {
                     DeleteArticleError::InvalidBearerToken => "You must be authorized to perform this action.".to_string(),
-                    DeleteArticleError::NoSuchArticleFoundById => "Not found".to_string()
+                    DeleteArticleError::InsufficientScope => "This token is not allowed to perform this action.".to_string(),
+                    DeleteArticleError::NoSuchArticleFoundById => "Not found".to_string(),
+                    DeleteArticleError::PreconditionFailed => "The article has been changed since the given precondition.".to_string(),
+                    DeleteArticleError::PreconditionRequired => "This server requires If-Match or If-Unmodified-Since for this action.".to_string(),
+                }
+            }
+        }
+    }
+}
+
+impl HttpStatusCode for UpdateArticleMetadataResult {
+    fn call_status_code(&self) -> StatusCode {
+        match self {
+            Ok(()) => StatusCode::NO_CONTENT,
+            Err(e) => {
+                match e {
+                    UpdateArticleMetadataError::InvalidBearerToken => StatusCode::UNAUTHORIZED,
+                    UpdateArticleMetadataError::InsufficientScope => StatusCode::FORBIDDEN,
+                    UpdateArticleMetadataError::NoSuchArticleFoundById => StatusCode::NOT_FOUND,
+                    UpdateArticleMetadataError::InvalidTitle
+                    | UpdateArticleMetadataError::InvalidSummary
+                    | UpdateArticleMetadataError::InvalidLang(_) => StatusCode::BAD_REQUEST,
+                }
+            }
+        }
+    }
+}
+
+impl ContainsHeaderMap for UpdateArticleMetadataResult {
+    type Iterator = Empty<Pair>;
+
+    fn response_headers(&self) -> Self::Iterator {
+        core::iter::empty()
+    }
+}
+
+impl IntoPlainText for UpdateArticleMetadataResult {
+    fn into_plain_text(self) -> String {
+        match self {
+            Ok(()) => "metadata updated".to_string(),
+            Err(e) => {
+                match e {
+                    UpdateArticleMetadataError::InvalidBearerToken => "You must be authorized to perform this action.".to_string(),
+                    UpdateArticleMetadataError::InsufficientScope => "This token is not allowed to perform this action.".to_string(),
+                    UpdateArticleMetadataError::NoSuchArticleFoundById => "Not found".to_string(),
+                    UpdateArticleMetadataError::InvalidTitle => "invalid title. A title must be 1 to 200 characters without control characters.".to_string(),
+                    UpdateArticleMetadataError::InvalidSummary => "invalid summary. A summary must be 1 to 1000 characters.".to_string(),
+                    UpdateArticleMetadataError::InvalidLang(lang) => format!("invalid lang: {lang:?}. It must be a BCP 47 language tag such as `ja` or `en-US`."),
+                }
+            }
+        }
+    }
+}
+
+impl HttpStatusCode for UpdateTagsResult {
+    fn call_status_code(&self) -> StatusCode {
+        match self {
+            Ok(()) => StatusCode::NO_CONTENT,
+            Err(e) => {
+                match e {
+                    UpdateTagsError::InvalidBearerToken => StatusCode::UNAUTHORIZED,
+                    UpdateTagsError::InsufficientScope => StatusCode::FORBIDDEN,
+                    UpdateTagsError::NoSuchArticleFoundById => StatusCode::NOT_FOUND,
+                    UpdateTagsError::InvalidTag(_) | UpdateTagsError::TooManyTags { .. } => StatusCode::BAD_REQUEST,
+                }
+            }
+        }
+    }
+}
+
+impl ContainsHeaderMap for UpdateTagsResult {
+    type Iterator = Empty<Pair>;
+
+    fn response_headers(&self) -> Self::Iterator {
+        core::iter::empty()
+    }
+}
+
+impl IntoPlainText for UpdateTagsResult {
+    fn into_plain_text(self) -> String {
+        match self {
+            Ok(()) => "tags updated".to_string(),
+            Err(e) => {
+                match e {
+                    UpdateTagsError::InvalidBearerToken => "You must be authorized to perform this action.".to_string(),
+                    UpdateTagsError::InsufficientScope => "This token is not allowed to perform this action.".to_string(),
+                    UpdateTagsError::NoSuchArticleFoundById => "Not found".to_string(),
+                    UpdateTagsError::InvalidTag(tag) => format!("invalid tag: {tag:?}. A tag must be 1 to 64 characters without whitespace."),
+                    UpdateTagsError::TooManyTags { max } => format!("too many tags. An article can have at most {max} tags."),
                 }
             }
         }
@@ -408,7 +715,11 @@ impl HttpStatusCode for ChangeArticleIdRequestResult {
             Err(e) => {
                 match e {
                     ChangeArticleIdError::Unauthorized => StatusCode::UNAUTHORIZED,
+                    ChangeArticleIdError::InsufficientScope => StatusCode::FORBIDDEN,
                     ChangeArticleIdError::ArticleNotFoundById => StatusCode::NOT_FOUND,
+                    ChangeArticleIdError::DuplicatedArticleId => StatusCode::CONFLICT,
+                    ChangeArticleIdError::PreconditionFailed => StatusCode::PRECONDITION_FAILED,
+                    ChangeArticleIdError::PreconditionRequired => StatusCode::PRECONDITION_REQUIRED,
                 }
             }
         }
@@ -432,144 +743,846 @@ impl IntoPlainText for ChangeArticleIdRequestResult {
             Err(e) => {
                 match e {
                     ChangeArticleIdError::Unauthorized => "You must be authorized to perform this action.".to_string(),
+                    ChangeArticleIdError::InsufficientScope => "This token is not allowed to perform this action.".to_string(),
                     ChangeArticleIdError::ArticleNotFoundById => "The article does not exist".to_string(),
+                    ChangeArticleIdError::DuplicatedArticleId => "An article with the new ID already exists. Choose another one.".to_string(),
+                    ChangeArticleIdError::PreconditionFailed => "The article has been changed since the given precondition.".to_string(),
+                    ChangeArticleIdError::PreconditionRequired => "This server requires If-Match or If-Unmodified-Since for this action.".to_string(),
                 }
             }
         }
     }
 }
 
-pub(super) struct MaybeNotModified<Repr> {
-    pub(super) inner: Repr,
-    pub(super) is_modified: bool,
-}
-
-impl<Repr: HttpStatusCode> HttpStatusCode for MaybeNotModified<Repr> {
+impl HttpStatusCode for ListArticleRevisionsResult {
     fn call_status_code(&self) -> StatusCode {
-        if self.is_modified {
-            StatusCode::NOT_MODIFIED
-        } else {
-            self.inner.call_status_code()
+        match self {
+            Ok(_) => StatusCode::OK,
+            Err(e) => {
+                match e {
+                    GetArticleError::NoSuchArticleFoundById => StatusCode::NOT_FOUND,
+                }
+            }
         }
     }
 }
 
-impl<Repr: ContainsHeaderMap> ContainsHeaderMap for MaybeNotModified<Repr> {
-    type Iterator = Repr::Iterator;
+impl ContainsHeaderMap for ListArticleRevisionsResult {
+    type Iterator = Empty<Pair>;
 
     fn response_headers(&self) -> Self::Iterator {
-        self.inner.response_headers()
+        empty()
     }
 }
 
-impl<Repr: IntoPlainText> IntoPlainText for MaybeNotModified<Repr> {
+impl IntoPlainText for ListArticleRevisionsResult {
     fn into_plain_text(self) -> String {
-        self.inner.into_plain_text()
+        match self {
+            Ok(revisions) => {
+                revisions
+                    .into_iter()
+                    .map(|x| format!("{} {}", x.revision, x.created_at.to_rfc3339()) + "\n")
+                    .collect()
+            }
+            Err(e) => {
+                match e {
+                    GetArticleError::NoSuchArticleFoundById => "Not found".to_string()
+                }
+            }
+        }
     }
 }
 
-impl<Repr: Serialize> Serialize for MaybeNotModified<Repr> {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
-        self.inner.serialize(serializer)
+const fn revision_not_found_status_code(e: &GetArticleRevisionError) -> StatusCode {
+    match e {
+        GetArticleRevisionError::NoSuchArticleFoundById
+        | GetArticleRevisionError::NoSuchRevisionFound => StatusCode::NOT_FOUND,
     }
 }
 
-pub(super) struct ReportLastModofied<Repr> {
-    pub(super) inner: Repr,
-    pub(super) latest_updated: Option<HttpDate>,
+fn revision_not_found_plain_text(e: &GetArticleRevisionError) -> String {
+    match e {
+        GetArticleRevisionError::NoSuchArticleFoundById => "Not found".to_string(),
+        GetArticleRevisionError::NoSuchRevisionFound => "The revision does not exist".to_string(),
+    }
 }
 
-impl<Repr: HttpStatusCode> HttpStatusCode for ReportLastModofied<Repr> {
+impl HttpStatusCode for GetArticleRevisionResult {
     fn call_status_code(&self) -> StatusCode {
-        self.inner.call_status_code()
+        match self {
+            Ok(_) => StatusCode::OK,
+            Err(e) => revision_not_found_status_code(e),
+        }
     }
 }
 
-impl<Repr: ContainsHeaderMap> ContainsHeaderMap for ReportLastModofied<Repr> {
-    type Iterator = Chain<Repr::Iterator, core::option::IntoIter<Pair>>;
+impl ContainsHeaderMap for GetArticleRevisionResult {
+    type Iterator = core::option::IntoIter<Pair>;
 
     fn response_headers(&self) -> Self::Iterator {
-        let existing = self.inner.response_headers();
-        
-        let to_be_chained = self.latest_updated.as_ref().map(|date| {
+        self.as_ref().ok().map(|d| {
             (
-                HeaderName::from_static("Last-Updated"),
-                HeaderValueUpdateMethod::Append(HeaderValue::from_str(&date.to_string()).expect("bug: http date"))
+                LAST_MODIFIED,
+                HeaderValueUpdateMethod::Overwrite(
+                    HttpFormattedDate::new(d.metadata.updated_at).to_string().try_into().unwrap()
+                )
             )
-        });
-        
-        existing.chain(to_be_chained)
+        }).into_iter()
     }
 }
 
-impl<Repr: IntoPlainText> IntoPlainText for ReportLastModofied<Repr> {
+impl IntoPlainText for GetArticleRevisionResult {
     fn into_plain_text(self) -> String {
-        self.inner.into_plain_text()
+        match self {
+            Ok(article) => article.data.content.into_inner(),
+            Err(e) => revision_not_found_plain_text(&e),
+        }
     }
 }
 
-impl<Repr: Serialize> Serialize for ReportLastModofied<Repr> {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
-        self.inner.serialize(serializer)
-    }
+/// 版や下書きのように、本文と更新日時だけを持つものをJSONにする。
+fn snapshot_json(snapshot: OwnedMetadata<ArticleSnapshotMetadata, ArticleSnapshot>) -> serde_json::Value {
+    serde_json::json!({
+        "content": snapshot.data.content.into_inner(),
+        "updated_at": snapshot.metadata.updated_at,
+    })
 }
 
+impl IntoJsonValue for GetArticleRevisionResult {
+    fn into_json_value(self) -> serde_json::Value {
+        match self {
+            Ok(revision) => snapshot_json(revision),
+            Err(e) => JsonMessage::Error(revision_not_found_plain_text(&e)).into_json_value(),
+        }
+    }
+}
 
-pub(super) struct ArticleIdCollectionResponseRepr(
-    pub(super) MaybeNotModified<ReportLastModofied<OwnedMetadata<ArticleListingResponseMetadata, ArticleListingResponseRepresentation>>>
-);
-
-impl HttpStatusCode for ArticleIdCollectionResponseRepr {
+impl HttpStatusCode for DiffArticleRevisionResult {
     fn call_status_code(&self) -> StatusCode {
-        StatusCode::OK
+        match self {
+            Ok(_) => StatusCode::OK,
+            Err(e) => revision_not_found_status_code(e),
+        }
     }
 }
 
-impl ContainsHeaderMap for ArticleIdCollectionResponseRepr {
-    type Iterator = EitherIter<
-        VecIter<(HeaderName, HeaderValueUpdateMethod)>,
-        Empty<(HeaderName, HeaderValueUpdateMethod)>,
-        (HeaderName, HeaderValueUpdateMethod)
-    >;
+impl ContainsHeaderMap for DiffArticleRevisionResult {
+    type Iterator = Empty<Pair>;
 
     fn response_headers(&self) -> Self::Iterator {
-        self.0.inner.inner.metadata.newest_updated_at.map_or(
-            EitherIter::Right(empty()),
-            |newest| {
-                let date = HttpFormattedDate::new(newest.with_timezone(newest.offset()));
-                let vec = vec![
-                    (LAST_MODIFIED, HeaderValueUpdateMethod::Overwrite(date.to_string().try_into().unwrap()))
-                ];
-                EitherIter::Left(vec.into_iter())
-            }
-        )
+        empty()
     }
 }
 
-impl Serialize for ArticleIdCollectionResponseRepr {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
-        self.0.inner.inner.data.serialize(serializer)
+impl IntoPlainText for DiffArticleRevisionResult {
+    fn into_plain_text(self) -> String {
+        match self {
+            Ok(diff) => diff.0,
+            Err(e) => revision_not_found_plain_text(&e),
+        }
     }
 }
 
-pub(super) struct InternalErrorExposedRepr(pub(super) Box<dyn std::error::Error>);
+impl IntoJsonValue for DiffArticleRevisionResult {
+    fn into_json_value(self) -> serde_json::Value {
+        match self {
+            Ok(diff) => serde_json::json!({ "diff": diff.0 }),
+            Err(e) => JsonMessage::Error(revision_not_found_plain_text(&e)).into_json_value(),
+        }
+    }
+}
 
-impl HttpStatusCode for InternalErrorExposedRepr {
+impl HttpStatusCode for RevertArticleResult {
     fn call_status_code(&self) -> StatusCode {
-        StatusCode::INTERNAL_SERVER_ERROR
+        match self {
+            Ok(()) => StatusCode::NO_CONTENT,
+            Err(e) => {
+                match e {
+                    RevertArticleError::InvalidBearerToken => StatusCode::UNAUTHORIZED,
+                    RevertArticleError::InsufficientScope => StatusCode::FORBIDDEN,
+                    RevertArticleError::ArticleNotFoundById
+                    | RevertArticleError::NoSuchRevisionFound => StatusCode::NOT_FOUND,
+                }
+            }
+        }
     }
 }
 
-impl ContainsHeaderMap for InternalErrorExposedRepr {
-    type Iterator = Empty<(HeaderName, HeaderValueUpdateMethod)>;
+impl ContainsHeaderMap for RevertArticleResult {
+    type Iterator = Empty<Pair>;
+
+    fn response_headers(&self) -> Self::Iterator {
+        empty()
+    }
+}
+
+impl IntoPlainText for RevertArticleResult {
+    fn into_plain_text(self) -> String {
+        match self {
+            Ok(()) => "reverted".to_string(),
+            Err(e) => {
+                match e {
+                    RevertArticleError::InvalidBearerToken => "You must be authorized to perform this action.".to_string(),
+                    RevertArticleError::InsufficientScope => "This token is not allowed to perform this action.".to_string(),
+                    RevertArticleError::ArticleNotFoundById => "Not found".to_string(),
+                    RevertArticleError::NoSuchRevisionFound => "The revision does not exist".to_string(),
+                }
+            }
+        }
+    }
+}
+
+impl HttpStatusCode for ListTrashResult {
+    fn call_status_code(&self) -> StatusCode {
+        match self {
+            Ok(_) => StatusCode::OK,
+            Err(e) => {
+                match e {
+                    ListTrashError::InvalidBearerToken => StatusCode::UNAUTHORIZED,
+                    ListTrashError::InsufficientScope => StatusCode::FORBIDDEN,
+                }
+            }
+        }
+    }
+}
+
+impl ContainsHeaderMap for ListTrashResult {
+    type Iterator = Empty<Pair>;
+
+    fn response_headers(&self) -> Self::Iterator {
+        empty()
+    }
+}
+
+impl IntoPlainText for ListTrashResult {
+    fn into_plain_text(self) -> String {
+        match self {
+            Ok(entries) => {
+                entries
+                    .into_iter()
+                    .map(|x| format!("{} {}", x.id, x.deleted_at.to_rfc3339()) + "\n")
+                    .collect()
+            }
+            Err(e) => {
+                match e {
+                    ListTrashError::InvalidBearerToken => "You must be authorized to perform this action.".to_string(),
+                    ListTrashError::InsufficientScope => "This token is not allowed to perform this action.".to_string(),
+                }
+            }
+        }
+    }
+}
+
+const fn draft_error_status_code(e: &DraftError) -> StatusCode {
+    match e {
+        DraftError::InvalidBearerToken => StatusCode::UNAUTHORIZED,
+        DraftError::InsufficientScope => StatusCode::FORBIDDEN,
+        DraftError::NoSuchArticleFoundById | DraftError::NoDraft => StatusCode::NOT_FOUND,
+        DraftError::InvalidByteSequenceForUtf8(_) => StatusCode::BAD_REQUEST,
+    }
+}
+
+fn draft_error_plain_text(e: &DraftError) -> String {
+    match e {
+        DraftError::InvalidBearerToken => "You must be authorized to perform this action.".to_string(),
+        DraftError::InsufficientScope => "This token is not allowed to perform this action.".to_string(),
+        DraftError::NoSuchArticleFoundById => "Not found".to_string(),
+        DraftError::NoDraft => "The article has no draft.".to_string(),
+        DraftError::InvalidByteSequenceForUtf8(e) => format!("You must provide valid UTF-8 sequence: {e}"),
+    }
+}
+
+impl HttpStatusCode for GetDraftResult {
+    fn call_status_code(&self) -> StatusCode {
+        match self {
+            Ok(_) => StatusCode::OK,
+            Err(e) => draft_error_status_code(e),
+        }
+    }
+}
+
+impl ContainsHeaderMap for GetDraftResult {
+    type Iterator = core::option::IntoIter<Pair>;
+
+    fn response_headers(&self) -> Self::Iterator {
+        self.as_ref().ok().map(|d| {
+            (
+                LAST_MODIFIED,
+                HeaderValueUpdateMethod::Overwrite(
+                    HttpFormattedDate::new(d.metadata.updated_at).to_string().try_into().unwrap()
+                )
+            )
+        }).into_iter()
+    }
+}
+
+impl IntoPlainText for GetDraftResult {
+    fn into_plain_text(self) -> String {
+        match self {
+            Ok(draft) => draft.data.content.into_inner(),
+            Err(e) => draft_error_plain_text(&e),
+        }
+    }
+}
+
+impl IntoJsonValue for GetDraftResult {
+    fn into_json_value(self) -> serde_json::Value {
+        match self {
+            Ok(draft) => snapshot_json(draft),
+            Err(e) => JsonMessage::Error(draft_error_plain_text(&e)).into_json_value(),
+        }
+    }
+}
+
+impl HttpStatusCode for UpdateDraftResult {
+    fn call_status_code(&self) -> StatusCode {
+        match self {
+            Ok(()) => StatusCode::NO_CONTENT,
+            Err(e) => draft_error_status_code(e),
+        }
+    }
+}
+
+impl ContainsHeaderMap for UpdateDraftResult {
+    type Iterator = Empty<Pair>;
+
+    fn response_headers(&self) -> Self::Iterator {
+        empty()
+    }
+}
+
+impl IntoPlainText for UpdateDraftResult {
+    fn into_plain_text(self) -> String {
+        match self {
+            Ok(()) => "saved".to_string(),
+            Err(e) => draft_error_plain_text(&e),
+        }
+    }
+}
+
+impl HttpStatusCode for SchedulePublicationResult {
+    fn call_status_code(&self) -> StatusCode {
+        match self {
+            Ok(()) => StatusCode::NO_CONTENT,
+            Err(e) => {
+                match e {
+                    SchedulePublicationError::InvalidBearerToken => StatusCode::UNAUTHORIZED,
+                    SchedulePublicationError::InsufficientScope => StatusCode::FORBIDDEN,
+                    SchedulePublicationError::NoSuchArticleFoundById | SchedulePublicationError::NotScheduled => StatusCode::NOT_FOUND,
+                    SchedulePublicationError::NotPrivate => StatusCode::CONFLICT,
+                    SchedulePublicationError::NotInFuture => StatusCode::BAD_REQUEST,
+                }
+            }
+        }
+    }
+}
+
+impl ContainsHeaderMap for SchedulePublicationResult {
+    type Iterator = Empty<Pair>;
+
+    fn response_headers(&self) -> Self::Iterator {
+        empty()
+    }
+}
+
+impl IntoPlainText for SchedulePublicationResult {
+    fn into_plain_text(self) -> String {
+        match self {
+            Ok(()) => "schedule updated".to_string(),
+            Err(e) => {
+                match e {
+                    SchedulePublicationError::InvalidBearerToken => "You must be authorized to perform this action.".to_string(),
+                    SchedulePublicationError::InsufficientScope => "This token is not allowed to perform this action.".to_string(),
+                    SchedulePublicationError::NoSuchArticleFoundById => "Not found".to_string(),
+                    SchedulePublicationError::NotPrivate => "Only private articles can be scheduled.".to_string(),
+                    SchedulePublicationError::NotInFuture => "publish_at must be in the future.".to_string(),
+                    SchedulePublicationError::NotScheduled => "The article is not scheduled.".to_string(),
+                }
+            }
+        }
+    }
+}
+
+impl HttpStatusCode for ListScheduledPublicationsResult {
+    fn call_status_code(&self) -> StatusCode {
+        match self {
+            Ok(_) => StatusCode::OK,
+            Err(e) => {
+                match e {
+                    ListScheduledPublicationsError::InvalidBearerToken => StatusCode::UNAUTHORIZED,
+                    ListScheduledPublicationsError::InsufficientScope => StatusCode::FORBIDDEN,
+                }
+            }
+        }
+    }
+}
+
+impl ContainsHeaderMap for ListScheduledPublicationsResult {
+    type Iterator = Empty<Pair>;
+
+    fn response_headers(&self) -> Self::Iterator {
+        empty()
+    }
+}
+
+impl IntoPlainText for ListScheduledPublicationsResult {
+    fn into_plain_text(self) -> String {
+        match self {
+            Ok(entries) => {
+                entries
+                    .into_iter()
+                    .map(|x| format!("{} {}", x.id, x.publish_at.to_rfc3339()) + "\n")
+                    .collect()
+            }
+            Err(e) => {
+                match e {
+                    ListScheduledPublicationsError::InvalidBearerToken => "You must be authorized to perform this action.".to_string(),
+                    ListScheduledPublicationsError::InsufficientScope => "This token is not allowed to perform this action.".to_string(),
+                }
+            }
+        }
+    }
+}
+
+impl HttpStatusCode for RestoreArticleResult {
+    fn call_status_code(&self) -> StatusCode {
+        match self {
+            Ok(()) => StatusCode::NO_CONTENT,
+            Err(e) => {
+                match e {
+                    RestoreArticleError::InvalidBearerToken => StatusCode::UNAUTHORIZED,
+                    RestoreArticleError::InsufficientScope => StatusCode::FORBIDDEN,
+                    RestoreArticleError::NoSuchArticleFoundInTrash => StatusCode::NOT_FOUND,
+                    RestoreArticleError::DuplicatedArticleId => StatusCode::CONFLICT,
+                }
+            }
+        }
+    }
+}
+
+impl ContainsHeaderMap for RestoreArticleResult {
+    type Iterator = Empty<Pair>;
+
+    fn response_headers(&self) -> Self::Iterator {
+        empty()
+    }
+}
+
+impl IntoPlainText for RestoreArticleResult {
+    fn into_plain_text(self) -> String {
+        match self {
+            Ok(()) => "restored".to_string(),
+            Err(e) => {
+                match e {
+                    RestoreArticleError::InvalidBearerToken => "You must be authorized to perform this action.".to_string(),
+                    RestoreArticleError::InsufficientScope => "This token is not allowed to perform this action.".to_string(),
+                    RestoreArticleError::NoSuchArticleFoundInTrash => "Not found in trash".to_string(),
+                    RestoreArticleError::DuplicatedArticleId => "An article with the same ID already exists. Rename or remove it first.".to_string(),
+                }
+            }
+        }
+    }
+}
+
+impl HttpStatusCode for PurgeArticleResult {
+    fn call_status_code(&self) -> StatusCode {
+        match self {
+            Ok(()) => StatusCode::NO_CONTENT,
+            Err(e) => {
+                match e {
+                    PurgeArticleError::InvalidBearerToken => StatusCode::UNAUTHORIZED,
+                    PurgeArticleError::InsufficientScope => StatusCode::FORBIDDEN,
+                    PurgeArticleError::NoSuchArticleFoundInTrash => StatusCode::NOT_FOUND,
+                }
+            }
+        }
+    }
+}
+
+impl ContainsHeaderMap for PurgeArticleResult {
+    type Iterator = Empty<Pair>;
+
+    fn response_headers(&self) -> Self::Iterator {
+        empty()
+    }
+}
+
+impl IntoPlainText for PurgeArticleResult {
+    fn into_plain_text(self) -> String {
+        match self {
+            Ok(()) => "purged".to_string(),
+            Err(e) => {
+                match e {
+                    PurgeArticleError::InvalidBearerToken => "You must be authorized to perform this action.".to_string(),
+                    PurgeArticleError::InsufficientScope => "This token is not allowed to perform this action.".to_string(),
+                    PurgeArticleError::NoSuchArticleFoundInTrash => "Not found in trash".to_string(),
+                }
+            }
+        }
+    }
+}
+
+const fn share_link_error_status_code(e: &ShareLinkError) -> StatusCode {
+    match e {
+        ShareLinkError::InvalidBearerToken => StatusCode::UNAUTHORIZED,
+        ShareLinkError::InsufficientScope => StatusCode::FORBIDDEN,
+        ShareLinkError::NoSuchArticleFoundById | ShareLinkError::NoSuchShareLinkFound => StatusCode::NOT_FOUND,
+    }
+}
+
+fn share_link_error_message(e: &ShareLinkError) -> String {
+    match e {
+        ShareLinkError::InvalidBearerToken => "You must be authorized to perform this action.".to_string(),
+        ShareLinkError::InsufficientScope => "This token is not allowed to perform this action.".to_string(),
+        ShareLinkError::NoSuchArticleFoundById => "Not found".to_string(),
+        ShareLinkError::NoSuchShareLinkFound => "No such share link".to_string(),
+    }
+}
+
+impl HttpStatusCode for CreateShareLinkResult {
+    fn call_status_code(&self) -> StatusCode {
+        match self {
+            Ok(_) => StatusCode::CREATED,
+            Err(e) => share_link_error_status_code(e),
+        }
+    }
+}
+
+impl ContainsHeaderMap for CreateShareLinkResult {
+    type Iterator = Empty<Pair>;
+
+    fn response_headers(&self) -> Self::Iterator {
+        empty()
+    }
+}
+
+impl IntoPlainText for CreateShareLinkResult {
+    fn into_plain_text(self) -> String {
+        match self {
+            Ok(created) => {
+                let expires_at = created.expires_at.map_or_else(|| "-".to_string(), |x| x.to_rfc3339());
+                format!("id: {}\ntoken: {}\nexpires_at: {expires_at}\n", created.id, created.token)
+            }
+            Err(e) => share_link_error_message(&e),
+        }
+    }
+}
+
+impl IntoJsonValue for CreateShareLinkResult {
+    fn into_json_value(self) -> serde_json::Value {
+        match self {
+            Ok(created) => serde_json::to_value(created).expect("bug: share link must be serializable"),
+            Err(e) => JsonMessage::Error(share_link_error_message(&e)).into_json_value(),
+        }
+    }
+}
+
+impl HttpStatusCode for ListShareLinksResult {
+    fn call_status_code(&self) -> StatusCode {
+        match self {
+            Ok(_) => StatusCode::OK,
+            Err(e) => share_link_error_status_code(e),
+        }
+    }
+}
+
+impl ContainsHeaderMap for ListShareLinksResult {
+    type Iterator = Empty<Pair>;
+
+    fn response_headers(&self) -> Self::Iterator {
+        empty()
+    }
+}
+
+impl IntoPlainText for ListShareLinksResult {
+    fn into_plain_text(self) -> String {
+        match self {
+            Ok(links) => {
+                links
+                    .into_iter()
+                    .map(|x| {
+                        let expires_at = x.expires_at.map_or_else(|| "-".to_string(), |x| x.to_rfc3339());
+                        format!("{} {} {expires_at}", x.id, x.created_at.to_rfc3339()) + "\n"
+                    })
+                    .collect()
+            }
+            Err(e) => share_link_error_message(&e),
+        }
+    }
+}
+
+impl IntoJsonValue for ListShareLinksResult {
+    fn into_json_value(self) -> serde_json::Value {
+        match self {
+            // ダイジェストは平文と同じく返さない
+            Ok(links) => links
+                .into_iter()
+                .map(|x| serde_json::json!({ "id": x.id, "created_at": x.created_at, "expires_at": x.expires_at }))
+                .collect(),
+            Err(e) => JsonMessage::Error(share_link_error_message(&e)).into_json_value(),
+        }
+    }
+}
+
+impl HttpStatusCode for RevokeShareLinkResult {
+    fn call_status_code(&self) -> StatusCode {
+        match self {
+            Ok(()) => StatusCode::NO_CONTENT,
+            Err(e) => share_link_error_status_code(e),
+        }
+    }
+}
+
+impl ContainsHeaderMap for RevokeShareLinkResult {
+    type Iterator = Empty<Pair>;
+
+    fn response_headers(&self) -> Self::Iterator {
+        empty()
+    }
+}
+
+impl IntoPlainText for RevokeShareLinkResult {
+    fn into_plain_text(self) -> String {
+        match self {
+            Ok(()) => "revoked".to_string(),
+            Err(e) => share_link_error_message(&e),
+        }
+    }
+}
+
+impl HttpStatusCode for GetAuditLogResult {
+    fn call_status_code(&self) -> StatusCode {
+        match self {
+            Ok(_) => StatusCode::OK,
+            Err(e) => {
+                match e {
+                    GetAuditLogError::InvalidBearerToken => StatusCode::UNAUTHORIZED,
+                    GetAuditLogError::InsufficientScope => StatusCode::FORBIDDEN,
+                }
+            }
+        }
+    }
+}
+
+impl ContainsHeaderMap for GetAuditLogResult {
+    type Iterator = Empty<Pair>;
+
+    fn response_headers(&self) -> Self::Iterator {
+        empty()
+    }
+}
+
+impl IntoPlainText for GetAuditLogResult {
+    fn into_plain_text(self) -> String {
+        match self {
+            // 監査ログと同じく、1行に1つのJSONとして返す
+            Ok(entries) => {
+                entries
+                    .into_iter()
+                    .map(|x| serde_json::to_string(&x).expect("audit entry must be serializable") + "\n")
+                    .collect()
+            }
+            Err(e) => {
+                match e {
+                    GetAuditLogError::InvalidBearerToken => "You must be authorized to perform this action.".to_string(),
+                    GetAuditLogError::InsufficientScope => "This token is not allowed to perform this action.".to_string(),
+                }
+            }
+        }
+    }
+}
+
+// 書き込みの結果はJSONでも返せる
+json_message!(
+    CreateArticleResult,
+    UpdateArticleResult,
+    DeleteArticleResult,
+    UpdateArticleMetadataResult,
+    UpdateTagsResult,
+    ChangeArticleIdRequestResult,
+    RevertArticleResult,
+    UpdateDraftResult,
+    SchedulePublicationResult,
+    RestoreArticleResult,
+    PurgeArticleResult,
+    RevokeShareLinkResult,
+);
+
+// 読み取りの結果は値をそのままJSONにする
+json_document!(
+    ListArticleRevisionsResult,
+    ListTrashResult,
+    ListScheduledPublicationsResult,
+    GetAuditLogResult,
+);
+
+pub(super) struct MaybeNotModified<Repr> {
+    pub(super) inner: Repr,
+    /// 真ならば、内容は省いて`304`を返す
+    pub(super) not_modified: bool,
+}
+
+impl<Repr: HttpStatusCode> HttpStatusCode for MaybeNotModified<Repr> {
+    fn call_status_code(&self) -> StatusCode {
+        if self.not_modified {
+            StatusCode::NOT_MODIFIED
+        } else {
+            self.inner.call_status_code()
+        }
+    }
+}
+
+impl<Repr: ContainsHeaderMap> ContainsHeaderMap for MaybeNotModified<Repr> {
+    type Iterator = Repr::Iterator;
+
+    fn response_headers(&self) -> Self::Iterator {
+        self.inner.response_headers()
+    }
+}
+
+impl<Repr: IntoPlainText> IntoPlainText for MaybeNotModified<Repr> {
+    fn into_plain_text(self) -> String {
+        // 304には本文を含めない (RFC 7232 § 4.1)
+        if self.not_modified {
+            String::new()
+        } else {
+            self.inner.into_plain_text()
+        }
+    }
+}
+
+impl<Repr: IntoJsonValue> IntoJsonValue for MaybeNotModified<Repr> {
+    fn into_json_value(self) -> serde_json::Value {
+        self.inner.into_json_value()
+    }
+}
+
+impl<Repr: Serialize> Serialize for MaybeNotModified<Repr> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        self.inner.serialize(serializer)
+    }
+}
+
+pub(super) struct ReportLastModofied<Repr> {
+    pub(super) inner: Repr,
+    pub(super) latest_updated: Option<HttpDate>,
+}
+
+impl<Repr: HttpStatusCode> HttpStatusCode for ReportLastModofied<Repr> {
+    fn call_status_code(&self) -> StatusCode {
+        self.inner.call_status_code()
+    }
+}
+
+impl<Repr: ContainsHeaderMap> ContainsHeaderMap for ReportLastModofied<Repr> {
+    type Iterator = Chain<Repr::Iterator, core::option::IntoIter<Pair>>;
+
+    fn response_headers(&self) -> Self::Iterator {
+        let existing = self.inner.response_headers();
+        
+        let to_be_chained = self.latest_updated.as_ref().map(|date| {
+            (
+                HeaderName::from_static("Last-Updated"),
+                HeaderValueUpdateMethod::Append(HeaderValue::from_str(&date.to_string()).expect("bug: http date"))
+            )
+        });
+        
+        existing.chain(to_be_chained)
+    }
+}
+
+impl<Repr: IntoPlainText> IntoPlainText for ReportLastModofied<Repr> {
+    fn into_plain_text(self) -> String {
+        self.inner.into_plain_text()
+    }
+}
+
+impl<Repr: Serialize> Serialize for ReportLastModofied<Repr> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        self.inner.serialize(serializer)
+    }
+}
+
+
+/// 2つ目は次のページを指す`Link`ヘッダーの値
+pub(super) struct ArticleIdCollectionResponseRepr(
+    pub(super) MaybeNotModified<ReportLastModofied<OwnedMetadata<ArticleListingResponseMetadata, ArticleListingResponseRepresentation>>>,
+    pub(super) Option<String>,
+);
+
+impl HttpStatusCode for ArticleIdCollectionResponseRepr {
+    fn call_status_code(&self) -> StatusCode {
+        if self.0.not_modified {
+            StatusCode::NOT_MODIFIED
+        } else {
+            StatusCode::OK
+        }
+    }
+}
+
+impl ContainsHeaderMap for ArticleIdCollectionResponseRepr {
+    type Iterator = VecIter<Pair>;
+
+    fn response_headers(&self) -> Self::Iterator {
+        let last_modified = self.0.inner.inner.metadata.newest_updated_at.map(|newest| {
+            let date = HttpFormattedDate::new(newest.with_timezone(newest.offset()));
+            (LAST_MODIFIED, HeaderValueUpdateMethod::Overwrite(date.to_string().try_into().unwrap()))
+        });
+        let next_page = self.1.as_ref().map(|link| {
+            (LINK, HeaderValueUpdateMethod::Overwrite(HeaderValue::from_str(link).expect("bug: link must be visible ASCII")))
+        });
+        let etag = etag_header(&listing_etag(&self.0.inner.inner.data, self.1.as_deref()));
+
+        last_modified.into_iter().chain(next_page).chain([etag]).collect::<Vec<_>>().into_iter()
+    }
+}
+
+impl Serialize for ArticleIdCollectionResponseRepr {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        self.0.inner.inner.data.serialize(serializer)
+    }
+}
+
+pub(super) struct SearchHitsRepr(pub(super) Vec<SearchHit>);
+
+impl HttpStatusCode for SearchHitsRepr {
+    fn call_status_code(&self) -> StatusCode {
+        StatusCode::OK
+    }
+}
+
+impl ContainsHeaderMap for SearchHitsRepr {
+    type Iterator = Empty<Pair>;
+
+    fn response_headers(&self) -> Self::Iterator {
+        empty()
+    }
+}
+
+impl Serialize for SearchHitsRepr {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        self.0.serialize(serializer)
+    }
+}
+
+pub(super) struct TagCountsRepr(pub(super) Vec<TagCount>);
+
+impl HttpStatusCode for TagCountsRepr {
+    fn call_status_code(&self) -> StatusCode {
+        StatusCode::OK
+    }
+}
+
+impl ContainsHeaderMap for TagCountsRepr {
+    type Iterator = Empty<Pair>;
 
     fn response_headers(&self) -> Self::Iterator {
         empty()
     }
 }
 
-impl Serialize for InternalErrorExposedRepr {
+impl Serialize for TagCountsRepr {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
-        self.0.to_string().serialize(serializer)
+        self.0.serialize(serializer)
     }
 }
@@ -26,4 +26,82 @@ fn rfc7232_example_jst() {
         ).to_string(),
         "Tue, 15 Nov 1994 12:45:26 GMT"
     );
-}
\ No newline at end of file
+}
+#[test]
+fn json_is_chosen_only_when_preferred_to_plain_text() {
+    use actix_web::http::header::ACCEPT;
+    use actix_web::test::TestRequest;
+
+    let prefers_json = |accept: Option<&str>| {
+        let request = accept.map_or_else(TestRequest::default, |x| TestRequest::default().insert_header((ACCEPT, x)));
+        super::prefers_json(&request.to_http_request())
+    };
+
+    assert!(!prefers_json(None));
+    assert!(!prefers_json(Some("*/*")));
+    assert!(!prefers_json(Some("text/plain, application/json")));
+    assert!(!prefers_json(Some("application/json;q=0.5, text/*")));
+    assert!(!prefers_json(Some("application/json;q=0")));
+    assert!(prefers_json(Some("application/json")));
+    assert!(prefers_json(Some("text/html, application/json;q=0.9, */*;q=0.8")));
+}
+
+#[test]
+fn write_results_are_json_messages() {
+    use actix_web::http::header::{ACCEPT, VARY};
+    use actix_web::http::StatusCode;
+    use actix_web::test::TestRequest;
+    use toy_blog_endpoint_model::{DeleteArticleError, DeleteArticleResult};
+    use crate::service::rest::inner_no_leak::ComposeInternalError;
+    use super::EndpointRepresentationCompiler;
+
+    let request = TestRequest::default().insert_header((ACCEPT, "application/json")).to_http_request();
+    let negotiate = |x: DeleteArticleResult| EndpointRepresentationCompiler::from_value(ComposeInternalError::Ok(x)).negotiate(&request);
+
+    let res = negotiate(Err(DeleteArticleError::NoSuchArticleFoundById));
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    assert_eq!(res.headers().get(VARY).unwrap(), "Accept");
+    assert_eq!(res.body(), r#"{"error":"Not found"}"#);
+
+    let res = negotiate(Ok(()));
+    assert!(res.status().is_success());
+    assert!(res.body().starts_with(r#"{"message":"#));
+}
+
+#[test]
+fn read_results_are_json_documents() {
+    use actix_web::http::header::{ACCEPT, VARY};
+    use actix_web::http::StatusCode;
+    use actix_web::test::TestRequest;
+    use chrono::Local;
+    use toy_blog_endpoint_model::{ArticleShareLink, DiffArticleRevisionResult, ListShareLinksResult, ListTrashError, ListTrashResult, ShareLinkId, UnifiedDiff};
+    use crate::service::rest::inner_no_leak::ComposeInternalError;
+    use super::{ContainsHeaderMap, EndpointRepresentationCompiler, HttpStatusCode, IntoJsonValue, IntoPlainText};
+
+    fn negotiate<T: IntoPlainText + IntoJsonValue + HttpStatusCode + ContainsHeaderMap>(x: T) -> (StatusCode, serde_json::Value) {
+        let request = TestRequest::default().insert_header((ACCEPT, "application/json")).to_http_request();
+        let res = EndpointRepresentationCompiler::from_value(ComposeInternalError::Ok(x)).negotiate(&request);
+        assert_eq!(res.headers().get(VARY).unwrap(), "Accept");
+
+        (res.status(), serde_json::from_str(res.body()).unwrap())
+    }
+
+    let links: ListShareLinksResult = Ok(vec![ArticleShareLink {
+        id: ShareLinkId("link".to_string()),
+        digest: "secret digest".to_string(),
+        created_at: Local::now(),
+        expires_at: None,
+    }]);
+    let (status, body) = negotiate(links);
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body[0]["id"], "link");
+    assert!(body[0]["expires_at"].is_null());
+    assert!(body[0].get("digest").is_none(), "digest must not be exposed");
+
+    let diff: DiffArticleRevisionResult = Ok(UnifiedDiff("-a\n+b\n".to_string()));
+    assert_eq!(negotiate(diff).1, serde_json::json!({ "diff": "-a\n+b\n" }));
+
+    let (status, body) = negotiate::<ListTrashResult>(Err(ListTrashError::InsufficientScope));
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert!(body["error"].is_string());
+}
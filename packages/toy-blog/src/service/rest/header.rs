@@ -3,7 +3,7 @@ use std::future::Ready;
 use std::str::FromStr;
 use actix_web::{FromRequest, HttpRequest};
 use actix_web::dev::Payload;
-use actix_web::http::header::{HeaderValue, ToStrError};
+use actix_web::http::header::{HeaderName, HeaderValue, IF_MODIFIED_SINCE, IF_UNMODIFIED_SINCE, ToStrError};
 use chrono::{DateTime, FixedOffset, ParseError, TimeZone};
 use thiserror::Error;
 
@@ -52,9 +52,9 @@ impl Display for HttpDate {
 }
 
 #[derive(Eq, PartialEq, Debug)]
-pub struct LastModified(pub HttpDate);
+pub struct IfModifiedSince(pub HttpDate);
 
-impl TryFrom<&HeaderValue> for LastModified {
+impl TryFrom<&HeaderValue> for IfModifiedSince {
     type Error = HttpDateParseError;
 
     fn try_from(value: &HeaderValue) -> Result<Self, Self::Error> {
@@ -62,26 +62,19 @@ impl TryFrom<&HeaderValue> for LastModified {
     }
 }
 
-impl FromRequest for LastModified {
+impl FromRequest for IfModifiedSince {
     type Error = HttpDateExtractionError;
     type Future = Ready<Result<Self, Self::Error>>;
 
     fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
-        let inner = || {
-            let w = req.headers().get("Last-Modified")
-                .ok_or(HttpDateExtractionError::NotFound)?;
-            let r = Self::try_from(w)?;
-            Ok(r)
-        };
-
-        std::future::ready(inner())
+        std::future::ready(extract_date_header(req, IF_MODIFIED_SINCE))
     }
 }
 
 #[derive(Eq, PartialEq, Debug)]
-pub struct IfModifiedSince(pub HttpDate);
+pub struct IfUnmodifiedSince(pub HttpDate);
 
-impl TryFrom<&HeaderValue> for IfModifiedSince {
+impl TryFrom<&HeaderValue> for IfUnmodifiedSince {
     type Error = HttpDateParseError;
 
     fn try_from(value: &HeaderValue) -> Result<Self, Self::Error> {
@@ -89,32 +82,28 @@ impl TryFrom<&HeaderValue> for IfModifiedSince {
     }
 }
 
-impl FromRequest for IfModifiedSince {
+impl FromRequest for IfUnmodifiedSince {
     type Error = HttpDateExtractionError;
     type Future = Ready<Result<Self, Self::Error>>;
 
     fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
-        let w = req.headers().get("If-Modified-Since")
-            .ok_or(HttpDateExtractionError::NotFound);
-        let w = match w {
-            Ok(t) => t,
-            Err(e) => return std::future::ready(Err(e)),
-        };
-
-        let r = Self::try_from(w);
-        let r = match r {
-            Ok(t) => t,
-            Err(e) => return std::future::ready(Err(HttpDateExtractionError::from(e))),
-        };
-
-        std::future::ready(Ok(r))
+        std::future::ready(extract_date_header(req, IF_UNMODIFIED_SINCE))
     }
 }
 
+fn extract_date_header<T>(req: &HttpRequest, name: HeaderName) -> Result<T, HttpDateExtractionError>
+where
+    T: for<'a> TryFrom<&'a HeaderValue, Error = HttpDateParseError>,
+{
+    let value = req.headers().get(&name).ok_or(HttpDateExtractionError::NotFound(name))?;
+
+    Ok(T::try_from(value)?)
+}
+
 #[derive(Error, Debug)]
 pub enum HttpDateExtractionError {
-    #[error("request does not have Last-Modified header")]
-    NotFound,
+    #[error("request does not have {0} header")]
+    NotFound(HeaderName),
     #[error("header value is malformed: {0}")]
     ParseFailure(#[from] HttpDateParseError),
 }
@@ -140,12 +129,12 @@ mod tests {
         assert_eq!(parsed.0, expected);
     }
     #[test]
-    #[should_panic]
+    #[should_panic(expected = "failed to parse")]
     fn too_long_should_fail() {
         let _ = HttpDate::from_str("Tue, 15 Nov 1994 12:45:26 GMT1").expect("failed to parse");
     }
     #[test]
-    #[should_panic]
+    #[should_panic(expected = "failed to parse")]
     fn too_short_should_fail() {
         let _ = HttpDate::from_str("Tue").expect("failed to parse");
     }
@@ -0,0 +1,183 @@
+use std::convert::Infallible;
+use std::future::{ready, Ready};
+use actix_web::{FromRequest, HttpRequest};
+use actix_web::dev::Payload;
+use actix_web::http::header::{Header, IfMatch, IF_MATCH};
+use actix_web::web::Data;
+use chrono::SubsecRound;
+use toy_blog_endpoint_model::{Article, ArticleId, ChangeArticleIdError, DeleteArticleError, UpdateArticleError};
+use crate::service::rest::exposed_representation_format::{article_document, content_etag, document_etag};
+use crate::service::rest::header::IfUnmodifiedSince;
+
+/// 前提条件の無い書き込みを受け付けるかどうか。`App`に登録されていなければ受け付ける。
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct PreconditionPolicy {
+    pub required: bool,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum PreconditionError {
+    /// `412 Precondition Failed`
+    Failed,
+    /// `428 Precondition Required`
+    Required,
+}
+
+/// 書き込みのリクエストに付けられた`If-Match`と`If-Unmodified-Since`。
+///
+/// 同じ記事を同時に編集した際に、他人の変更を黙って上書きしないために使う。
+#[derive(Debug)]
+pub struct WritePreconditions {
+    /// 解釈できない`If-Match`はどの`ETag`とも一致しない`Items`として扱う
+    if_match: Option<IfMatch>,
+    if_unmodified_since: Option<IfUnmodifiedSince>,
+    required: bool,
+}
+
+impl WritePreconditions {
+    /// 変更前の`article`に対して前提条件を評価する。
+    ///
+    /// RFC 7232 § 6に従い、`If-Match`があれば`If-Unmodified-Since`は見ない。`If-Match`は強い比較で評価し、
+    /// 平文とJSONのどちらの表現の`ETag`と一致してもよい。
+    pub fn evaluate(&self, article_id: &ArticleId, article: &Article) -> Result<(), PreconditionError> {
+        let satisfied = match (&self.if_match, &self.if_unmodified_since) {
+            (None, None) if self.required => return Err(PreconditionError::Required),
+            (Some(IfMatch::Any), _) | (None, None) => true,
+            (Some(IfMatch::Items(tags)), _) => {
+                let etags = [
+                    content_etag(&article.content),
+                    document_etag(&article_document(article_id.clone(), article.clone())),
+                ];
+                tags.iter().any(|x| etags.iter().any(|etag| x.strong_eq(etag)))
+            }
+            // HTTP-dateは秒単位なので、秒未満を切り捨ててから比べる
+            (None, Some(if_unmodified_since)) => article.updated_at.trunc_subsecs(0) <= if_unmodified_since.0.0,
+        };
+
+        if satisfied {
+            Ok(())
+        } else {
+            Err(PreconditionError::Failed)
+        }
+    }
+}
+
+impl FromRequest for WritePreconditions {
+    type Error = Infallible;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let if_match = req.headers().contains_key(IF_MATCH)
+            .then(|| IfMatch::parse(req).unwrap_or(IfMatch::Items(vec![])));
+        // 日付として解釈できない`If-Unmodified-Since`は無視する (RFC 7232 § 3.4)
+        let if_unmodified_since = IfUnmodifiedSince::from_request(req, payload).into_inner().ok();
+        let required = req.app_data::<Data<PreconditionPolicy>>().is_some_and(|x| x.required);
+
+        ready(Ok(Self { if_match, if_unmodified_since, required }))
+    }
+}
+
+macro_rules! from_precondition_error {
+    ($($error:ident),* $(,)?) => {
+        $(
+            impl From<PreconditionError> for $error {
+                fn from(value: PreconditionError) -> Self {
+                    match value {
+                        PreconditionError::Failed => Self::PreconditionFailed,
+                        PreconditionError::Required => Self::PreconditionRequired,
+                    }
+                }
+            }
+        )*
+    };
+}
+
+from_precondition_error!(
+    UpdateArticleError,
+    DeleteArticleError,
+    ChangeArticleIdError,
+);
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+    use actix_web::FromRequest;
+    use actix_web::http::header::{IF_MATCH, IF_UNMODIFIED_SINCE};
+    use actix_web::test::TestRequest;
+    use actix_web::web::Data;
+    use chrono::{Local, TimeDelta};
+    use toy_blog_endpoint_model::{Article, ArticleId, Visibility};
+    use crate::service::rest::exposed_representation_format::{article_document, content_etag, document_etag};
+    use crate::service::rest::header::HttpDate;
+    use super::{PreconditionError, PreconditionPolicy, WritePreconditions};
+
+    fn article() -> Article {
+        Article {
+            created_at: Local::now(),
+            updated_at: Local::now(),
+            content: "hello".to_string(),
+            visibility: Visibility::Public,
+            created_by: None,
+            updated_by: None,
+            tags: BTreeSet::new(),
+            title: None,
+            summary: None,
+            lang: None,
+            publish_at: None,
+            draft: None,
+        }
+    }
+
+    fn evaluate_on(request: TestRequest, article: &Article) -> Result<(), PreconditionError> {
+        let (req, mut payload) = request.to_http_parts();
+        WritePreconditions::from_request(&req, &mut payload).into_inner().unwrap().evaluate(&ArticleId::new("x".to_string()), article)
+    }
+
+    fn evaluate(request: TestRequest) -> Result<(), PreconditionError> {
+        evaluate_on(request, &article())
+    }
+
+    #[test]
+    fn if_match_uses_strong_comparison() {
+        let etag = content_etag("hello").to_string();
+
+        assert_eq!(evaluate(TestRequest::default().insert_header((IF_MATCH, etag.as_str()))), Ok(()));
+        assert_eq!(evaluate(TestRequest::default().insert_header((IF_MATCH, "*"))), Ok(()));
+        assert_eq!(evaluate(TestRequest::default().insert_header((IF_MATCH, format!("W/{etag}")))), Err(PreconditionError::Failed));
+        assert_eq!(evaluate(TestRequest::default().insert_header((IF_MATCH, "\"other\""))), Err(PreconditionError::Failed));
+        assert_eq!(evaluate(TestRequest::default().insert_header((IF_MATCH, "garbage"))), Err(PreconditionError::Failed));
+    }
+
+    #[test]
+    fn if_match_accepts_etag_of_json_representation() {
+        let article = article();
+        let etag = document_etag(&article_document(ArticleId::new("x".to_string()), article.clone())).to_string();
+
+        assert_eq!(evaluate_on(TestRequest::default().insert_header((IF_MATCH, etag.as_str())), &article), Ok(()));
+        assert_eq!(evaluate_on(TestRequest::default().insert_header((IF_MATCH, format!("W/{etag}"))), &article), Err(PreconditionError::Failed));
+    }
+
+    #[test]
+    fn if_unmodified_since_is_ignored_when_if_match_is_present() {
+        let hour_ago = HttpDate::try_from(Local::now() - TimeDelta::hours(1)).unwrap().to_string();
+        let hour_later = HttpDate::try_from(Local::now() + TimeDelta::hours(1)).unwrap().to_string();
+
+        assert_eq!(evaluate(TestRequest::default().insert_header((IF_UNMODIFIED_SINCE, hour_later.as_str()))), Ok(()));
+        assert_eq!(evaluate(TestRequest::default().insert_header((IF_UNMODIFIED_SINCE, hour_ago.as_str()))), Err(PreconditionError::Failed));
+        assert_eq!(
+            evaluate(TestRequest::default().insert_header((IF_MATCH, "*")).insert_header((IF_UNMODIFIED_SINCE, hour_ago.as_str()))),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn precondition_is_required_only_by_policy() {
+        let required = || TestRequest::default().app_data(Data::new(PreconditionPolicy { required: true }));
+
+        assert_eq!(evaluate(TestRequest::default()), Ok(()));
+        assert_eq!(evaluate(required()), Err(PreconditionError::Required));
+        // 解釈できない日付は無いものとして扱う
+        assert_eq!(evaluate(required().insert_header((IF_UNMODIFIED_SINCE, "yesterday"))), Err(PreconditionError::Required));
+        assert_eq!(evaluate(required().insert_header((IF_MATCH, "*"))), Ok(()));
+    }
+}
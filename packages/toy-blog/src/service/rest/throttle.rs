@@ -0,0 +1,151 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use log::warn;
+
+/// 待たされずに認証に失敗できる回数
+const FREE_ATTEMPTS: u32 = 3;
+/// 認証の失敗が続いたときに待たせる時間の上限
+const MAX_BACKOFF: Duration = Duration::from_mins(1);
+/// この回数だけ認証に失敗すると締め出す
+const LOCKOUT_THRESHOLD: u32 = 10;
+const LOCKOUT_DURATION: Duration = Duration::from_mins(15);
+/// 最後の失敗からこの時間が経つと、失敗の回数を忘れる
+const FAILURE_WINDOW: Duration = Duration::from_hours(1);
+/// 記録しているアドレスがこの数を超えたら、古い記録を捨てる
+const PRUNE_THRESHOLD: usize = 4096;
+
+#[derive(Copy, Clone, Debug)]
+struct FailureRecord {
+    count: u32,
+    last_failure: Instant,
+    /// 次に認証を試みてよい時刻
+    retry_at: Instant,
+}
+
+/// 認証を試みてよいかどうか
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Verdict {
+    Allowed,
+    /// この時間が経つまでは`429`を返す
+    TooManyAttempts { retry_after: Duration },
+}
+
+/// クライアントのアドレスごとに認証の失敗を数え、失敗が続いたら認証を試みさせない。
+///
+/// 失敗が[`FREE_ATTEMPTS`]回を超えると、次に試みるまで指数関数的に伸びる時間だけ待たせる。
+/// [`LOCKOUT_THRESHOLD`]回に達すると[`LOCKOUT_DURATION`]の間締め出す。認証に成功すると記録は消える。
+#[derive(Debug, Default)]
+pub struct AuthFailureThrottle {
+    records: Mutex<HashMap<IpAddr, FailureRecord>>,
+}
+
+impl AuthFailureThrottle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn check(&self, ip: IpAddr) -> Verdict {
+        self.check_at(ip, Instant::now())
+    }
+
+    fn check_at(&self, ip: IpAddr, now: Instant) -> Verdict {
+        let records = self.records.lock().expect("throttle is poisoned");
+        match records.get(&ip) {
+            Some(record) if now < record.retry_at => Verdict::TooManyAttempts { retry_after: record.retry_at - now },
+            _ => Verdict::Allowed,
+        }
+    }
+
+    pub fn record_failure(&self, ip: IpAddr) {
+        self.record_failure_at(ip, Instant::now());
+    }
+
+    fn record_failure_at(&self, ip: IpAddr, now: Instant) {
+        let mut records = self.records.lock().expect("throttle is poisoned");
+        if records.len() > PRUNE_THRESHOLD {
+            records.retain(|_, record| now < record.retry_at || now.saturating_duration_since(record.last_failure) < FAILURE_WINDOW);
+        }
+
+        let count = match records.get(&ip) {
+            Some(record) if now.saturating_duration_since(record.last_failure) < FAILURE_WINDOW => record.count + 1,
+            _ => 1,
+        };
+
+        let retry_at = if count >= LOCKOUT_THRESHOLD {
+            warn!("locking out {ip} for {}s after {count} failed authentication attempts", LOCKOUT_DURATION.as_secs());
+            now + LOCKOUT_DURATION
+        } else if count > FREE_ATTEMPTS {
+            now + backoff(count - FREE_ATTEMPTS)
+        } else {
+            now
+        };
+
+        // 締め出した後に失敗した場合は、最初から数え直す
+        let count = if count >= LOCKOUT_THRESHOLD { 0 } else { count };
+        records.insert(ip, FailureRecord { count, last_failure: now, retry_at });
+    }
+
+    pub fn record_success(&self, ip: IpAddr) {
+        self.records.lock().expect("throttle is poisoned").remove(&ip);
+    }
+}
+
+/// `excess`回目の超過に対して待たせる時間。1秒から倍々に伸び、[`MAX_BACKOFF`]で頭打ちになる。
+fn backoff(excess: u32) -> Duration {
+    Duration::from_secs(1u64.checked_shl(excess - 1).unwrap_or(u64::MAX)).min(MAX_BACKOFF)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::time::{Duration, Instant};
+    use super::{AuthFailureThrottle, backoff, FREE_ATTEMPTS, LOCKOUT_DURATION, LOCKOUT_THRESHOLD, MAX_BACKOFF, Verdict};
+
+    const CLIENT: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+    const OTHER: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 2));
+
+    #[test]
+    fn backoff_doubles_until_cap() {
+        assert_eq!(backoff(1), Duration::from_secs(1));
+        assert_eq!(backoff(2), Duration::from_secs(2));
+        assert_eq!(backoff(3), Duration::from_secs(4));
+        assert_eq!(backoff(64), MAX_BACKOFF);
+    }
+
+    #[test]
+    fn repeated_failures_lead_to_lockout() {
+        let throttle = AuthFailureThrottle::new();
+        let mut now = Instant::now();
+
+        for _ in 0..FREE_ATTEMPTS {
+            throttle.record_failure_at(CLIENT, now);
+            assert_eq!(throttle.check_at(CLIENT, now), Verdict::Allowed);
+        }
+
+        throttle.record_failure_at(CLIENT, now);
+        assert_eq!(throttle.check_at(CLIENT, now), Verdict::TooManyAttempts { retry_after: Duration::from_secs(1) });
+        assert_eq!(throttle.check_at(OTHER, now), Verdict::Allowed);
+
+        for _ in FREE_ATTEMPTS + 1..LOCKOUT_THRESHOLD {
+            now += MAX_BACKOFF;
+            throttle.record_failure_at(CLIENT, now);
+        }
+        assert_eq!(throttle.check_at(CLIENT, now), Verdict::TooManyAttempts { retry_after: LOCKOUT_DURATION });
+        assert_eq!(throttle.check_at(CLIENT, now + LOCKOUT_DURATION), Verdict::Allowed);
+    }
+
+    #[test]
+    fn success_clears_failures() {
+        let throttle = AuthFailureThrottle::new();
+        let now = Instant::now();
+        for _ in 0..=FREE_ATTEMPTS {
+            throttle.record_failure_at(CLIENT, now);
+        }
+        assert_ne!(throttle.check_at(CLIENT, now), Verdict::Allowed);
+
+        throttle.record_success(CLIENT);
+        assert_eq!(throttle.check_at(CLIENT, now), Verdict::Allowed);
+    }
+}
@@ -0,0 +1,143 @@
+use std::path::Path;
+use anyhow::bail;
+use chrono::{Local, TimeDelta};
+use crate::service::hash_token::generate_token;
+use crate::service::persistence::replace_file_atomically;
+use crate::service::rest::auth::{hash_token, read_token_file, Role, TokenEntry, TokenScope};
+use crate::service::rest::TOKEN_FILE_PATH;
+
+fn write_token_file(path: &Path, entries: &[TokenEntry]) -> Result<(), anyhow::Error> {
+    let mut json = serde_json::to_vec_pretty(entries)?;
+    json.push(b'\n');
+    replace_file_atomically(path, &json)?;
+
+    Ok(())
+}
+
+fn print_reload_hint() {
+    println!("send SIGHUP to the running server to reload {TOKEN_FILE_PATH}.");
+}
+
+/// `name`という名前のトークンを作り、`entries`に加える。作ったトークンを返す。
+fn add_token_to(entries: &mut Vec<TokenEntry>, name: &str, scopes: Vec<TokenScope>, role: Role) -> Result<String, anyhow::Error> {
+    if entries.iter().any(|x| x.name == name) {
+        bail!("token {name} already exists. Use rotate-token to replace it.")
+    }
+
+    let token = generate_token();
+    entries.push(TokenEntry {
+        name: name.to_string(),
+        hash: hash_token(&token)?,
+        scopes,
+        role,
+        expires_at: None,
+    });
+
+    Ok(token)
+}
+
+/// `name`という名前のトークンを新しく作り直し、作ったトークンを返す。
+///
+/// 古いトークンは`overlap`が経つまで受け付けられる。期限の切れたトークンはこの時に取り除かれる。
+fn rotate_token_in(entries: &mut Vec<TokenEntry>, name: &str, overlap: TimeDelta) -> Result<String, anyhow::Error> {
+    let now = Local::now();
+    entries.retain(|x| !x.is_expired_at(now));
+
+    let mut current = entries.iter_mut().filter(|x| x.name == name).peekable();
+    let Some((scopes, role)) = current.peek().map(|x| (x.scopes.clone(), x.role)) else {
+        bail!("no such token: {name}")
+    };
+    let expires_at = now + overlap;
+    for old in current {
+        old.expires_at = Some(old.expires_at.map_or(expires_at, |x| x.min(expires_at)));
+    }
+
+    let token = generate_token();
+    entries.push(TokenEntry {
+        name: name.to_string(),
+        hash: hash_token(&token)?,
+        scopes,
+        role,
+        expires_at: None,
+    });
+
+    Ok(token)
+}
+
+/// `add-token`サブコマンド。
+pub fn add_token(name: &str, scopes: Vec<TokenScope>, role: Role) -> Result<(), anyhow::Error> {
+    let path = Path::new(TOKEN_FILE_PATH);
+    let mut entries = read_token_file(path)?;
+    let token = add_token_to(&mut entries, name, scopes, role)?;
+    write_token_file(path, &entries)?;
+
+    println!("token: {token}");
+    print_reload_hint();
+
+    Ok(())
+}
+
+/// `rotate-token`サブコマンド。
+pub fn rotate_token(name: &str, overlap_minutes: u32) -> Result<(), anyhow::Error> {
+    let path = Path::new(TOKEN_FILE_PATH);
+    let mut entries = read_token_file(path)?;
+    let token = rotate_token_in(&mut entries, name, TimeDelta::minutes(overlap_minutes.into()))?;
+    write_token_file(path, &entries)?;
+
+    println!("token: {token}");
+    println!("the old token of {name} is accepted for {overlap_minutes} more minute(s).");
+    print_reload_hint();
+
+    Ok(())
+}
+
+/// `revoke-token`サブコマンド。同じ名前のトークンは入れ替え中のものも含めて全て取り除かれる。
+pub fn revoke_token(name: &str) -> Result<(), anyhow::Error> {
+    let path = Path::new(TOKEN_FILE_PATH);
+    let mut entries = read_token_file(path)?;
+    let before = entries.len();
+    entries.retain(|x| x.name != name);
+    if entries.len() == before {
+        bail!("no such token: {name}")
+    }
+    write_token_file(path, &entries)?;
+
+    println!("revoked {name}.");
+    print_reload_hint();
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Local, TimeDelta};
+    use crate::service::rest::auth::{Role, TokenEntry, TokenScope};
+    use crate::service::token::{add_token_to, rotate_token_in};
+
+    fn entry(name: &str, expires_at: Option<chrono::DateTime<Local>>) -> TokenEntry {
+        TokenEntry { name: name.to_string(), hash: String::new(), scopes: vec![TokenScope::ArticleWrite], role: Role::Author, expires_at }
+    }
+
+    #[test]
+    fn rotation_keeps_old_token_for_overlap() {
+        let mut entries = vec![entry("ci", None), entry("gone", Some(Local::now() - TimeDelta::minutes(1)))];
+        let before = Local::now();
+        rotate_token_in(&mut entries, "ci", TimeDelta::minutes(30)).unwrap();
+
+        assert_eq!(entries.len(), 2, "expired token should be dropped");
+        let expires_at = entries[0].expires_at.unwrap();
+        assert!(before + TimeDelta::minutes(30) <= expires_at && expires_at <= Local::now() + TimeDelta::minutes(30));
+        assert_eq!(entries[1].name, "ci");
+        assert_eq!(entries[1].scopes, [TokenScope::ArticleWrite]);
+        assert_eq!(entries[1].role, Role::Author);
+        assert_eq!(entries[1].expires_at, None);
+        assert!(rotate_token_in(&mut entries, "absent", TimeDelta::zero()).is_err());
+    }
+
+    #[test]
+    fn duplicated_name_is_rejected() {
+        let mut entries = vec![entry("ci", None)];
+        assert!(add_token_to(&mut entries, "ci", vec![], Role::Author).is_err());
+        assert_eq!(entries.len(), 1);
+    }
+}